@@ -1,15 +1,94 @@
-use crate::window::{Display, Window, WindowType};
+use crate::label::Label;
+use crate::layout::{Align, Edges, LayoutOptions, Placement};
+use crate::module::{Module, Modules};
+use crate::window::color::{Color, ColorRgba32};
+use crate::window::font::{FontDatabase, FontHandle};
+use crate::window::{codec, Display, Image, Surface, Window, WindowType};
 use crate::BarError;
+use std::path::Path;
 use std::pin::Pin;
+use std::time::Instant;
+
+/// The height of the bar in pixels
+pub const BAR_HEIGHT: u64 = 40;
+
+/// The font descriptions tried in order for the text of the bar
+const FONTS: [&str; 3] = [
+    "DejaVu Sans Mono 10",
+    "Liberation Mono 10",
+    "Noto Sans Mono 10",
+];
+
+fn background() -> ColorRgba32 {
+    ColorRgba32::from_rgba8(0x20, 0x1c, 0x1a, 0xff)
+}
+
+/// Loads the first of [`FONTS`] that is installed
+pub fn load_font() -> Result<FontHandle, BarError> {
+    let db = FontDatabase::scan_system();
+    FONTS
+        .iter()
+        .find_map(|spec| db.load_fallbacks(&[spec]).ok())
+        .ok_or_else(|| {
+            BarError(format!(
+                "None of the fonts {} is installed",
+                FONTS.join(", ")
+            ))
+        })
+}
+
+/// The modules the bar shows, both in its window and in rendered files
+pub fn modules(font: FontHandle) -> Modules {
+    let mut modules = Modules::with_options(LayoutOptions {
+        spacing: 4,
+        ..LayoutOptions::default()
+    });
+    let title = Label::new(
+        "coffee bar",
+        font,
+        ColorRgba32::from_rgba8(0xd0, 0xc8, 0xb8, 0xff),
+    )
+    .background(ColorRgba32::from_rgba8(0x40, 0x38, 0x30, 0xff));
+    modules.push(
+        Box::new(title),
+        Placement::new(Align::Left).padding(Edges::symmetric(8, 0)),
+    );
+    modules
+}
+
+/// Lays out the modules and draws them onto a frame filling the whole image
+pub fn draw_frame(frame: &mut Image<ColorRgba32>, modules: &mut Modules) -> Result<(), BarError> {
+    let (width, height) = frame.get_size();
+    frame.fill(background());
+    modules.layout(width as u32, height as u32);
+    modules.render(frame).map_err(BarError::from_dis)
+}
+
+/// Renders a single frame of the modules into an image file instead of opening a window.
+/// The format is chosen by the file extension.
+pub fn render_to_file(
+    path: &Path,
+    width: u64,
+    height: u64,
+    modules: &mut Modules,
+) -> Result<(), BarError> {
+    let mut image = Image::<ColorRgba32>::new(width, height)
+        .ok_or_else(|| BarError(format!("Can not render a {}x{} frame", width, height)))?;
+    modules.update_due(Instant::now());
+    draw_frame(&mut image, modules)?;
+    codec::save(&image, path).map_err(BarError::from_dis)
+}
 
 pub struct Bar<D: Display, W: Window<'static, D>> {
     dis: Pin<Box<D>>,
     win: W,
+    modules: Modules,
     _pin: std::marker::PhantomPinned,
 }
 
 impl<D: Display + 'static, W: Window<'static, D>> Bar<D, W> {
     pub fn new() -> Result<Self, BarError> {
+        let modules = modules(load_font()?);
         let dis = D::new().map_err(BarError::from_dis)?;
 
         let size = dis
@@ -22,7 +101,7 @@ impl<D: Display + 'static, W: Window<'static, D>> Bar<D, W> {
             .new_window_builder()
             .title(String::from("coffee bar"))
             .pos(0, 0)
-            .size(size.0, 40)
+            .size(size.0, BAR_HEIGHT)
             .transparency(true)
             .window_type(WindowType::Docking)
             .build()
@@ -31,23 +110,83 @@ impl<D: Display + 'static, W: Window<'static, D>> Bar<D, W> {
         Ok(Self {
             dis: dis.into(),
             win,
+            modules,
             _pin: std::marker::PhantomPinned,
         })
     }
 
-    pub fn main_loop(self) -> Result<(), BarError> {
-        for event in self.win {
-            let event = match event {
-                Ok(v) => v,
-                Err(e) => {
-                    println!("warning: {}", e);
-                    continue;
-                }
+    /// Appends a module after the other ones of its zone
+    pub fn push_module(&mut self, module: Box<dyn Module>, placement: Placement) {
+        self.modules.push(module, placement);
+    }
+
+    /// Sets the spacing, separators and collision strategy of the layout
+    pub fn set_layout_options(&mut self, options: LayoutOptions) {
+        self.modules.set_options(options);
+    }
+
+    fn redraw(&mut self) -> Result<(), BarError> {
+        draw_frame(self.win.canvas(), &mut self.modules)?;
+        self.win.present().map_err(BarError::from_dis)
+    }
+
+    /// Updates the modules when they are due and passes input to them,
+    /// redrawing the bar whenever one of them changed
+    pub fn main_loop(mut self) -> Result<(), BarError> {
+        self.modules.update_due(Instant::now());
+        self.redraw()?;
+        loop {
+            let timeout = self
+                .modules
+                .next_update()
+                .map(|next| next.saturating_duration_since(Instant::now()));
+            let changed = match self.win.wait_event(timeout).map_err(BarError::from_dis)? {
+                Some(event) => self.modules.handle_event(&event),
+                None => false,
             };
-            println!("{:?}", event);
+            if self.modules.update_due(Instant::now()) || changed {
+                self.redraw()?;
+            }
         }
-        Ok(())
     }
 }
 
 pub type X11Bar = Bar<crate::window::xwindow::Display, crate::window::xwindow::Window<'static>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> FontHandle {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/font.bdf");
+        FontHandle::load(path, 8.0).unwrap()
+    }
+
+    #[test]
+    fn frames_show_the_bar_modules() {
+        let mut image = Image::new(200, 20).unwrap();
+        // nothing of a previous frame is left
+        image.fill(ColorRgba32::from_rgba8(255, 0, 0, 255));
+        draw_frame(&mut image, &mut modules(font())).unwrap();
+        let pixel = |x, y| image.get_pixel_at(x, y).unwrap().clone();
+        assert_eq!(pixel(199, 0), background());
+        assert_eq!(pixel(199, 19), background());
+        // the title on the left
+        let title = ColorRgba32::from_rgba8(0x40, 0x38, 0x30, 0xff);
+        assert_eq!(pixel(1, 10), title);
+        let text = (8..20).flat_map(|x| (0..20).map(move |y| (x, y)));
+        let text = text.filter(|&(x, y)| pixel(x, y) != title && pixel(x, y) != background());
+        assert!(text.count() > 0);
+    }
+
+    #[test]
+    fn rendered_files_show_the_bar_modules() {
+        let path = std::env::temp_dir().join(format!("coffee-bar-{}.png", std::process::id()));
+        render_to_file(&path, 200, 20, &mut modules(font())).unwrap();
+        let rendered: Image<ColorRgba32> = codec::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut frame = Image::new(200, 20).unwrap();
+        draw_frame(&mut frame, &mut modules(font())).unwrap();
+        assert_eq!(rendered.data(), frame.data());
+    }
+}
@@ -0,0 +1,479 @@
+//! Golden image tests, which render the bar with fixed fake content offscreen
+//! and compare the frames against the reference images in `tests/golden`.
+//!
+//! After an intended change of the rendering, run the tests with
+//! `COFFEE_BAR_UPDATE_GOLDEN=1` to rewrite the references and review them like code.
+//! On a mismatch the rendered frame and a diff image are written to `target/golden`.
+
+use crate::bar;
+use crate::label::{Label, Overflow};
+use crate::layout::{Align, Collision, Edges, LayoutOptions, Placement, SegmentStyle, Separator};
+use crate::module::{Module, Modules};
+use crate::window::codec;
+use crate::window::color::{Color, ColorRgba32};
+use crate::window::draw::{
+    Circle, CircleArc, DrawCommand, FillRule, GradientStop, Line, LineInfo, LinearGradient, Path,
+    RadialGradient, Radii, Rect, Text,
+};
+use crate::window::font::{FontHandle, Truncate};
+use crate::window::{Image, ImageDraw, ImageDrawError, Surface};
+use std::path::PathBuf;
+
+const REFERENCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/golden");
+const UPDATE_VAR: &str = "COFFEE_BAR_UPDATE_GOLDEN";
+
+/// The outcome of comparing a rendered frame to its reference
+struct Comparison {
+    /// The number of pixels with a channel differing by more than the tolerance
+    mismatches: usize,
+    /// The largest difference of a single channel
+    max_delta: u8,
+    /// The reference faded to gray with mismatching pixels in red
+    diff: Image<ColorRgba32>,
+}
+
+fn pixel(image: &Image<ColorRgba32>, x: u64, y: u64) -> ColorRgba32 {
+    image.get_pixel_at(x, y).cloned().unwrap_or_default()
+}
+
+/// Compares two images channel by channel, returns `None` if their sizes differ
+fn compare(
+    actual: &Image<ColorRgba32>,
+    reference: &Image<ColorRgba32>,
+    tolerance: u8,
+) -> Option<Comparison> {
+    if actual.get_size() != reference.get_size() {
+        return None;
+    }
+    let (width, height) = actual.get_size();
+    let mut diff = Image::new(width, height)?;
+    let (mut mismatches, mut max_delta) = (0, 0);
+    for y in 0..height {
+        for x in 0..width {
+            let (a, r) = (pixel(actual, x, y), pixel(reference, x, y));
+            let delta = [
+                a.r.abs_diff(r.r),
+                a.g.abs_diff(r.g),
+                a.b.abs_diff(r.b),
+                a.a.abs_diff(r.a),
+            ]
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0);
+            max_delta = max_delta.max(delta);
+            let color = if delta > tolerance {
+                mismatches += 1;
+                ColorRgba32::from_rgba8(255, 0, 0, 255)
+            } else {
+                let gray = ((u16::from(r.r) + u16::from(r.g) + u16::from(r.b)) / 3) as u8;
+                let faded = 192 + gray / 4;
+                ColorRgba32::from_rgba8(faded, faded, faded, 255)
+            };
+            diff.set_pixel(x, y, color);
+        }
+    }
+    Some(Comparison {
+        mismatches,
+        max_delta,
+        diff,
+    })
+}
+
+fn write(image: &Image<ColorRgba32>, path: &std::path::Path) {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).expect("can not create the golden image directory");
+    }
+    codec::save(image, path).unwrap_or_else(|e| panic!("can not write {}: {}", path.display(), e));
+}
+
+/// Compares a frame to the reference `tests/golden/<name>.png`.
+/// Channels may differ by `tolerance` to allow for rounding in the rasterizer.
+fn assert_golden(name: &str, image: &Image<ColorRgba32>, tolerance: u8) {
+    let reference_path = PathBuf::from(REFERENCE_DIR).join(format!("{}.png", name));
+    let actual_path = PathBuf::from(OUTPUT_DIR).join(format!("{}.actual.png", name));
+    let diff_path = PathBuf::from(OUTPUT_DIR).join(format!("{}.diff.png", name));
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        write(image, &reference_path);
+        return;
+    }
+    let reference = match codec::load(&reference_path) {
+        Ok(reference) => reference,
+        Err(e) => {
+            write(image, &actual_path);
+            panic!(
+                "no reference for \"{}\" [{}], the frame was written to {}, \
+                 run with {}=1 to accept it",
+                name,
+                e,
+                actual_path.display(),
+                UPDATE_VAR
+            );
+        }
+    };
+    match compare(image, &reference, tolerance) {
+        Some(cmp) if cmp.mismatches == 0 => (),
+        Some(cmp) => {
+            write(image, &actual_path);
+            write(&cmp.diff, &diff_path);
+            panic!(
+                "\"{}\" differs from its reference in {} pixels (largest channel difference {}, \
+                 tolerance {}), see {} and {}",
+                name,
+                cmp.mismatches,
+                cmp.max_delta,
+                tolerance,
+                actual_path.display(),
+                diff_path.display()
+            );
+        }
+        None => {
+            write(image, &actual_path);
+            panic!(
+                "\"{}\" is {:?} but its reference is {:?}, see {}",
+                name,
+                image.get_size(),
+                reference.get_size(),
+                actual_path.display()
+            );
+        }
+    }
+}
+
+/// A module with fixed content, so that frames never change
+struct FakeModule {
+    text: &'static str,
+    fg: ColorRgba32,
+    bg: ColorRgba32,
+    font: FontHandle,
+}
+
+const PADDING: u32 = 4;
+
+impl Module for FakeModule {
+    fn update(&mut self) -> bool {
+        false
+    }
+
+    fn preferred_size(&self, max_height: u32) -> (u32, u32) {
+        (self.font.measure(self.text).width + 2 * PADDING, max_height)
+    }
+
+    /// Draws a labelled block filling the region
+    fn render(&mut self, surface: &mut ImageDraw<'_, ColorRgba32>) -> Result<(), ImageDrawError> {
+        let (width, height) = (surface.get_width() as u32, surface.get_height() as u32);
+        let text_height = self.font.measure("").height();
+        surface.draw(
+            DrawCommand::FilledRoundedRect(
+                Rect::new(0, 0, width, height),
+                Radii::all(3),
+                self.bg.clone().into(),
+            ) + DrawCommand::Text(
+                Text::new(
+                    PADDING as i32,
+                    ((height - text_height) / 2) as i32,
+                    self.text,
+                    self.font.clone(),
+                ),
+                self.fg.clone().into(),
+            ),
+        )
+    }
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> ColorRgba32 {
+    ColorRgba32::from_rgba8(r, g, b, 255)
+}
+
+fn font() -> FontHandle {
+    FontHandle::load(PathBuf::from(REFERENCE_DIR).join("font.bdf"), 8.0)
+        .expect("can not load the golden test font")
+}
+
+fn status(text: &'static str, fg: ColorRgba32, bg: ColorRgba32) -> Box<FakeModule> {
+    Box::new(FakeModule {
+        text,
+        fg,
+        bg,
+        font: font(),
+    })
+}
+
+fn workspace(text: &'static str, focused: bool) -> Box<FakeModule> {
+    let (fg, bg) = if focused {
+        (rgb(0x20, 0x1c, 0x1a), rgb(0xe0, 0xa0, 0x50))
+    } else {
+        (rgb(0xd0, 0xc8, 0xb8), rgb(0x40, 0x38, 0x30))
+    };
+    status(text, fg, bg)
+}
+
+/// Renders a frame with workspaces on the left and status modules on the right
+fn render_bar(width: u64, height: u64) -> Image<ColorRgba32> {
+    let mut modules = Modules::new();
+    let left = Placement::new(Align::Left).margin(Edges::new(2, 2, 0, 2));
+    for (text, focused) in [("1", true), ("2", false), ("3", false)].iter().copied() {
+        modules.push(workspace(text, focused), left.clone());
+    }
+    let right = Placement::new(Align::Right).margin(Edges::new(0, 2, 2, 2));
+    let white = rgb(0xf0, 0xf0, 0xf0);
+    modules.push(
+        status("CPU 12%", white.clone(), rgb(0x30, 0x60, 0x90)),
+        right.clone(),
+    );
+    modules.push(
+        status("VOL 40%", white, rgb(0x50, 0x80, 0x40)),
+        right.clone(),
+    );
+    modules.push(
+        status("12:34", rgb(0x20, 0x1c, 0x1a), rgb(0xd0, 0xc8, 0xb8)),
+        right,
+    );
+
+    let mut image = Image::new(width, height).unwrap();
+    bar::draw_frame(&mut image, &mut modules).unwrap();
+    image
+}
+
+#[test]
+fn bar_with_fake_modules() {
+    assert_golden("bar", &render_bar(240, 20), 2);
+}
+
+/// A centered title between a wide left and right zone, with separators and padding
+fn render_layout(collision: Collision) -> Image<ColorRgba32> {
+    let mut modules = Modules::with_options(LayoutOptions {
+        spacing: 2,
+        separator: Some(Separator::Line {
+            width: 1,
+            inset: 4,
+            line: LineInfo::new(1, rgb(0x80, 0x78, 0x70)),
+        }),
+        collision,
+    });
+    let gray = rgb(0x40, 0x38, 0x30);
+    let light = rgb(0xd0, 0xc8, 0xb8);
+    let padded = |align| Placement::new(align).padding(Edges::symmetric(2, 3));
+    modules.push(workspace("1", true), padded(Align::Left));
+    modules.push(workspace("2", false), padded(Align::Left).min_width(24));
+    modules.push(
+        status("VOLUME LEVEL 100%", light.clone(), rgb(0x30, 0x60, 0x90)),
+        padded(Align::Center).max_width(50),
+    );
+    modules.push(
+        status("12:34", light.clone(), gray.clone()),
+        padded(Align::Right),
+    );
+    modules.push(status("VOL 40%", light, gray), padded(Align::Right));
+
+    let mut image = Image::new(220, 20).unwrap();
+    bar::draw_frame(&mut image, &mut modules).unwrap();
+    image
+}
+
+#[test]
+fn layout_zones_and_collisions() {
+    assert_golden("layout-shift", &render_layout(Collision::Shift), 2);
+    assert_golden("layout-clip", &render_layout(Collision::Clip), 2);
+    assert_golden("layout-hide", &render_layout(Collision::Hide), 2);
+}
+
+#[test]
+fn truncated_labels() {
+    let light = rgb(0xd0, 0xc8, 0xb8);
+    let mut modules = Modules::with_options(LayoutOptions {
+        spacing: 4,
+        ..LayoutOptions::default()
+    });
+    for mode in [Truncate::End, Truncate::Middle, Truncate::Start]
+        .iter()
+        .copied()
+    {
+        let label = Label::new("VOLUME 100% 12:34", font(), light.clone())
+            .overflow(Overflow::Ellipsis(mode));
+        modules.push(Box::new(label), Placement::new(Align::Left).max_width(60));
+    }
+    let mut image = Image::new(200, 12).unwrap();
+    bar::draw_frame(&mut image, &mut modules).unwrap();
+    assert_golden("truncated", &image, 2);
+}
+
+/// Labels on colored backgrounds joined by segments in the left and right zone.
+/// The spacing is ignored by segments.
+fn render_segments(style: SegmentStyle, height: u64) -> Image<ColorRgba32> {
+    let mut modules = Modules::with_options(LayoutOptions {
+        spacing: 3,
+        separator: Some(Separator::Segment { style, width: 7 }),
+        ..LayoutOptions::default()
+    });
+    let light = rgb(0xf0, 0xe8, 0xd8);
+    let backgrounds = [
+        rgb(0x30, 0x60, 0x90),
+        rgb(0x90, 0x50, 0x30),
+        rgb(0x40, 0x70, 0x40),
+    ];
+    for (text, align) in [("1", Align::Left), ("2", Align::Left), ("3", Align::Left)]
+        .iter()
+        .copied()
+        .chain(
+            [("12:34", Align::Right), ("VOL 40%", Align::Right)]
+                .iter()
+                .copied(),
+        )
+    {
+        let background = backgrounds[modules.len() % backgrounds.len()].clone();
+        let label = Label::new(text, font(), light.clone()).background(background);
+        modules.push(
+            Box::new(label),
+            Placement::new(align).padding(Edges::symmetric(4, 4)),
+        );
+    }
+    let mut image = Image::new(160, height).unwrap();
+    bar::draw_frame(&mut image, &mut modules).unwrap();
+    image
+}
+
+#[test]
+fn powerline_segments() {
+    assert_golden(
+        "segments-arrow",
+        &render_segments(SegmentStyle::Arrow, 16),
+        2,
+    );
+    assert_golden(
+        "segments-slant",
+        &render_segments(SegmentStyle::Slant, 16),
+        2,
+    );
+    assert_golden(
+        "segments-rounded",
+        &render_segments(SegmentStyle::Rounded, 16),
+        2,
+    );
+}
+
+/// The backgrounds of the modules reach as high as the segments on a bar taller than them
+#[test]
+fn tall_powerline_segments() {
+    let image = render_segments(SegmentStyle::Arrow, 28);
+    let first = rgb(0x30, 0x60, 0x90);
+    for y in [0, 13, 27].iter().copied() {
+        assert_eq!(pixel(&image, 1, y), first, "at y {}", y);
+    }
+    assert_golden("segments-tall", &image, 2);
+}
+
+#[test]
+fn shapes_and_gradients() {
+    let mut image = Image::new(96, 32).unwrap();
+    let stops = vec![
+        GradientStop::new(0.0, rgb(0xe0, 0x60, 0x40)),
+        GradientStop::new(1.0, ColorRgba32::from_rgba8(0x40, 0x60, 0xe0, 0x80)),
+    ];
+    let commands = DrawCommand::Clear(rgb(0x20, 0x1c, 0x1a))
+        + DrawCommand::FilledRoundedRect(
+            Rect::new(2, 2, 40, 28),
+            Radii::new(8, 2, 8, 2),
+            LinearGradient::new(30.0, stops.clone()).into(),
+        )
+        + DrawCommand::FilledCircle(
+            Circle::new(60, 16, 12),
+            RadialGradient::centered(stops).into(),
+        )
+        + DrawCommand::Arc(
+            CircleArc::new(Circle::new(60, 16, 14), -90.0, 270.0),
+            LineInfo::new(2, rgb(0xf0, 0xf0, 0xf0)),
+        )
+        + DrawCommand::Line(
+            Line::between(78, 28, 94, 4),
+            LineInfo::new(1, rgb(0xe0, 0xa0, 0x50)),
+        );
+    image.draw(commands).unwrap();
+    assert_golden("shapes", &image, 2);
+}
+
+/// A five-pointed star drawn in one stroke, its center is a hole by the even-odd rule
+fn star(cx: f32, cy: f32, r: f32) -> Path {
+    let corner = |i: u32| {
+        let angle = (i * 144) as f32 * std::f32::consts::PI / 180.0;
+        (cx + r * angle.sin(), cy - r * angle.cos())
+    };
+    Path::polyline((0..5).map(corner)).close()
+}
+
+#[test]
+fn paths_and_fill_rules() {
+    let mut image = Image::new(128, 32).unwrap();
+    let yellow = rgb(0xe0, 0xb0, 0x40);
+    let heart = Path::new()
+        .move_to(80.0, 28.0)
+        .cubic_to(66.0, 18.0, 66.0, 6.0, 74.0, 6.0)
+        .quad_to(78.0, 6.0, 80.0, 11.0)
+        .quad_to(82.0, 6.0, 86.0, 6.0)
+        .cubic_to(94.0, 6.0, 94.0, 18.0, 80.0, 28.0)
+        .close();
+    // a sparkline over the area below it
+    let values = [4.0, 9.0, 6.0, 14.0, 11.0, 20.0, 16.0, 24.0, 13.0];
+    let graph = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (100.0 + i as f32 * 3.0, 29.0 - v));
+    let line = Path::polyline(graph);
+    let area = line
+        .clone()
+        .line_to(124.0, 29.0)
+        .line_to(100.0, 29.0)
+        .close();
+    let commands = DrawCommand::Clear(rgb(0x20, 0x1c, 0x1a))
+        + DrawCommand::FilledPath(
+            star(16.0, 17.0, 14.0),
+            FillRule::NonZero,
+            yellow.clone().into(),
+        )
+        + DrawCommand::FilledPath(star(48.0, 17.0, 14.0), FillRule::EvenOdd, yellow.into())
+        + DrawCommand::FilledPath(
+            heart,
+            FillRule::NonZero,
+            LinearGradient::between(90.0, rgb(0xf0, 0x60, 0x60), rgb(0x90, 0x20, 0x40)).into(),
+        )
+        + DrawCommand::FilledPath(
+            area,
+            FillRule::NonZero,
+            LinearGradient::between(
+                90.0,
+                ColorRgba32::from_rgba8(0x40, 0xa0, 0xe0, 0xa0),
+                ColorRgba32::from_rgba8(0x40, 0xa0, 0xe0, 0x00),
+            )
+            .into(),
+        )
+        + DrawCommand::PathOutline(line, LineInfo::new(1, rgb(0x80, 0xd0, 0xf0)))
+        + DrawCommand::PathOutline(
+            star(16.0, 17.0, 14.0),
+            LineInfo::new(1, rgb(0x90, 0x50, 0x30)),
+        );
+    image.draw(commands).unwrap();
+    assert_golden("paths", &image, 2);
+}
+
+#[test]
+fn comparison_respects_the_tolerance() {
+    let mut reference = Image::new(4, 2).unwrap();
+    reference
+        .draw(DrawCommand::Clear(rgb(100, 100, 100)))
+        .unwrap();
+    let mut actual = reference.clone();
+    actual
+        .draw(DrawCommand::Pixel(1, 0, rgb(102, 100, 100)))
+        .unwrap();
+    actual
+        .draw(DrawCommand::Pixel(2, 1, rgb(100, 90, 100)))
+        .unwrap();
+
+    let cmp = compare(&actual, &reference, 2).unwrap();
+    assert_eq!((cmp.mismatches, cmp.max_delta), (1, 10));
+    assert_eq!(pixel(&cmp.diff, 2, 1), rgb(255, 0, 0));
+    assert_ne!(pixel(&cmp.diff, 1, 0), rgb(255, 0, 0));
+    assert_eq!(compare(&actual, &reference, 10).unwrap().mismatches, 0);
+    assert!(compare(&actual, &Image::new(4, 3).unwrap(), 255).is_none());
+}
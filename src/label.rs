@@ -0,0 +1,246 @@
+//! A module showing a line of text, which is shortened or scrolled if it does not fit
+
+use crate::module::Module;
+use crate::window::color::ColorRgba32;
+use crate::window::draw::{DrawCommand, Text};
+use crate::window::font::{FontHandle, Truncate};
+use crate::window::{ImageDraw, ImageDrawError, Surface};
+use std::time::{Duration, Instant};
+
+/// Scrolls a text that does not fit through its region like a ticker
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marquee {
+    /// The scroll speed in pixels per second
+    pub speed: f32,
+    /// The space between the end of the text and its next repetition
+    pub gap: u32,
+    /// How long the text rests at its start before every round
+    pub pause: Duration,
+    /// The time between two frames of the animation, at least [`MIN_FRAME`]
+    pub frame: Duration,
+}
+
+/// The shortest time between two frames of a marquee, so that it can not keep the bar busy
+pub const MIN_FRAME: Duration = Duration::from_millis(10);
+
+impl Default for Marquee {
+    fn default() -> Self {
+        Self {
+            speed: 30.0,
+            gap: 24,
+            pause: Duration::from_secs(2),
+            frame: Duration::from_millis(50),
+        }
+    }
+}
+
+impl Marquee {
+    /// How far a text of the given width has scrolled after `elapsed`,
+    /// starting over after every round of `text_width + gap` pixels
+    pub fn offset(&self, elapsed: Duration, text_width: u32) -> u32 {
+        let round = f64::from(text_width) + f64::from(self.gap);
+        if self.speed <= 0.0 || round == 0.0 {
+            return 0;
+        }
+        let pause = self.pause.as_secs_f64();
+        let cycle = pause + round / f64::from(self.speed);
+        let t = elapsed.as_secs_f64() % cycle;
+        let offset = (t - pause).max(0.0) * f64::from(self.speed);
+        offset.min(round - 1.0) as u32
+    }
+}
+
+/// What happens to a text wider than its region
+#[derive(Debug, Clone, PartialEq)]
+pub enum Overflow {
+    /// The text is cut off at the edge of the region
+    Clip,
+    /// A part of the text is replaced with an ellipsis
+    Ellipsis(Truncate),
+    /// The text scrolls through the region, texts that fit stay still
+    Marquee(Marquee),
+}
+
+impl Default for Overflow {
+    fn default() -> Self {
+        Self::Ellipsis(Truncate::End)
+    }
+}
+
+/// A line of text, as wide as the text unless the placement limits its width
+pub struct Label {
+    text: String,
+    font: FontHandle,
+    color: ColorRgba32,
+    background: Option<ColorRgba32>,
+    overflow: Overflow,
+    /// When the text was set, a marquee starts every text at its beginning
+    since: Instant,
+    /// The width of the region of the last render
+    width: Option<u32>,
+}
+
+impl Label {
+    pub fn new<S: Into<String>>(text: S, font: FontHandle, color: ColorRgba32) -> Self {
+        Self {
+            text: text.into(),
+            font,
+            color,
+            background: None,
+            overflow: Overflow::default(),
+            since: Instant::now(),
+            width: None,
+        }
+    }
+
+    /// Sets what happens to a text wider than the region
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets the color filling the area of the label
+    pub fn background(mut self, color: ColorRgba32) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text, returns whether it changed
+    pub fn set_text<S: Into<String>>(&mut self, text: S) -> bool {
+        let text = text.into();
+        if text == self.text {
+            return false;
+        }
+        self.text = text;
+        self.since = Instant::now();
+        true
+    }
+
+    /// Whether the text is scrolled in the region of the last render
+    fn scrolls(&self) -> bool {
+        let text_width = self.font.measure(&self.text).width;
+        matches!(self.overflow, Overflow::Marquee(_))
+            && self.width.is_some_and(|width| text_width > width)
+    }
+}
+
+impl Module for Label {
+    /// A scrolling text changes with every frame
+    fn update(&mut self) -> bool {
+        self.scrolls()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        match self.overflow {
+            Overflow::Marquee(ref marquee) if self.scrolls() => Some(marquee.frame.max(MIN_FRAME)),
+            _ => None,
+        }
+    }
+
+    fn preferred_size(&self, max_height: u32) -> (u32, u32) {
+        let extents = self.font.measure(&self.text);
+        (extents.width, extents.height().min(max_height))
+    }
+
+    fn background(&self) -> Option<ColorRgba32> {
+        self.background.clone()
+    }
+
+    fn render(&mut self, surface: &mut ImageDraw<'_, ColorRgba32>) -> Result<(), ImageDrawError> {
+        let (width, height) = (surface.get_width() as u32, surface.get_height() as u32);
+        self.width = Some(width);
+        let extents = self.font.measure(&self.text);
+        let y = (height.saturating_sub(extents.height()) / 2) as i32;
+        let text = |x: i64, text: &str| {
+            let x = x.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+            DrawCommand::Text(
+                Text::new(x, y, text, self.font.clone()),
+                self.color.clone().into(),
+            )
+        };
+        let commands = match self.overflow {
+            _ if extents.width <= width => text(0, &self.text),
+            Overflow::Clip => text(0, &self.text),
+            Overflow::Ellipsis(mode) => text(0, &self.font.truncate(&self.text, width, mode)),
+            Overflow::Marquee(ref marquee) => {
+                let x = -i64::from(marquee.offset(self.since.elapsed(), extents.width));
+                // the repetition follows the text after the gap
+                let next = x + i64::from(extents.width) + i64::from(marquee.gap);
+                text(x, &self.text) + text(next, &self.text)
+            }
+        };
+        surface.draw(commands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marquee(gap: u32) -> Marquee {
+        Marquee {
+            speed: 10.0,
+            gap,
+            pause: Duration::from_secs(2),
+            frame: Duration::from_millis(50),
+        }
+    }
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    #[test]
+    fn marquees_rest_before_every_round() {
+        let marquee = marquee(20);
+        // a round takes the pause and 10 seconds of scrolling
+        for t in [0.0, 1.0, 1.99, 12.0, 13.5].iter() {
+            assert_eq!(marquee.offset(secs(*t), 80), 0, "at {}s", t);
+        }
+        assert_eq!(marquee.offset(secs(3.0), 80), 10);
+        assert_eq!(marquee.offset(secs(14.5), 80), 5);
+    }
+
+    #[test]
+    fn marquees_wrap_after_the_text_and_gap() {
+        let marquee = marquee(20);
+        assert_eq!(marquee.offset(secs(11.95), 80), 99);
+        assert_eq!(marquee.offset(secs(12.05), 80), 0);
+        assert_eq!(marquee.offset(secs(12.0 + 11.95), 80), 99);
+    }
+
+    #[test]
+    fn huge_marquees_do_not_overflow() {
+        let marquee = marquee(u32::MAX);
+        assert_eq!(marquee.offset(secs(3.0), u32::MAX), 10);
+        assert_eq!(marquee.offset(secs(8e8), u32::MAX), u32::MAX);
+        assert_eq!(
+            Marquee {
+                speed: 0.0,
+                ..marquee
+            }
+            .offset(secs(3.0), 80),
+            0
+        );
+    }
+
+    #[test]
+    fn marquee_frames_are_limited() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/font.bdf");
+        let font = FontHandle::load(path, 8.0).unwrap();
+        let marquee = Marquee {
+            frame: Duration::ZERO,
+            ..Marquee::default()
+        };
+        let mut label = Label::new("VOLUME 40%", font, ColorRgba32::default())
+            .overflow(Overflow::Marquee(marquee));
+        assert_eq!(label.interval(), None);
+        let mut image = crate::window::Image::new(20, 8).unwrap();
+        label.render(&mut image.image_draw()).unwrap();
+        assert_eq!(label.interval(), Some(MIN_FRAME));
+    }
+}
@@ -0,0 +1,794 @@
+//! Placing the modules of a bar in a left, a center and a right zone
+
+use crate::window::color::ColorRgba32;
+use crate::window::draw::{DrawCommand, Line, LineInfo, Polygon, Radii, Rect, Text};
+use crate::window::font::FontHandle;
+
+/// The zone of the bar a module is placed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Distances to the four sides of a rectangle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Edges {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl Edges {
+    pub const fn new(left: u32, top: u32, right: u32, bottom: u32) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// The same distance to every side
+    pub const fn all(v: u32) -> Self {
+        Self::new(v, v, v, v)
+    }
+
+    /// One distance to the left and right and another one to the top and bottom
+    pub const fn symmetric(horizontal: u32, vertical: u32) -> Self {
+        Self::new(horizontal, vertical, horizontal, vertical)
+    }
+
+    /// The sum of the left and right distances
+    pub const fn horizontal(&self) -> u32 {
+        self.left.saturating_add(self.right)
+    }
+
+    /// The sum of the top and bottom distances
+    pub const fn vertical(&self) -> u32 {
+        self.top.saturating_add(self.bottom)
+    }
+}
+
+/// Describes where a module is placed and how much space it takes
+#[derive(Debug, Clone, Default)]
+pub struct Placement {
+    align: Align,
+    padding: Edges,
+    margin: Edges,
+    min_width: u32,
+    max_width: Option<u32>,
+}
+
+impl Placement {
+    pub fn new(align: Align) -> Self {
+        Self {
+            align,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the space around the content, which still receives the module's clicks
+    pub fn padding(mut self, padding: Edges) -> Self {
+        self.padding = padding;
+        self
+    }
+    /// Sets the space around the padding, which belongs to no module
+    pub fn margin(mut self, margin: Edges) -> Self {
+        self.margin = margin;
+        self
+    }
+    /// Sets the width the content takes at least
+    pub fn min_width(mut self, w: u32) -> Self {
+        self.min_width = w;
+        self
+    }
+    /// Sets the width the content takes at most, it wins over the minimum
+    pub fn max_width(mut self, w: u32) -> Self {
+        self.max_width = Some(w);
+        self
+    }
+
+    /// Gets the zone
+    pub fn get_align(&self) -> Align {
+        self.align
+    }
+    /// Gets the padding
+    pub fn get_padding(&self) -> Edges {
+        self.padding
+    }
+    /// Gets the margin
+    pub fn get_margin(&self) -> Edges {
+        self.margin
+    }
+    /// Gets the minimum content width
+    pub fn get_min_width(&self) -> u32 {
+        self.min_width
+    }
+    /// Gets the maximum content width
+    pub fn get_max_width(&self) -> Option<u32> {
+        self.max_width
+    }
+
+    /// Limits a preferred content width to the minimum and maximum
+    pub fn clamp_width(&self, w: u32) -> u32 {
+        w.max(self.min_width)
+            .min(self.max_width.unwrap_or(u32::MAX))
+    }
+}
+
+/// What happens when the center zone does not fit between the left and right zone.
+/// The left and right zone always keep their place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collision {
+    /// Moves the center zone towards the free space, clipping it only if it is too wide
+    #[default]
+    Shift,
+    /// Keeps the center zone centered on the bar and clips its overlapping parts
+    Clip,
+    /// Hides the center zone while it does not fit centered
+    Hide,
+}
+
+/// The shape of a powerline segment edge, which points away from the start of its zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SegmentStyle {
+    /// A triangle as high as the bar
+    #[default]
+    Arrow,
+    /// A diagonal from the top to the bottom of the bar
+    Slant,
+    /// A half circle, flattened if the width is less than half of the height
+    Rounded,
+}
+
+/// Drawn between adjacent modules of a zone
+#[derive(Debug, Clone)]
+pub enum Separator {
+    /// Empty space of the given width
+    Space(u32),
+    /// A vertical line centered in `width` pixels, `inset` pixels away from the top and bottom
+    Line {
+        width: u32,
+        inset: u32,
+        line: LineInfo<ColorRgba32>,
+    },
+    /// Text like `|`, centered vertically
+    Text {
+        text: String,
+        font: FontHandle,
+        color: ColorRgba32,
+    },
+    /// A powerline-style edge of the given width between the backgrounds of the modules,
+    /// which join seamlessly without spacing and vertical margins
+    Segment { style: SegmentStyle, width: u32 },
+}
+
+impl Separator {
+    pub fn width(&self) -> u32 {
+        match self {
+            Self::Space(w) => *w,
+            Self::Line { width, .. } => *width,
+            Self::Text { text, font, .. } => font.measure(text).width,
+            Self::Segment { width, .. } => *width,
+        }
+    }
+
+    /// Returns the commands drawing a placed separator,
+    /// segments take the backgrounds of the modules before and after it
+    pub fn draw(
+        &self,
+        placed: &PlacedSeparator,
+        backgrounds: (Option<ColorRgba32>, Option<ColorRgba32>),
+    ) -> DrawCommand<ColorRgba32> {
+        let rect = placed.rect;
+        match self {
+            Self::Space(_) => DrawCommand::Chain(Vec::new()),
+            Self::Line { inset, line, .. } => {
+                let x = rect.x + (rect.w / 2) as i32;
+                let h = rect.h.saturating_sub(2 * inset).max(1) as i32;
+                DrawCommand::Line(Line::new(x, rect.y + *inset as i32, 0, h - 1), line.clone())
+            }
+            Self::Text { text, font, color } => {
+                let y = rect.y + (rect.h.saturating_sub(font.measure(text).height()) / 2) as i32;
+                DrawCommand::Text(
+                    Text::new(rect.x, y, text.as_str(), font.clone()),
+                    color.clone().into(),
+                )
+            }
+            Self::Segment { style, .. } => draw_segment(*style, placed, backgrounds),
+        }
+        .clipped(rect)
+    }
+}
+
+/// Fills the region with the background the segment points to
+/// and draws the shape of the other background on top of it
+fn draw_segment(
+    style: SegmentStyle,
+    placed: &PlacedSeparator,
+    (before, after): (Option<ColorRgba32>, Option<ColorRgba32>),
+) -> DrawCommand<ColorRgba32> {
+    let Rect { x, y, w, h } = placed.rect;
+    // segments of the right zone point to its start on the left
+    let reversed = placed.align == Align::Right;
+    let (base, shape) = if reversed {
+        (before, after)
+    } else {
+        (after, before)
+    };
+    let mut commands = DrawCommand::empty();
+    if let Some(color) = base {
+        commands += DrawCommand::FilledRect(placed.rect, color.into());
+    }
+    let color = match shape {
+        Some(color) => color,
+        None => return commands,
+    };
+    // the shape starts at the edge `start` and points `w` pixels towards `end`
+    let (start, end) = if reversed {
+        ((x + w as i32) as f32, x as f32)
+    } else {
+        (x as f32, (x + w as i32) as f32)
+    };
+    let (top, bottom) = (y as f32, (y + h as i32) as f32);
+    commands += match style {
+        SegmentStyle::Arrow => DrawCommand::FilledPolygon(
+            Polygon::triangle((start, top), (end, (top + bottom) / 2.0), (start, bottom)),
+            color.into(),
+        ),
+        SegmentStyle::Slant => DrawCommand::FilledPolygon(
+            Polygon::triangle((start, top), (end, top), (start, bottom)),
+            color.into(),
+        ),
+        SegmentStyle::Rounded => {
+            // the outer half of a rectangle reaching `w` pixels behind the start
+            let (rx, radii) = if reversed {
+                (x, Radii::new(w, 0, 0, w))
+            } else {
+                (x - w as i32, Radii::new(0, w, w, 0))
+            };
+            DrawCommand::FilledRoundedRect(Rect::new(rx, y, 2 * w, h), radii, color.into())
+        }
+    };
+    commands
+}
+
+/// Options applying to all modules of a bar
+#[derive(Debug, Clone, Default)]
+pub struct LayoutOptions {
+    /// The space between adjacent modules of a zone, on both sides of a separator.
+    /// Segments touch the modules around them.
+    pub spacing: u32,
+    pub separator: Option<Separator>,
+    pub collision: Collision,
+}
+
+/// Where a module was placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Block {
+    /// The region the module draws into, which may be cut off by other zones
+    pub content: Rect,
+    /// The part of the content that is not cut off
+    pub visible: Rect,
+    /// The visible content with its padding, where clicks go to the module
+    pub area: Rect,
+}
+
+impl Block {
+    /// Whether the module got no space at all
+    pub fn is_hidden(&self) -> bool {
+        self.visible.w == 0 || self.visible.h == 0
+    }
+
+    /// Whether parts of the content are cut off
+    pub fn is_clipped(&self) -> bool {
+        self.visible != self.content
+    }
+}
+
+/// Where a separator was placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlacedSeparator {
+    /// The region the separator is drawn into
+    pub rect: Rect,
+    /// The zone of the separator
+    pub align: Align,
+    /// The index of the module before the separator
+    pub before: usize,
+    /// The index of the module after the separator
+    pub after: usize,
+}
+
+/// The result of a layout pass
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    /// One block per module in their original order, hidden modules get empty blocks
+    pub blocks: Vec<Block>,
+    pub separators: Vec<PlacedSeparator>,
+}
+
+impl Layout {
+    /// Finds the module whose area contains a point,
+    /// returns its index and the point relative to its content, even if that is cut off
+    pub fn block_at(&self, (x, y): (i32, i32)) -> Option<(usize, (i32, i32))> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| !block.is_hidden())
+            .find(|(_, block)| contains(block.area, x, y))
+            .map(|(i, block)| (i, (x - block.content.x, y - block.content.y)))
+    }
+}
+
+fn contains(rect: Rect, x: i32, y: i32) -> bool {
+    let (dx, dy) = (
+        i64::from(x) - i64::from(rect.x),
+        i64::from(y) - i64::from(rect.y),
+    );
+    dx >= 0 && dy >= 0 && dx < i64::from(rect.w) && dy < i64::from(rect.h)
+}
+
+/// Converts a coordinate to an `i32`, saturating at the ends of the coordinate space
+fn saturate(v: i64) -> i32 {
+    v.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+}
+
+/// Restricts a rectangle horizontally to `lo..hi`, rectangles outside become empty
+fn clip_x(rect: Rect, lo: i64, hi: i64) -> Rect {
+    let x0 = i64::from(rect.x).max(lo);
+    let x1 = (i64::from(rect.x) + i64::from(rect.w)).min(hi);
+    if x1 <= x0 {
+        Rect::default()
+    } else {
+        Rect::new(saturate(x0), rect.y, (x1 - x0) as u32, rect.h)
+    }
+}
+
+/// A module as placed within its zone, before the zone is moved into place
+struct Item {
+    index: usize,
+    /// The distance of the margin box to the start of the zone
+    offset: i64,
+    block: Block,
+}
+
+/// The modules of one zone placed from its start
+#[derive(Default)]
+struct Zone {
+    items: Vec<Item>,
+    /// The distances of separators to the start of the zone
+    /// and the indices of the modules around them
+    separators: Vec<(i64, usize, usize)>,
+    width: i64,
+}
+
+impl Zone {
+    fn push(&mut self, index: usize, block: Block, outer_width: i64, options: &LayoutOptions) {
+        if let Some(last) = self.items.last() {
+            let spacing = i64::from(options.spacing);
+            match options.separator {
+                Some(Separator::Segment { width, .. }) => {
+                    self.separators.push((self.width, last.index, index));
+                    self.width += i64::from(width);
+                }
+                Some(ref separator) => {
+                    self.separators
+                        .push((self.width + spacing, last.index, index));
+                    self.width += 2 * spacing + i64::from(separator.width());
+                }
+                None => self.width += spacing,
+            }
+        }
+        self.items.push(Item {
+            index,
+            offset: self.width,
+            block,
+        });
+        self.width += outer_width;
+    }
+
+    /// Moves the zone to start at `x` and clips it to `lo..hi`
+    fn place(
+        self,
+        align: Align,
+        x: i64,
+        (lo, hi): (i64, i64),
+        separator_width: u32,
+        height: u32,
+        layout: &mut Layout,
+    ) {
+        for item in self.items {
+            let shift = |rect: Rect| Rect {
+                x: saturate(i64::from(rect.x) + x + item.offset),
+                ..rect
+            };
+            let content = shift(item.block.content);
+            let visible = clip_x(content, lo, hi);
+            layout.blocks[item.index] = if visible.w == 0 {
+                Block::default()
+            } else {
+                Block {
+                    content,
+                    visible,
+                    area: clip_x(shift(item.block.area), lo, hi),
+                }
+            };
+        }
+        for (offset, before, after) in self.separators {
+            let rect = Rect::new(saturate(x + offset), 0, separator_width, height);
+            let rect = clip_x(rect, lo, hi);
+            if rect.w > 0 {
+                layout.separators.push(PlacedSeparator {
+                    rect,
+                    align,
+                    before,
+                    after,
+                });
+            }
+        }
+    }
+}
+
+/// Lays out modules given by their placement and preferred size on a bar of the given size.
+/// Modules with a content width of zero take no space and get no separators.
+pub fn layout<'a, I: IntoIterator<Item = (&'a Placement, (u32, u32))>>(
+    modules: I,
+    options: &LayoutOptions,
+    width: u32,
+    height: u32,
+) -> Layout {
+    let mut layout = Layout::default();
+    let segments = matches!(options.separator, Some(Separator::Segment { .. }));
+    let (mut left, mut center, mut right) = (Zone::default(), Zone::default(), Zone::default());
+    for (index, (placement, (w, h))) in modules.into_iter().enumerate() {
+        layout.blocks.push(Block::default());
+        let (padding, margin) = (placement.padding, placement.margin);
+        let content_w = placement.clamp_width(w);
+        if content_w == 0 {
+            continue;
+        }
+        // the area is centered vertically within the margins
+        let inner_h = height.saturating_sub(margin.vertical());
+        let content_h = h.min(inner_h.saturating_sub(padding.vertical()));
+        let area_h = content_h.saturating_add(padding.vertical()).min(inner_h);
+        let area_y = i64::from(margin.top) + i64::from((inner_h - area_h) / 2);
+        let area = Rect::new(
+            saturate(margin.left.into()),
+            saturate(area_y),
+            content_w.saturating_add(padding.horizontal()),
+            area_h,
+        );
+        let content = Rect::new(
+            saturate(i64::from(area.x) + i64::from(padding.left)),
+            saturate(area_y + i64::from(padding.top)),
+            content_w,
+            content_h,
+        );
+        // backgrounds reach as high as the segments joining them
+        let area = if segments {
+            Rect {
+                y: 0,
+                h: height,
+                ..area
+            }
+        } else {
+            area
+        };
+        let block = Block {
+            content,
+            visible: content,
+            area,
+        };
+        let outer_width = i64::from(area.w) + i64::from(margin.horizontal());
+        let zone = match placement.align {
+            Align::Left => &mut left,
+            Align::Center => &mut center,
+            Align::Right => &mut right,
+        };
+        zone.push(index, block, outer_width, options);
+    }
+
+    let separator_width = options.separator.as_ref().map_or(0, Separator::width);
+    let width = i64::from(width);
+    // the left zone wins over the right zone, both win over the center zone
+    let left_end = left.width.min(width);
+    let right_x = width - right.width;
+    let right_start = right_x.max(left_end);
+    let free = (left_end, right_start);
+    let centered = (width - center.width) / 2;
+    let fits = centered >= left_end && centered + center.width <= right_start;
+    let center_x = match options.collision {
+        Collision::Shift => centered.min(right_start - center.width).max(left_end),
+        Collision::Clip => centered,
+        Collision::Hide if fits => centered,
+        Collision::Hide => {
+            center.items.clear();
+            center.separators.clear();
+            centered
+        }
+    };
+    left.place(
+        Align::Left,
+        0,
+        (0, left_end),
+        separator_width,
+        height,
+        &mut layout,
+    );
+    right.place(
+        Align::Right,
+        right_x,
+        (right_start, width),
+        separator_width,
+        height,
+        &mut layout,
+    );
+    center.place(
+        Align::Center,
+        center_x,
+        free,
+        separator_width,
+        height,
+        &mut layout,
+    );
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place(items: &[(Placement, (u32, u32))], options: &LayoutOptions, width: u32) -> Layout {
+        layout(
+            items.iter().map(|(placement, size)| (placement, *size)),
+            options,
+            width,
+            20,
+        )
+    }
+
+    fn contents(layout: &Layout) -> Vec<Rect> {
+        layout.blocks.iter().map(|block| block.content).collect()
+    }
+
+    fn visible(layout: &Layout) -> Vec<Rect> {
+        layout.blocks.iter().map(|block| block.visible).collect()
+    }
+
+    #[test]
+    fn zones_start_at_their_edges() {
+        let items = [
+            (Placement::new(Align::Right), (8, 20)),
+            (Placement::new(Align::Center), (20, 6)),
+            (Placement::new(Align::Left), (10, 100)),
+        ];
+        let out = place(&items, &LayoutOptions::default(), 100);
+        // lower modules are centered vertically
+        assert_eq!(
+            contents(&out),
+            [
+                Rect::new(92, 0, 8, 20),
+                Rect::new(40, 7, 20, 6),
+                Rect::new(0, 0, 10, 20),
+            ]
+        );
+        assert_eq!(contents(&out), visible(&out));
+        assert!(out.separators.is_empty());
+    }
+
+    #[test]
+    fn spacing_without_separators() {
+        let options = LayoutOptions {
+            spacing: 4,
+            ..LayoutOptions::default()
+        };
+        let items = [
+            (Placement::new(Align::Left), (10, 20)),
+            (Placement::new(Align::Left), (5, 20)),
+            (Placement::new(Align::Right), (6, 20)),
+            (Placement::new(Align::Right), (8, 20)),
+        ];
+        let out = place(&items, &options, 100);
+        assert_eq!(
+            contents(&out),
+            [
+                Rect::new(0, 0, 10, 20),
+                Rect::new(14, 0, 5, 20),
+                Rect::new(82, 0, 6, 20),
+                Rect::new(92, 0, 8, 20),
+            ]
+        );
+        assert!(out.separators.is_empty());
+    }
+
+    #[test]
+    fn spacing_surrounds_separators() {
+        let options = LayoutOptions {
+            spacing: 2,
+            separator: Some(Separator::Space(3)),
+            ..LayoutOptions::default()
+        };
+        let items = [
+            (Placement::new(Align::Left), (10, 20)),
+            // modules without width take no space and get no separators
+            (Placement::new(Align::Left), (0, 20)),
+            (Placement::new(Align::Left), (5, 20)),
+            (Placement::new(Align::Right), (6, 20)),
+            (Placement::new(Align::Right), (8, 20)),
+        ];
+        let out = place(&items, &options, 100);
+        assert_eq!(out.blocks[2].content, Rect::new(17, 0, 5, 20));
+        assert!(out.blocks[1].is_hidden());
+        assert_eq!(out.blocks[3].content, Rect::new(79, 0, 6, 20));
+        assert_eq!(
+            out.separators,
+            [
+                PlacedSeparator {
+                    rect: Rect::new(12, 0, 3, 20),
+                    align: Align::Left,
+                    before: 0,
+                    after: 2,
+                },
+                PlacedSeparator {
+                    rect: Rect::new(87, 0, 3, 20),
+                    align: Align::Right,
+                    before: 3,
+                    after: 4,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_join_full_height_backgrounds() {
+        let options = LayoutOptions {
+            spacing: 2,
+            separator: Some(Separator::Segment {
+                style: SegmentStyle::Arrow,
+                width: 3,
+            }),
+            ..LayoutOptions::default()
+        };
+        let placement = Placement::new(Align::Left)
+            .padding(Edges::symmetric(1, 2))
+            .margin(Edges::symmetric(0, 3));
+        let items = [(placement.clone(), (10, 6)), (placement, (5, 6))];
+        let out = place(&items, &options, 100);
+        let areas: Vec<Rect> = out.blocks.iter().map(|block| block.area).collect();
+        assert_eq!(areas, [Rect::new(0, 0, 12, 20), Rect::new(15, 0, 7, 20)]);
+        assert_eq!(
+            contents(&out),
+            [Rect::new(1, 7, 10, 6), Rect::new(16, 7, 5, 6)]
+        );
+        assert_eq!(out.separators[0].rect, Rect::new(12, 0, 3, 20));
+    }
+
+    #[test]
+    fn widths_are_limited() {
+        let items = [
+            (Placement::new(Align::Left).min_width(10), (4, 20)),
+            (Placement::new(Align::Left).max_width(30), (50, 20)),
+            // the maximum wins over the minimum
+            (
+                Placement::new(Align::Left).min_width(20).max_width(8),
+                (4, 20),
+            ),
+        ];
+        let out = place(&items, &LayoutOptions::default(), 100);
+        let widths: Vec<u32> = out.blocks.iter().map(|block| block.content.w).collect();
+        assert_eq!(widths, [10, 30, 8]);
+        assert_eq!(out.blocks[2].content.x, 40);
+    }
+
+    #[test]
+    fn padding_belongs_to_the_module_and_margins_to_none() {
+        let placement = Placement::new(Align::Left)
+            .padding(Edges::symmetric(2, 1))
+            .margin(Edges::new(3, 2, 0, 2));
+        let out = place(&[(placement, (10, 100))], &LayoutOptions::default(), 100);
+        let block = out.blocks[0];
+        assert_eq!(block.area, Rect::new(3, 2, 14, 16));
+        assert_eq!(block.content, Rect::new(5, 3, 10, 14));
+        assert_eq!(out.block_at((6, 4)), Some((0, (1, 1))));
+        // clicks on the padding are outside of the content
+        assert_eq!(out.block_at((3, 2)), Some((0, (-2, -1))));
+        assert_eq!(out.block_at((16, 17)), Some((0, (11, 14))));
+        // clicks on the margin or outside of every module
+        assert_eq!(out.block_at((2, 10)), None);
+        assert_eq!(out.block_at((10, 1)), None);
+        assert_eq!(out.block_at((10, 18)), None);
+        assert_eq!(out.block_at((17, 10)), None);
+    }
+
+    #[test]
+    fn huge_sizes_saturate_instead_of_overflowing() {
+        let huge = Placement::new(Align::Left)
+            .padding(Edges::new(u32::MAX, u32::MAX, 1, u32::MAX))
+            .margin(Edges::all(u32::MAX));
+        let items = [
+            (Placement::new(Align::Left), (10, 20)),
+            (huge, (u32::MAX, u32::MAX)),
+            (
+                Placement::new(Align::Right).padding(Edges::symmetric(u32::MAX, 0)),
+                (10, 20),
+            ),
+        ];
+        let options = LayoutOptions {
+            spacing: u32::MAX,
+            separator: Some(Separator::Space(u32::MAX)),
+            ..LayoutOptions::default()
+        };
+        let out = place(&items, &options, 100);
+        assert_eq!(out.blocks[0].content, Rect::new(0, 0, 10, 20));
+        // the other modules are pushed beyond the edges of the bar
+        assert!(out.blocks[1].is_hidden() && out.blocks[2].is_hidden());
+        assert!(out.separators.is_empty());
+    }
+
+    /// A left zone of `left` pixels, a center module of `center` pixels
+    /// and a right zone of 20 pixels on a bar of 100 pixels
+    fn collide(left: u32, center: u32, collision: Collision) -> Block {
+        let options = LayoutOptions {
+            collision,
+            ..LayoutOptions::default()
+        };
+        let items = [
+            (Placement::new(Align::Left), (left, 20)),
+            (Placement::new(Align::Center), (center, 20)),
+            (Placement::new(Align::Right), (20, 20)),
+        ];
+        place(&items, &options, 100).blocks[1]
+    }
+
+    #[test]
+    fn centered_zones_that_fit_stay_centered() {
+        for collision in [Collision::Shift, Collision::Clip, Collision::Hide].iter() {
+            let block = collide(30, 30, *collision);
+            assert_eq!(
+                (block.content, block.visible),
+                (Rect::new(35, 0, 30, 20), Rect::new(35, 0, 30, 20))
+            );
+        }
+    }
+
+    #[test]
+    fn shifting_moves_the_center_zone_into_the_free_space() {
+        let block = collide(45, 30, Collision::Shift);
+        assert_eq!(block.content, Rect::new(45, 0, 30, 20));
+        assert!(!block.is_clipped());
+        // too wide for the free space between 45 and 80
+        let block = collide(45, 40, Collision::Shift);
+        assert_eq!(block.content, Rect::new(45, 0, 40, 20));
+        assert_eq!(block.visible, Rect::new(45, 0, 35, 20));
+    }
+
+    #[test]
+    fn clipping_keeps_the_center_zone_centered() {
+        let block = collide(45, 30, Collision::Clip);
+        assert_eq!(block.content, Rect::new(35, 0, 30, 20));
+        assert_eq!(block.visible, Rect::new(45, 0, 20, 20));
+    }
+
+    #[test]
+    fn hiding_removes_the_center_zone_while_it_does_not_fit() {
+        assert!(collide(45, 30, Collision::Hide).is_hidden());
+        assert!(!collide(35, 30, Collision::Hide).is_hidden());
+    }
+
+    #[test]
+    fn the_left_zone_wins_over_the_right_zone() {
+        let items = [
+            (Placement::new(Align::Left), (70, 20)),
+            (Placement::new(Align::Right), (50, 20)),
+        ];
+        let out = place(&items, &LayoutOptions::default(), 100);
+        assert_eq!(out.blocks[0].visible, Rect::new(0, 0, 70, 20));
+        assert_eq!(out.blocks[1].content, Rect::new(50, 0, 50, 20));
+        assert_eq!(out.blocks[1].visible, Rect::new(70, 0, 30, 20));
+        assert_eq!(out.block_at((75, 5)), Some((1, (25, 5))));
+    }
+}
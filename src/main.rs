@@ -1,16 +1,81 @@
 //! A modular i3-bar written in rust
 
-#![feature(const_fn)]
-#![feature(move_ref_pattern)]
-
 mod bar;
 mod error;
+#[cfg(test)]
+mod golden;
+pub mod label;
+pub mod layout;
+pub mod module;
 pub mod window;
 
 pub use bar::{Bar, X11Bar};
 pub use error::BarError;
+pub use module::{Module, Modules};
+use std::path::PathBuf;
+
+const USAGE: &str = "usage: coffee-bar [--render FILE [--size WIDTHxHEIGHT]]
+
+options:
+    --render FILE          render one frame to a .png or .ppm file instead of opening a window
+    --size WIDTHxHEIGHT    the size of the rendered frame (default: 1920x40)
+    --help                 print this help";
+
+/// Command line options
+struct Options {
+    /// Renders a single frame to this file instead of opening a window
+    render: Option<PathBuf>,
+    /// The size of a rendered frame
+    size: (u64, u64),
+}
+
+impl Options {
+    /// Parses the arguments following the program name, returns `None` if help was requested
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Self>, BarError> {
+        let mut options = Self {
+            render: None,
+            size: (1920, bar::BAR_HEIGHT),
+        };
+        let mut size_given = false;
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| BarError(format!("{} expects a value", arg)))
+            };
+            match arg.as_str() {
+                "--render" => options.render = Some(PathBuf::from(value()?)),
+                "--size" => {
+                    let size = value()?;
+                    options.size = size
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .filter(|&(w, h)| w > 0 && h > 0)
+                        .ok_or_else(|| BarError(format!("invalid size \"{}\"", size)))?;
+                    size_given = true;
+                }
+                "-h" | "--help" => return Ok(None),
+                _ => return Err(BarError(format!("unknown argument \"{}\"\n{}", arg, USAGE))),
+            }
+        }
+        if size_given && options.render.is_none() {
+            return Err(BarError(String::from("--size requires --render")));
+        }
+        Ok(Some(options))
+    }
+}
 
 fn run_bar() -> Result<(), BarError> {
+    let options = match Options::parse(std::env::args().skip(1))? {
+        Some(options) => options,
+        None => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+    };
+    if let Some(path) = options.render {
+        let mut modules = bar::modules(bar::load_font()?);
+        return bar::render_to_file(&path, options.size.0, options.size.1, &mut modules);
+    }
     Ok(X11Bar::new()?.main_loop()?)
 }
 
@@ -21,3 +86,54 @@ fn main() {
         std::process::exit(1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, BarError> {
+        Options::parse(args.iter().map(|arg| String::from(*arg)))
+    }
+
+    #[test]
+    fn the_bar_opens_a_window_by_default() {
+        let options = parse(&[]).unwrap().unwrap();
+        assert_eq!(
+            (options.render, options.size),
+            (None, (1920, bar::BAR_HEIGHT))
+        );
+        assert!(parse(&["--help"]).unwrap().is_none());
+        assert!(parse(&["--render", "bar.png", "-h"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn frames_are_rendered_in_the_given_size() {
+        let options = parse(&["--render", "bar.png"]).unwrap().unwrap();
+        assert_eq!(options.render, Some(PathBuf::from("bar.png")));
+        assert_eq!(options.size, (1920, bar::BAR_HEIGHT));
+        let options = parse(&["--size", "300x20", "--render", "bar.ppm"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (options.render, options.size),
+            (Some(PathBuf::from("bar.ppm")), (300, 20))
+        );
+    }
+
+    #[test]
+    fn malformed_arguments_are_rejected() {
+        let invalid = [
+            &["--render"][..],
+            &["--render", "bar.png", "--size"],
+            &["--size", "300x20"],
+            &["--render", "bar.png", "--size", "300"],
+            &["--render", "bar.png", "--size", "0x20"],
+            &["--render", "bar.png", "--size", "300x-20"],
+            &["--render", "bar.png", "--size", "wide x tall"],
+            &["--frobnicate"],
+        ];
+        for args in invalid.iter() {
+            assert!(parse(args).is_err(), "{:?} was accepted", args);
+        }
+    }
+}
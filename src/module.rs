@@ -0,0 +1,378 @@
+//! Modules are the blocks a bar is made of, like a clock or a workspace list
+
+use crate::layout::{self, Layout, LayoutOptions, Placement};
+use crate::window::color::ColorRgba32;
+use crate::window::draw::{Blit, DrawCommand};
+use crate::window::event::{Button, Event};
+use crate::window::{Image, ImageDraw, ImageDrawError, Surface};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A block of the bar, which draws its content into a region assigned by the bar
+pub trait Module {
+    /// Refreshes the content, returns whether it changed and has to be redrawn
+    fn update(&mut self) -> bool;
+    /// The time between two updates, modules that only change on input return `None`
+    fn interval(&self) -> Option<Duration> {
+        None
+    }
+    /// The size the module would like to take, the height is limited to `max_height`
+    fn preferred_size(&self, max_height: u32) -> (u32, u32);
+    /// The color filling the module's area below its content,
+    /// segment separators join the backgrounds of adjacent modules
+    fn background(&self) -> Option<ColorRgba32> {
+        None
+    }
+    /// Draws the module, the top left corner of its region is the origin
+    fn render(&mut self, surface: &mut ImageDraw<'_, ColorRgba32>) -> Result<(), ImageDrawError>;
+    /// Handles a click at a position relative to the region, returns whether to redraw
+    fn click(&mut self, _button: Button, _pos: (i32, i32)) -> bool {
+        false
+    }
+    /// Handles a scroll step, `dy` is negative upwards and `dx` negative to the left.
+    /// Returns whether to redraw.
+    fn scroll(&mut self, _dx: i32, _dy: i32, _pos: (i32, i32)) -> bool {
+        false
+    }
+}
+
+/// A module and the state the bar keeps about it
+struct Slot {
+    module: Box<dyn Module>,
+    placement: Placement,
+    /// Whether the module was updated at least once
+    updated: bool,
+    /// When the module wants its next update
+    next_update: Option<Instant>,
+}
+
+/// The ordered modules of a bar and their last layout
+#[derive(Default)]
+pub struct Modules {
+    slots: Vec<Slot>,
+    options: LayoutOptions,
+    layout: Layout,
+}
+
+impl Modules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty list whose modules are laid out with the given options
+    pub fn with_options(options: LayoutOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Appends a module after the other ones of its zone
+    pub fn push(&mut self, module: Box<dyn Module>, placement: Placement) {
+        self.slots.push(Slot {
+            module,
+            placement,
+            updated: false,
+            next_update: None,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn options(&self) -> &LayoutOptions {
+        &self.options
+    }
+
+    pub fn set_options(&mut self, options: LayoutOptions) {
+        self.options = options;
+    }
+
+    /// The result of the last layout pass
+    pub fn get_layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Updates every module whose interval elapsed and those never updated before,
+    /// returns whether any of them changed
+    pub fn update_due(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+        for slot in &mut self.slots {
+            if !slot.updated || slot.next_update.is_some_and(|next| next <= now) {
+                changed |= slot.module.update();
+                slot.updated = true;
+                slot.next_update = slot.module.interval().map(|interval| now + interval);
+            }
+        }
+        changed
+    }
+
+    /// The earliest time a module wants to be updated, `None` if all wait for input
+    pub fn next_update(&self) -> Option<Instant> {
+        self.slots.iter().filter_map(|slot| slot.next_update).min()
+    }
+
+    /// Places the modules on a bar of the given size by their preferred sizes
+    pub fn layout(&mut self, width: u32, height: u32) {
+        let sizes: Vec<_> = self
+            .slots
+            .iter()
+            .map(|slot| {
+                let margin = slot.placement.get_margin().vertical();
+                let padding = slot.placement.get_padding().vertical();
+                let max_height = height.saturating_sub(margin + padding);
+                slot.module.preferred_size(max_height)
+            })
+            .collect();
+        let modules = self.slots.iter().map(|slot| &slot.placement).zip(sizes);
+        self.layout = layout::layout(modules, &self.options, width, height);
+    }
+
+    /// Draws the separators and lets every module render into its region of the last layout.
+    /// Modules wanting updates after rendering are scheduled from now on.
+    pub fn render(&mut self, frame: &mut Image<ColorRgba32>) -> Result<(), ImageDrawError> {
+        if let Some(ref separator) = self.options.separator {
+            for placed in &self.layout.separators {
+                let backgrounds = (
+                    self.slots[placed.before].module.background(),
+                    self.slots[placed.after].module.background(),
+                );
+                frame.draw(separator.draw(placed, backgrounds))?;
+            }
+        }
+        for (slot, block) in self.slots.iter_mut().zip(&self.layout.blocks) {
+            if block.is_hidden() {
+                continue;
+            }
+            if let Some(color) = slot.module.background() {
+                frame.draw(DrawCommand::FilledRect(block.area, color.into()))?;
+            }
+            let rect = block.content;
+            if block.is_clipped() {
+                // the module draws its whole content, only the visible part lands in the frame
+                if let Some(mut content) = Image::new(rect.w.into(), rect.h.into()) {
+                    slot.module.render(&mut content.image_draw())?;
+                    let blit = Blit::new(rect.x, rect.y, Arc::new(content));
+                    frame.draw(DrawCommand::Image(blit).clipped(block.visible))?;
+                }
+            } else {
+                let region =
+                    frame.sub_image_mut(rect.x as u64, rect.y as u64, rect.w.into(), rect.h.into());
+                if let Some(mut region) = region {
+                    slot.module.render(&mut region)?;
+                }
+            }
+            // modules may start wanting updates once they know their region,
+            // e.g. to scroll a text that turned out too wide
+            if slot.updated && slot.next_update.is_none() {
+                slot.next_update = slot
+                    .module
+                    .interval()
+                    .map(|interval| Instant::now() + interval);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the module below a point, returns its index and the point relative to its content.
+    /// Points on the padding lie outside of the content.
+    pub fn module_at(&self, pos: (i32, i32)) -> Option<(usize, (i32, i32))> {
+        self.layout.block_at(pos)
+    }
+
+    /// Passes presses to the module below the pointer, returns whether to redraw
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        let (button, pos) = match *event {
+            Event::ButtonDown(button, pos) => (button, pos),
+            Event::ButtonUp(..) | Event::ButtonMove(..) => return false,
+        };
+        let (i, pos) = match self.module_at(pos) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let module = &mut self.slots[i].module;
+        match button {
+            Button::ScrollUp => module.scroll(0, -1, pos),
+            Button::ScrollDown => module.scroll(0, 1, pos),
+            Button::ScrollLeft => module.scroll(-1, 0, pos),
+            Button::ScrollRight => module.scroll(1, 0, pos),
+            Button::Left | Button::Middle | Button::Right => module.click(button, pos),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{Align, Collision, Edges};
+    use crate::window::color::Color;
+    use crate::window::draw::Rect;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Draws every column in a red of its x-coordinate
+    struct Columns(u32);
+
+    impl Module for Columns {
+        fn update(&mut self) -> bool {
+            false
+        }
+        fn preferred_size(&self, max_height: u32) -> (u32, u32) {
+            (self.0, max_height)
+        }
+        fn render(
+            &mut self,
+            surface: &mut ImageDraw<'_, ColorRgba32>,
+        ) -> Result<(), ImageDrawError> {
+            let h = surface.get_height() as u32;
+            for x in 0..surface.get_width() as i32 {
+                let color = ColorRgba32::from_rgba8(x as u8, 0, 0, 255);
+                surface.draw(DrawCommand::FilledRect(Rect::new(x, 0, 1, h), color.into()))?;
+            }
+            Ok(())
+        }
+    }
+
+    fn red(frame: &Image<ColorRgba32>, x: u64) -> u8 {
+        frame.get_pixel_at(x, 0).unwrap().r
+    }
+
+    #[test]
+    fn cut_off_modules_keep_their_origin() {
+        let mut modules = Modules::with_options(LayoutOptions {
+            collision: Collision::Clip,
+            ..LayoutOptions::default()
+        });
+        modules.push(Box::new(Columns(100)), Placement::new(Align::Left));
+        modules.push(Box::new(Columns(60)), Placement::new(Align::Center));
+        modules.push(Box::new(Columns(30)), Placement::new(Align::Left));
+        modules.push(Box::new(Columns(40)), Placement::new(Align::Right));
+        modules.layout(160, 4);
+        let blocks = &modules.get_layout().blocks;
+        // the center zone lies within the left zone, which ends at 130
+        assert!(blocks[1].is_hidden());
+        // the right zone is pushed behind the left zone
+        assert_eq!(blocks[3].content, Rect::new(120, 0, 40, 4));
+        assert_eq!(blocks[3].visible, Rect::new(130, 0, 30, 4));
+        assert_eq!(modules.module_at((131, 1)), Some((3, (11, 1))));
+
+        let mut frame = Image::new(160, 4).unwrap();
+        modules.render(&mut frame).unwrap();
+        assert_eq!(
+            (red(&frame, 99), red(&frame, 100), red(&frame, 129)),
+            (99, 0, 29)
+        );
+        // the cut off right module shows its own right part
+        assert_eq!((red(&frame, 130), red(&frame, 159)), (10, 39));
+    }
+
+    #[test]
+    fn the_center_zone_may_overlap_the_left_zone() {
+        let mut modules = Modules::with_options(LayoutOptions {
+            collision: Collision::Clip,
+            ..LayoutOptions::default()
+        });
+        modules.push(Box::new(Columns(100)), Placement::new(Align::Left));
+        modules.push(Box::new(Columns(60)), Placement::new(Align::Center));
+        modules.layout(160, 4);
+        let block = modules.get_layout().blocks[1];
+        assert_eq!(block.content, Rect::new(50, 0, 60, 4));
+        assert_eq!(block.visible, Rect::new(100, 0, 10, 4));
+        assert_eq!(modules.module_at((105, 2)), Some((1, (55, 2))));
+
+        let mut frame = Image::new(160, 4).unwrap();
+        modules.render(&mut frame).unwrap();
+        assert_eq!(
+            (red(&frame, 99), red(&frame, 100), red(&frame, 109)),
+            (99, 50, 59)
+        );
+        assert_eq!(frame.get_pixel_at(110, 0).unwrap().a, 0);
+    }
+
+    /// Logs the input it receives, tagged with its name
+    struct Recorder(&'static str, Rc<RefCell<Vec<String>>>);
+
+    impl Module for Recorder {
+        fn update(&mut self) -> bool {
+            false
+        }
+        fn preferred_size(&self, max_height: u32) -> (u32, u32) {
+            (10, max_height)
+        }
+        fn render(&mut self, _: &mut ImageDraw<'_, ColorRgba32>) -> Result<(), ImageDrawError> {
+            Ok(())
+        }
+        fn click(&mut self, button: Button, pos: (i32, i32)) -> bool {
+            self.1
+                .borrow_mut()
+                .push(format!("{} {:?} {:?}", self.0, button, pos));
+            true
+        }
+        fn scroll(&mut self, dx: i32, dy: i32, pos: (i32, i32)) -> bool {
+            self.1
+                .borrow_mut()
+                .push(format!("{} scroll {} {} {:?}", self.0, dx, dy, pos));
+            true
+        }
+    }
+
+    #[test]
+    fn events_reach_the_module_below_in_its_coordinates() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut modules = Modules::with_options(LayoutOptions {
+            spacing: 4,
+            ..LayoutOptions::default()
+        });
+        let placement = Placement::new(Align::Left)
+            .padding(Edges::symmetric(2, 2))
+            .margin(Edges::new(3, 3, 0, 3));
+        modules.push(Box::new(Recorder("a", log.clone())), placement);
+        modules.push(
+            Box::new(Recorder("b", log.clone())),
+            Placement::new(Align::Left),
+        );
+        modules.layout(100, 20);
+        // "a" takes 3..17 with its content at 5..15, "b" starts after the spacing
+        let events = [
+            Event::ButtonDown(Button::Left, (6, 6)),
+            Event::ButtonDown(Button::Right, (22, 10)),
+            Event::ButtonDown(Button::ScrollUp, (6, 6)),
+            Event::ButtonDown(Button::ScrollRight, (25, 0)),
+            Event::ButtonUp(Button::Left, (6, 6)),
+            Event::ButtonMove(Button::Left, (6, 6)),
+        ];
+        let redraws: Vec<bool> = events.iter().map(|e| modules.handle_event(e)).collect();
+        assert_eq!(redraws, [true, true, true, true, false, false]);
+        assert_eq!(
+            *log.borrow(),
+            [
+                "a Left (1, 1)",
+                "b Right (1, 10)",
+                "a scroll 0 -1 (1, 1)",
+                "b scroll 1 0 (4, 0)",
+            ]
+        );
+    }
+
+    #[test]
+    fn padding_clicks_reach_the_module_and_margin_clicks_none() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut modules = Modules::new();
+        let placement = Placement::new(Align::Left)
+            .padding(Edges::symmetric(2, 2))
+            .margin(Edges::all(3));
+        modules.push(Box::new(Recorder("a", log.clone())), placement);
+        modules.layout(100, 20);
+        assert!(modules.handle_event(&Event::ButtonDown(Button::Left, (3, 3))));
+        assert!(modules.handle_event(&Event::ButtonDown(Button::Middle, (16, 16))));
+        for pos in [(2, 10), (10, 2), (10, 17), (17, 10)].iter() {
+            assert!(!modules.handle_event(&Event::ButtonDown(Button::Left, *pos)));
+        }
+        assert_eq!(*log.borrow(), ["a Left (-2, -2)", "a Middle (11, 11)"]);
+    }
+}
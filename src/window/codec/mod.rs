@@ -0,0 +1,179 @@
+//! Decoding of image files into [`Image`]s and encoding of [`Image`]s
+
+mod png;
+mod ppm;
+mod svg;
+mod xpm;
+
+pub use self::png::{decode_png, encode_png};
+pub use self::ppm::{decode_ppm, encode_ppm};
+pub use self::svg::{decode_svg, load_svg, render_svg, SvgCache};
+pub use self::xpm::decode_xpm;
+
+use super::color::{Color, ColorRgba32};
+use super::Image;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum CodecError {
+    Io(std::io::Error),
+    /// The data is damaged or does not follow the format
+    Invalid(String),
+    /// The data uses a feature or format that is not implemented
+    Unsupported(String),
+}
+
+impl std::fmt::Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read or write image [{}]", e),
+            Self::Invalid(e) => write!(f, "invalid image: {}", e),
+            Self::Unsupported(e) => write!(f, "unsupported image: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<std::io::Error> for CodecError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Detects SVG documents by an `<svg` tag near the start of an XML document
+fn is_svg(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(1024)];
+    head.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<')
+        && head.windows(4).any(|w| w == b"<svg")
+}
+
+fn is_netpbm(data: &[u8]) -> bool {
+    matches!(data, [b'P', b'2'..=b'7', ws, ..] if ws.is_ascii_whitespace())
+}
+
+/// Decodes an image, the format is detected by its content
+pub fn decode(data: &[u8]) -> Result<Image<ColorRgba32>, CodecError> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        decode_png(data)
+    } else if data.starts_with(b"/* XPM */") || data.starts_with(b"! XPM2") {
+        let text = std::str::from_utf8(data)
+            .map_err(|_| CodecError::Invalid(String::from("XPM is not valid UTF-8")))?;
+        decode_xpm(text)
+    } else if is_netpbm(data) {
+        decode_ppm(data)
+    } else if is_svg(data) {
+        decode_svg(data)
+    } else {
+        Err(CodecError::Unsupported(String::from(
+            "unknown image format",
+        )))
+    }
+}
+
+/// Loads a PNG, netpbm, XPM or SVG file, SVGs are rendered at the size they specify
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Image<ColorRgba32>, CodecError> {
+    decode(&std::fs::read(path)?)
+}
+
+/// Formats images can be written in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Png,
+    /// PPM, or PAM for pixel formats with alpha
+    Ppm,
+}
+
+impl Format {
+    /// Guesses the format from a file extension
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Some(Self::Png),
+            "ppm" | "pnm" | "pam" => Some(Self::Ppm),
+            _ => None,
+        }
+    }
+}
+
+pub fn encode<C: Color>(image: &Image<C>, format: Format) -> Result<Vec<u8>, CodecError> {
+    match format {
+        Format::Png => encode_png(image),
+        Format::Ppm => Ok(encode_ppm(image)),
+    }
+}
+
+/// Writes an image to a file in the format its extension names
+pub fn save<C: Color, P: AsRef<Path>>(image: &Image<C>, path: P) -> Result<(), CodecError> {
+    let path = path.as_ref();
+    let format = Format::from_path(path).ok_or_else(|| {
+        CodecError::Unsupported(format!("no image format for \"{}\"", path.display()))
+    })?;
+    std::fs::write(path, encode(image, format)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::color::{Color, ColorRgb24};
+    use super::super::Surface;
+    use super::*;
+
+    /// A 3x2 image, the odd width catches row padding and stride mistakes
+    fn pattern<C: Color>() -> Image<C> {
+        let mut image = Image::new(3, 2).unwrap();
+        for (i, (x, y)) in [(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)]
+            .iter()
+            .copied()
+            .enumerate()
+        {
+            let v = i as u8 * 40;
+            image.set_pixel(x, y, C::from_rgba8(v, 255 - v, v / 2, 255 - v / 3));
+        }
+        image
+    }
+
+    fn round_trip<C: Color>(format: Format) -> (Image<C>, Image<ColorRgba32>) {
+        let image = pattern::<C>();
+        let decoded = decode(&encode(&image, format).unwrap()).unwrap();
+        (image, decoded)
+    }
+
+    fn assert_same<C: Color + 'static>(image: &Image<C>, decoded: &Image<ColorRgba32>) {
+        assert_eq!(decoded.get_size(), image.get_size());
+        let expected: Vec<_> = image.data().iter().cloned().map(Color::as_rgba32).collect();
+        assert_eq!(decoded.data(), &expected[..]);
+    }
+
+    #[test]
+    fn ppm_round_trips_opaque_images() {
+        let (image, decoded) = round_trip::<ColorRgb24>(Format::Ppm);
+        assert!(encode(&image, Format::Ppm)
+            .unwrap()
+            .starts_with(b"P6\n3 2\n255\n"));
+        assert_same(&image, &decoded);
+    }
+
+    #[test]
+    fn pam_round_trips_alpha() {
+        let (image, decoded) = round_trip::<ColorRgba32>(Format::Ppm);
+        assert!(encode(&image, Format::Ppm).unwrap().starts_with(b"P7\n"));
+        assert!(decoded.data().iter().any(|c| c.a < 255));
+        assert_same(&image, &decoded);
+    }
+
+    #[test]
+    fn png_round_trips_with_and_without_alpha() {
+        let (image, decoded) = round_trip::<ColorRgba32>(Format::Png);
+        assert_same(&image, &decoded);
+        let (image, decoded) = round_trip::<ColorRgb24>(Format::Png);
+        assert_same(&image, &decoded);
+    }
+
+    #[test]
+    fn netpbm_headers_may_contain_comments() {
+        let decoded = decode(b"P3\n# a comment\n1 1\n255\n10 20 30\n").unwrap();
+        assert_eq!(decoded.data(), &[ColorRgba32::from_rgba8(10, 20, 30, 255)]);
+        assert!(decode(b"P6\n2 2\n255\n\x00\x01").is_err());
+    }
+}
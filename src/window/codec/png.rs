@@ -0,0 +1,111 @@
+use super::super::color::{Color, ColorRgba32};
+use super::super::Image;
+use super::CodecError;
+use std::convert::TryFrom;
+
+impl From<png::DecodingError> for CodecError {
+    fn from(e: png::DecodingError) -> Self {
+        match e {
+            png::DecodingError::IoError(e) => Self::Io(e),
+            png::DecodingError::Format(e) => Self::Invalid(e.to_string()),
+            png::DecodingError::Parameter(e) => Self::Invalid(e.to_string()),
+            png::DecodingError::LimitsExceeded => {
+                Self::Unsupported(String::from("PNG exceeds the memory limits"))
+            }
+        }
+    }
+}
+
+impl From<png::EncodingError> for CodecError {
+    fn from(e: png::EncodingError) -> Self {
+        match e {
+            png::EncodingError::IoError(e) => Self::Io(e),
+            e => Self::Unsupported(e.to_string()),
+        }
+    }
+}
+
+/// Encodes an image as 8 bit PNG, as RGBA if its pixel format has an alpha channel
+/// and as RGB otherwise
+pub fn encode_png<C: Color>(image: &Image<C>) -> Result<Vec<u8>, CodecError> {
+    let too_large = || CodecError::Unsupported(String::from("PNG dimensions exceed 32 bits"));
+    let width = u32::try_from(image.res.0).map_err(|_| too_large())?;
+    let height = u32::try_from(image.res.1).map_err(|_| too_large())?;
+    let alpha = C::get_format().has_alpha();
+    let mut data = Vec::with_capacity(image.data.len() * if alpha { 4 } else { 3 });
+    for c in &image.data {
+        data.extend_from_slice(&[c.r8(), c.g8(), c.b8()]);
+        if alpha {
+            data.push(c.a8());
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(if alpha {
+        png::ColorType::Rgba
+    } else {
+        png::ColorType::Rgb
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(out)
+}
+
+/// Decodes a PNG image of any color type and bit depth.
+/// Palettes and transparency chunks are expanded and 16 bit channels reduced to 8 bits.
+pub fn decode_png(data: &[u8]) -> Result<Image<ColorRgba32>, CodecError> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let buf = &buf[..info.buffer_size()];
+    let pixels: Vec<ColorRgba32> = match info.color_type {
+        png::ColorType::Rgba => buf
+            .chunks_exact(4)
+            .map(|p| ColorRgba32 {
+                r: p[0],
+                g: p[1],
+                b: p[2],
+                a: p[3],
+            })
+            .collect(),
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .map(|p| ColorRgba32 {
+                r: p[0],
+                g: p[1],
+                b: p[2],
+                a: 255,
+            })
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .map(|p| ColorRgba32 {
+                r: p[0],
+                g: p[0],
+                b: p[0],
+                a: p[1],
+            })
+            .collect(),
+        png::ColorType::Grayscale => buf
+            .iter()
+            .map(|&v| ColorRgba32 {
+                r: v,
+                g: v,
+                b: v,
+                a: 255,
+            })
+            .collect(),
+        png::ColorType::Indexed => {
+            return Err(CodecError::Unsupported(String::from(
+                "indexed PNG was not expanded",
+            )))
+        }
+    };
+    Image::from_data(info.width.into(), info.height.into(), pixels)
+        .ok_or_else(|| CodecError::Invalid(String::from("PNG data does not match its size")))
+}
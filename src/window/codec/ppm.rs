@@ -0,0 +1,225 @@
+use super::super::color::{Color, ColorRgba32};
+use super::super::Image;
+use super::CodecError;
+
+fn invalid<S: Into<String>>(msg: S) -> CodecError {
+    CodecError::Invalid(msg.into())
+}
+
+/// Encodes an image as binary PPM (`P6`), or as PAM (`P7`) with a `RGB_ALPHA` tuple type
+/// if its pixel format has an alpha channel, since PPM can not store transparency
+pub fn encode_ppm<C: Color>(image: &Image<C>) -> Vec<u8> {
+    let (width, height) = image.res;
+    let alpha = C::get_format().has_alpha();
+    let mut out = if alpha {
+        format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            width, height
+        )
+    } else {
+        format!("P6\n{} {}\n255\n", width, height)
+    }
+    .into_bytes();
+    out.reserve(image.data.len() * if alpha { 4 } else { 3 });
+    for c in &image.data {
+        out.extend_from_slice(&[c.r8(), c.g8(), c.b8()]);
+        if alpha {
+            out.push(c.a8());
+        }
+    }
+    out
+}
+
+/// Splits the whitespace separated tokens of a netpbm header, skipping `#` comments
+struct Tokens<'d> {
+    data: &'d [u8],
+    pos: usize,
+}
+
+impl<'d> Tokens<'d> {
+    fn skip_space(&mut self) {
+        while let Some(&b) = self.data.get(self.pos) {
+            match b {
+                b'#' => {
+                    while self.data.get(self.pos).is_some_and(|&b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn next(&mut self) -> Option<&'d str> {
+        self.skip_space();
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()
+            .filter(|t| !t.is_empty())
+    }
+
+    fn number(&mut self, what: &str) -> Result<u32, CodecError> {
+        self.next()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| invalid(format!("missing or malformed {}", what)))
+    }
+
+    /// The raster of binary formats starts after exactly one whitespace character
+    fn raster(self) -> &'d [u8] {
+        self.data.get(self.pos + 1..).unwrap_or(&[])
+    }
+}
+
+/// Channel layouts of netpbm files
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    RgbAlpha,
+}
+
+impl Layout {
+    fn depth(self) -> usize {
+        match self {
+            Self::Gray => 1,
+            Self::GrayAlpha => 2,
+            Self::Rgb => 3,
+            Self::RgbAlpha => 4,
+        }
+    }
+
+    fn color(self, s: &[u8]) -> ColorRgba32 {
+        let (r, g, b, a) = match self {
+            Self::Gray => (s[0], s[0], s[0], 255),
+            Self::GrayAlpha => (s[0], s[0], s[0], s[1]),
+            Self::Rgb => (s[0], s[1], s[2], 255),
+            Self::RgbAlpha => (s[0], s[1], s[2], s[3]),
+        };
+        ColorRgba32 { r, g, b, a }
+    }
+}
+
+/// Reads a PAM header, the `P7` magic has already been consumed
+fn pam_header(tokens: &mut Tokens) -> Result<(u32, u32, u32, Layout), CodecError> {
+    let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+    let mut tuple_type = String::new();
+    loop {
+        match tokens.next() {
+            Some("ENDHDR") => break,
+            Some("WIDTH") => width = Some(tokens.number("PAM width")?),
+            Some("HEIGHT") => height = Some(tokens.number("PAM height")?),
+            Some("DEPTH") => depth = Some(tokens.number("PAM depth")?),
+            Some("MAXVAL") => maxval = Some(tokens.number("PAM maxval")?),
+            Some("TUPLTYPE") => tuple_type = tokens.next().unwrap_or_default().to_string(),
+            Some(key) => return Err(invalid(format!("unknown PAM header field \"{}\"", key))),
+            None => return Err(invalid("PAM header lacks ENDHDR")),
+        }
+    }
+    let missing = |field| invalid(format!("PAM header lacks {}", field));
+    let layout = match (depth.ok_or_else(|| missing("DEPTH"))?, tuple_type.as_str()) {
+        (1, _) => Layout::Gray,
+        (2, _) => Layout::GrayAlpha,
+        (3, _) => Layout::Rgb,
+        (4, "RGB_ALPHA") | (4, "") => Layout::RgbAlpha,
+        (depth, tuple_type) => {
+            return Err(CodecError::Unsupported(format!(
+                "PAM tuple type \"{}\" of depth {}",
+                tuple_type, depth
+            )))
+        }
+    };
+    Ok((
+        width.ok_or_else(|| missing("WIDTH"))?,
+        height.ok_or_else(|| missing("HEIGHT"))?,
+        maxval.ok_or_else(|| missing("MAXVAL"))?,
+        layout,
+    ))
+}
+
+/// Decodes the netpbm formats PGM and PPM in plain (`P2`, `P3`) and binary (`P5`, `P6`)
+/// encoding and PAM (`P7`) with grayscale or RGB tuples, optionally with alpha.
+/// Samples with a maximum value other than 255 are scaled to 8 bits.
+pub fn decode_ppm(data: &[u8]) -> Result<Image<ColorRgba32>, CodecError> {
+    let mut tokens = Tokens { data, pos: 0 };
+    let magic = tokens.next().unwrap_or_default();
+    let (width, height, maxval, layout, plain) = match magic {
+        "P2" | "P3" | "P5" | "P6" => {
+            let width = tokens.number("width")?;
+            let height = tokens.number("height")?;
+            let maxval = tokens.number("maxval")?;
+            let layout = match magic {
+                "P2" | "P5" => Layout::Gray,
+                _ => Layout::Rgb,
+            };
+            (
+                width,
+                height,
+                maxval,
+                layout,
+                magic == "P2" || magic == "P3",
+            )
+        }
+        "P7" => {
+            let (width, height, maxval, layout) = pam_header(&mut tokens)?;
+            (width, height, maxval, layout, false)
+        }
+        _ => return Err(CodecError::Unsupported(String::from("netpbm variant"))),
+    };
+    if !(1..=65535).contains(&maxval) {
+        return Err(invalid(format!("maxval {} is out of range", maxval)));
+    }
+
+    let too_large = || CodecError::Unsupported(String::from("image is too large"));
+    let count = super::super::get_area(width.into(), height.into())
+        .and_then(|area| area.checked_mul(layout.depth()))
+        .ok_or_else(too_large)?;
+    let scale = |v: u32| -> Result<u8, CodecError> {
+        if v > maxval {
+            return Err(invalid(format!("sample {} exceeds maxval {}", v, maxval)));
+        }
+        Ok(((v * 255 + maxval / 2) / maxval) as u8)
+    };
+    let samples = if plain {
+        let mut samples = Vec::with_capacity(count.min(1 << 24));
+        for _ in 0..count {
+            samples.push(scale(tokens.number("sample")?)?);
+        }
+        samples
+    } else {
+        let wide = maxval > 255;
+        let raster = tokens.raster();
+        let bytes = if wide {
+            count.checked_mul(2)
+        } else {
+            Some(count)
+        };
+        let raster = bytes
+            .and_then(|bytes| raster.get(..bytes))
+            .ok_or_else(|| invalid("raster is truncated"))?;
+        if wide {
+            raster
+                .chunks_exact(2)
+                .map(|s| scale(u32::from(u16::from_be_bytes([s[0], s[1]]))))
+                .collect::<Result<_, _>>()?
+        } else {
+            raster
+                .iter()
+                .map(|&s| scale(u32::from(s)))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let pixels = samples
+        .chunks_exact(layout.depth())
+        .map(|s| layout.color(s))
+        .collect();
+    Image::from_data(width.into(), height.into(), pixels).ok_or_else(too_large)
+}
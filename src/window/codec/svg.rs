@@ -0,0 +1,179 @@
+use super::super::color::Color;
+use super::super::Image;
+use super::CodecError;
+use resvg::{tiny_skia, usvg};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+impl From<usvg::Error> for CodecError {
+    fn from(e: usvg::Error) -> Self {
+        Self::Invalid(e.to_string())
+    }
+}
+
+fn parse(data: &[u8], resources_dir: Option<&Path>) -> Result<usvg::Tree, CodecError> {
+    let options = usvg::Options {
+        resources_dir: resources_dir.map(Path::to_path_buf),
+        ..Default::default()
+    };
+    Ok(usvg::Tree::from_data(data, &options)?)
+}
+
+/// Rasterizes a parsed SVG, scaling it uniformly to fit and centering it
+fn rasterize<C: Color>(tree: &usvg::Tree, width: u32, height: u32) -> Result<Image<C>, CodecError> {
+    let too_large = || CodecError::Unsupported(format!("SVG size {}x{}", width, height));
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or_else(too_large)?;
+    let size = tree.size();
+    let scale = (width as f32 / size.width()).min(height as f32 / size.height());
+    let transform = tiny_skia::Transform::from_scale(scale, scale).post_translate(
+        (width as f32 - size.width() * scale) / 2.0,
+        (height as f32 - size.height() * scale) / 2.0,
+    );
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+
+    let data = pixmap
+        .pixels()
+        .iter()
+        .map(|p| {
+            let c = p.demultiply();
+            C::from_rgba8(c.red(), c.green(), c.blue(), c.alpha())
+        })
+        .collect();
+    Image::from_data(u64::from(width), u64::from(height), data).ok_or_else(too_large)
+}
+
+/// Renders an SVG document into an image of `width` x `height` pixels.
+/// The drawing keeps its aspect ratio and is centered, the remaining area stays transparent.
+pub fn render_svg<C: Color>(data: &[u8], width: u32, height: u32) -> Result<Image<C>, CodecError> {
+    rasterize(&parse(data, None)?, width, height)
+}
+
+/// Renders an SVG document at the size it specifies
+pub fn decode_svg<C: Color>(data: &[u8]) -> Result<Image<C>, CodecError> {
+    let tree = parse(data, None)?;
+    let size = tree.size().to_int_size();
+    rasterize(&tree, size.width(), size.height())
+}
+
+/// Renders an SVG file, relative references in the file are resolved from its directory
+pub fn load_svg<C: Color, P: AsRef<Path>>(
+    path: P,
+    width: u32,
+    height: u32,
+) -> Result<Image<C>, CodecError> {
+    let path = path.as_ref();
+    let tree = parse(&std::fs::read(path)?, path.parent())?;
+    rasterize(&tree, width, height)
+}
+
+/// SVG files rendered at the sizes they were requested at,
+/// so that icons are only rasterized once per bar height
+pub struct SvgCache<C: Color> {
+    images: HashMap<(PathBuf, u32, u32), Arc<Image<C>>>,
+}
+
+impl<C: Color> Default for SvgCache<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Color> SvgCache<C> {
+    pub fn new() -> Self {
+        Self {
+            images: HashMap::new(),
+        }
+    }
+
+    /// Returns the file rendered at `width` x `height`, rendering it on the first request.
+    /// Failures are not cached, so a file that appears later is picked up.
+    pub fn get<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        width: u32,
+        height: u32,
+    ) -> Result<Arc<Image<C>>, CodecError> {
+        let key = (path.as_ref().to_path_buf(), width, height);
+        if let Some(image) = self.images.get(&key) {
+            return Ok(image.clone());
+        }
+        let image = Arc::new(load_svg(&key.0, width, height)?);
+        self.images.insert(key, image.clone());
+        Ok(image)
+    }
+
+    /// Drops all sizes of a file, e.g. after the icon theme changed
+    pub fn invalidate<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        self.images.retain(|(p, _, _), _| p != path);
+    }
+
+    pub fn clear(&mut self) {
+        self.images.clear();
+    }
+
+    /// The number of cached renderings
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::color::ColorRgba32;
+    use super::*;
+
+    fn square(color: &str) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"4\" height=\"4\">\
+             <rect width=\"4\" height=\"4\" fill=\"{}\"/></svg>",
+            color
+        )
+    }
+
+    /// The red channel in the center of an image
+    fn red(image: &Image<ColorRgba32>) -> u8 {
+        let (w, h) = image.res;
+        image.get_pixel_at(w / 2, h / 2).unwrap().r
+    }
+
+    #[test]
+    fn renderings_are_cached_per_size() {
+        let dir = std::env::temp_dir().join(format!("coffee-bar-svg-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("icon.svg");
+        let mut cache = SvgCache::<ColorRgba32>::new();
+        // failures are not cached
+        assert!(cache.get(&path, 8, 8).is_err());
+        assert!(cache.is_empty());
+
+        std::fs::write(&path, square("red")).unwrap();
+        let first = cache.get(&path, 8, 8).unwrap();
+        assert_eq!((first.res, red(&first)), ((8, 8), 255));
+        // the file is not read again for a size that was rendered before
+        std::fs::write(&path, square("blue")).unwrap();
+        let hit = cache.get(&path, 8, 8).unwrap();
+        assert!(Arc::ptr_eq(&first, &hit));
+        let other = cache.get(&path, 16, 8).unwrap();
+        assert_eq!((other.res, red(&other)), ((16, 8), 0));
+        assert_eq!(cache.len(), 2);
+
+        cache.invalidate(&path);
+        assert!(cache.is_empty());
+        assert_eq!(red(&cache.get(&path, 8, 8).unwrap()), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn renderings_keep_the_aspect_ratio() {
+        let image: Image<ColorRgba32> = render_svg(square("red").as_bytes(), 8, 4).unwrap();
+        let alpha = |x| image.get_pixel_at(x, 2).unwrap().a;
+        assert_eq!((alpha(1), alpha(2), alpha(5), alpha(6)), (0, 255, 255, 0));
+    }
+}
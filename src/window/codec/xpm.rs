@@ -0,0 +1,156 @@
+use super::super::color::{self, ColorRgba32};
+use super::super::Image;
+use super::CodecError;
+use std::collections::HashMap;
+
+fn invalid<S: Into<String>>(msg: S) -> CodecError {
+    CodecError::Invalid(msg.into())
+}
+
+/// Extracts the string literals of an XPM3 file, which is C source code
+fn string_literals(text: &str) -> Result<Vec<String>, CodecError> {
+    let mut strings = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => prev = c,
+                        None => return Err(invalid("unterminated comment")),
+                    }
+                }
+            }
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => s.extend(chars.next()),
+                        Some(c) => s.push(c),
+                        None => return Err(invalid("unterminated string")),
+                    }
+                }
+                strings.push(s);
+            }
+            _ => (),
+        }
+    }
+    Ok(strings)
+}
+
+/// Parses an XPM color, which is an X11 color name or a hex color.
+/// `None` is transparent, hex colors may have 1 to 4 digits per channel.
+fn parse_color(value: &str) -> Option<ColorRgba32> {
+    let rgb = |r, g, b| Some(ColorRgba32 { r, g, b, a: 255 });
+    if value.eq_ignore_ascii_case("none") || value.eq_ignore_ascii_case("transparent") {
+        return Some(ColorRgba32::default());
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits = hex.len() / 3;
+        if hex.len() % 3 != 0 || !(1..=4).contains(&digits) {
+            return None;
+        }
+        let channel = |i: usize| {
+            let v = u32::from_str_radix(hex.get(i * digits..(i + 1) * digits)?, 16).ok()?;
+            // scales the channel to 8 bits
+            let max = (1u32 << (4 * digits)) - 1;
+            Some(((v * 255 + max / 2) / max) as u8)
+        };
+        return rgb(channel(0)?, channel(1)?, channel(2)?);
+    }
+    color::x11_named_color(value)
+}
+
+/// Picks the color of a color definition like `c #ff0000 m black`.
+/// The color visual is preferred over grayscale and monochrome ones.
+fn parse_definition(def: &str) -> Result<ColorRgba32, CodecError> {
+    const KEYS: [&str; 5] = ["c", "g", "g4", "m", "s"];
+    let mut pairs: Vec<(&str, Vec<&str>)> = Vec::new();
+    for token in def.split_whitespace() {
+        // a key directly following a key is a value, e.g. the symbolic name in "s c"
+        let expects_value = pairs.last().is_some_and(|(_, value)| value.is_empty());
+        if KEYS.contains(&token) && !expects_value {
+            pairs.push((token, Vec::new()));
+        } else if let Some((_, value)) = pairs.last_mut() {
+            value.push(token);
+        } else {
+            return Err(invalid(format!("color definition \"{}\" lacks a key", def)));
+        }
+    }
+    ["c", "g", "g4", "m"]
+        .iter()
+        .find_map(|k| {
+            pairs
+                .iter()
+                .find(|(key, value)| key == k && !value.is_empty())
+        })
+        .map(|(_, value)| value.join(" "))
+        .ok_or_else(|| invalid(format!("color definition \"{}\" lacks a color", def)))
+        .and_then(|v| {
+            parse_color(&v).ok_or_else(|| CodecError::Unsupported(format!("color \"{}\"", v)))
+        })
+}
+
+/// Decodes an XPM3 image or an XPM2 image starting with `! XPM2`
+pub fn decode_xpm(text: &str) -> Result<Image<ColorRgba32>, CodecError> {
+    let lines = match text.strip_prefix("! XPM2") {
+        Some(rest) => rest
+            .lines()
+            .map(String::from)
+            .filter(|l| !l.is_empty())
+            .collect(),
+        None => string_literals(text)?,
+    };
+    let mut lines = lines.iter();
+    let header = lines.next().ok_or_else(|| invalid("missing header"))?;
+    let values = header
+        .split_whitespace()
+        .map(|v| v.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid(format!("malformed header \"{}\"", header)))?;
+    let (width, height, colors, cpp) = match values[..] {
+        [w, h, n, cpp, ..] if cpp > 0 => (w, h, n, cpp),
+        _ => return Err(invalid(format!("malformed header \"{}\"", header))),
+    };
+
+    // every color takes a line, so malformed headers can not reserve more
+    let mut palette = HashMap::with_capacity(colors.min(lines.len()));
+    for _ in 0..colors {
+        let line = lines.next().ok_or_else(|| invalid("missing colors"))?;
+        let split = line.char_indices().nth(cpp).map_or(line.len(), |(i, _)| i);
+        let (chars, def) = line.split_at(split);
+        palette.insert(chars, parse_definition(def)?);
+    }
+
+    let mut data = Vec::with_capacity(width.saturating_mul(height).min(1 << 24));
+    for _ in 0..height {
+        let line = lines.next().ok_or_else(|| invalid("missing pixel rows"))?;
+        let mut rest = line.as_str();
+        for _ in 0..width {
+            let split = rest.char_indices().nth(cpp).map_or(rest.len(), |(i, _)| i);
+            let (chars, tail) = rest.split_at(split);
+            let color = palette
+                .get(chars)
+                .ok_or_else(|| invalid(format!("undefined pixel \"{}\"", chars)))?;
+            data.push(color.clone());
+            rest = tail;
+        }
+    }
+    Image::from_data(width as u64, height as u64, data).ok_or_else(|| invalid("image is too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_color_counts_are_rejected_without_allocating() {
+        let text = "static char *icon[] = { \"1 1 18446744073709551615 1\", \"a c #ff0000\" };";
+        assert!(decode_xpm(text).is_err());
+        assert!(decode_xpm("! XPM2\n1 1 18446744073709551615 1\na c red\na\n").is_err());
+    }
+}
@@ -1,3 +1,13 @@
+mod math;
+mod names;
+mod parse;
+
+pub use math::{color_at, linear_to_srgb, srgb_to_linear, Hsl, Hsv};
+pub use parse::{
+    format_hex, format_hex_with, named_color, parse_color, parse_color_with, x11_named_color,
+    ColorParseError, HexOrder,
+};
+
 /// The PixelFormat describes how a pixel is stored in memory
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PixelFormat {
@@ -7,6 +17,13 @@ pub enum PixelFormat {
     Rgba32,
     /// alpha, red, green, blue, 8 bits each
     Argb32,
+    /// blue, green, red, alpha, 8 bits each,
+    /// which is how X servers on little-endian machines store 32 bit pixels
+    Bgra32,
+    /// red with 5, green with 6 and blue with 5 bits packed into 16 bits
+    Rgb565,
+    /// gray, 8 bits
+    Gray8,
 }
 
 impl PixelFormat {
@@ -15,27 +32,33 @@ impl PixelFormat {
             PixelFormat::Rgb24 => 24,
             PixelFormat::Rgba32 => 32,
             PixelFormat::Argb32 => 32,
+            PixelFormat::Bgra32 => 32,
+            PixelFormat::Rgb565 => 16,
+            PixelFormat::Gray8 => 8,
         }
     }
 
     pub const fn alpha_bits(&self) -> usize {
         match self {
-            PixelFormat::Rgb24 => 0,
+            PixelFormat::Rgb24 | PixelFormat::Rgb565 | PixelFormat::Gray8 => 0,
             PixelFormat::Rgba32 => 8,
             PixelFormat::Argb32 => 8,
+            PixelFormat::Bgra32 => 8,
         }
     }
 
     pub const fn has_alpha(&self) -> bool {
         match self {
-            PixelFormat::Rgb24 => false,
-            PixelFormat::Rgba32 | PixelFormat::Argb32 => true,
+            PixelFormat::Rgb24 | PixelFormat::Rgb565 | PixelFormat::Gray8 => false,
+            PixelFormat::Rgba32 | PixelFormat::Argb32 | PixelFormat::Bgra32 => true,
         }
     }
 }
 
 pub trait Color: Clone + Sized + Default {
     fn get_format() -> PixelFormat;
+    /// Creates a color from 8 bit channels, formats without alpha ignore `a`
+    fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self;
     fn r8(&self) -> u8;
     fn g8(&self) -> u8;
     fn b8(&self) -> u8;
@@ -55,6 +78,86 @@ pub trait Color: Clone + Sized + Default {
             a: self.a8(),
         }
     }
+    /// Converts the color into another format, alpha is dropped by formats without alpha
+    fn convert<T: Color>(&self) -> T {
+        T::from_rgba8(self.r8(), self.g8(), self.b8(), self.a8())
+    }
+    fn to_hsl(&self) -> Hsl {
+        math::to_hsl(self)
+    }
+    fn from_hsl(hsl: Hsl) -> Self {
+        math::from_hsl(hsl)
+    }
+    fn to_hsv(&self) -> Hsv {
+        math::to_hsv(self)
+    }
+    fn from_hsv(hsv: Hsv) -> Self {
+        math::from_hsv(hsv)
+    }
+    /// Interpolates from this color at `t = 0.0` to `other` at `t = 1.0` in linear light,
+    /// which avoids the dark middle of gradients mixed in sRGB
+    fn mix(&self, other: &Self, t: f32) -> Self {
+        math::mix(self, other, t)
+    }
+    /// Raises the HSL lightness by `amount`, `0.2` makes a color 20% lighter
+    fn lighten(&self, amount: f32) -> Self {
+        let mut hsl = self.to_hsl();
+        hsl.l += amount;
+        Self::from_hsl(hsl)
+    }
+    /// Lowers the HSL lightness by `amount`
+    fn darken(&self, amount: f32) -> Self {
+        self.lighten(-amount)
+    }
+    /// Raises the HSL saturation by `amount`, negative amounts desaturate
+    fn saturate(&self, amount: f32) -> Self {
+        let mut hsl = self.to_hsl();
+        hsl.s += amount;
+        Self::from_hsl(hsl)
+    }
+    /// Removes the saturation, leaving the gray of the same HSL lightness
+    fn grayscale(&self) -> Self {
+        self.saturate(-1.0)
+    }
+    /// Multiplies the alpha channel by `factor`, e.g. to fade a color in or out
+    fn multiply_alpha(&self, factor: f32) -> Self {
+        let a = (f32::from(self.a8()) * factor).round().clamp(0.0, 255.0) as u8;
+        Self::from_rgba8(self.r8(), self.g8(), self.b8(), a)
+    }
+}
+
+/// A [`Color`] with a fixed representation in memory,
+/// so that buffers of it can be exchanged with other programs
+pub trait PackedColor: Color {
+    /// The number of bytes of a pixel
+    const SIZE: usize;
+    /// Writes the pixel into the first [`Self::SIZE`] bytes of `out`
+    fn write_bytes(&self, out: &mut [u8]);
+    /// Reads a pixel from the first [`Self::SIZE`] bytes of `bytes`
+    fn read_bytes(bytes: &[u8]) -> Self;
+}
+
+/// Converts a slice of colors into another format, stopping at the end of the shorter slice
+pub fn convert_colors<S: Color, T: Color>(src: &[S], dst: &mut [T]) {
+    for (s, d) in src.iter().zip(dst) {
+        *d = s.convert();
+    }
+}
+
+/// Encodes colors into the memory representation of the format `T`,
+/// stopping when either the colors or the bytes run out
+pub fn encode_colors<S: Color, T: PackedColor>(src: &[S], out: &mut [u8]) {
+    for (s, d) in src.iter().zip(out.chunks_exact_mut(T::SIZE)) {
+        s.convert::<T>().write_bytes(d);
+    }
+}
+
+/// Decodes colors stored in the memory representation of the format `S`,
+/// stopping when either the bytes or the colors run out
+pub fn decode_colors<S: PackedColor, T: Color>(bytes: &[u8], dst: &mut [T]) {
+    for (s, d) in bytes.chunks_exact(S::SIZE).zip(dst) {
+        *d = S::read_bytes(s).convert();
+    }
 }
 
 /// A [`Color`] of [`PixelFormat::Rgba32`]
@@ -74,6 +177,9 @@ impl Color for ColorRgba32 {
     fn get_format() -> PixelFormat {
         PixelFormat::Rgba32
     }
+    fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
     fn r8(&self) -> u8 {
         self.r
     }
@@ -103,6 +209,107 @@ impl Color for ColorRgb24 {
     fn get_format() -> PixelFormat {
         PixelFormat::Rgb24
     }
+    fn from_rgba8(r: u8, g: u8, b: u8, _a: u8) -> Self {
+        Self { r, g, b }
+    }
+    fn r8(&self) -> u8 {
+        self.r
+    }
+    fn g8(&self) -> u8 {
+        self.g
+    }
+    fn b8(&self) -> u8 {
+        self.b
+    }
+    fn a8(&self) -> u8 {
+        255
+    }
+}
+
+impl PackedColor for ColorRgba32 {
+    const SIZE: usize = 4;
+    fn write_bytes(&self, out: &mut [u8]) {
+        out[..4].copy_from_slice(&[self.r, self.g, self.b, self.a]);
+    }
+    fn read_bytes(bytes: &[u8]) -> Self {
+        Self::from_rgba8(bytes[0], bytes[1], bytes[2], bytes[3])
+    }
+}
+
+impl PackedColor for ColorRgb24 {
+    const SIZE: usize = 3;
+    fn write_bytes(&self, out: &mut [u8]) {
+        out[..3].copy_from_slice(&[self.r, self.g, self.b]);
+    }
+    fn read_bytes(bytes: &[u8]) -> Self {
+        Self::from_rgba8(bytes[0], bytes[1], bytes[2], 255)
+    }
+}
+
+/// A [`Color`] of [`PixelFormat::Argb32`]
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ColorArgb32 {
+    /// alpha channel
+    pub a: u8,
+    /// red channel
+    pub r: u8,
+    /// green channel
+    pub g: u8,
+    /// blue channel
+    pub b: u8,
+}
+
+impl Color for ColorArgb32 {
+    fn get_format() -> PixelFormat {
+        PixelFormat::Argb32
+    }
+    fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { a, r, g, b }
+    }
+    fn r8(&self) -> u8 {
+        self.r
+    }
+    fn g8(&self) -> u8 {
+        self.g
+    }
+    fn b8(&self) -> u8 {
+        self.b
+    }
+    fn a8(&self) -> u8 {
+        self.a
+    }
+}
+
+impl PackedColor for ColorArgb32 {
+    const SIZE: usize = 4;
+    fn write_bytes(&self, out: &mut [u8]) {
+        out[..4].copy_from_slice(&[self.a, self.r, self.g, self.b]);
+    }
+    fn read_bytes(bytes: &[u8]) -> Self {
+        Self::from_rgba8(bytes[1], bytes[2], bytes[3], bytes[0])
+    }
+}
+
+/// A [`Color`] of [`PixelFormat::Bgra32`]
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ColorBgra32 {
+    /// blue channel
+    pub b: u8,
+    /// green channel
+    pub g: u8,
+    /// red channel
+    pub r: u8,
+    /// alpha channel
+    pub a: u8,
+}
+
+impl Color for ColorBgra32 {
+    fn get_format() -> PixelFormat {
+        PixelFormat::Bgra32
+    }
+    fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { b, g, r, a }
+    }
     fn r8(&self) -> u8 {
         self.r
     }
@@ -112,7 +319,202 @@ impl Color for ColorRgb24 {
     fn b8(&self) -> u8 {
         self.b
     }
+    fn a8(&self) -> u8 {
+        self.a
+    }
+}
+
+impl PackedColor for ColorBgra32 {
+    const SIZE: usize = 4;
+    fn write_bytes(&self, out: &mut [u8]) {
+        out[..4].copy_from_slice(&[self.b, self.g, self.r, self.a]);
+    }
+    fn read_bytes(bytes: &[u8]) -> Self {
+        Self::from_rgba8(bytes[2], bytes[1], bytes[0], bytes[3])
+    }
+}
+
+/// A [`Color`] of [`PixelFormat::Rgb565`], red is stored in the highest bits
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ColorRgb565(pub u16);
+
+/// Scales an 8 bit channel down to `bits` bits with rounding
+fn pack_channel(v: u8, bits: u32) -> u16 {
+    let max = (1u32 << bits) - 1;
+    ((u32::from(v) * max + 127) / 255) as u16
+}
+
+/// Scales a channel of `bits` bits up to 8 bits, so that the maximum becomes 255
+fn unpack_channel(v: u16, bits: u32) -> u8 {
+    let max = (1u32 << bits) - 1;
+    ((u32::from(v) * 255 + max / 2) / max) as u8
+}
+
+impl Color for ColorRgb565 {
+    fn get_format() -> PixelFormat {
+        PixelFormat::Rgb565
+    }
+    fn from_rgba8(r: u8, g: u8, b: u8, _a: u8) -> Self {
+        Self((pack_channel(r, 5) << 11) | (pack_channel(g, 6) << 5) | pack_channel(b, 5))
+    }
+    fn r8(&self) -> u8 {
+        unpack_channel(self.0 >> 11, 5)
+    }
+    fn g8(&self) -> u8 {
+        unpack_channel((self.0 >> 5) & 0x3f, 6)
+    }
+    fn b8(&self) -> u8 {
+        unpack_channel(self.0 & 0x1f, 5)
+    }
     fn a8(&self) -> u8 {
         255
     }
 }
+
+/// Stored in little-endian byte order like in framebuffers of little-endian machines
+impl PackedColor for ColorRgb565 {
+    const SIZE: usize = 2;
+    fn write_bytes(&self, out: &mut [u8]) {
+        out[..2].copy_from_slice(&self.0.to_le_bytes());
+    }
+    fn read_bytes(bytes: &[u8]) -> Self {
+        Self(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+/// A [`Color`] of [`PixelFormat::Gray8`]
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ColorGray8 {
+    /// gray value
+    pub v: u8,
+}
+
+impl Color for ColorGray8 {
+    fn get_format() -> PixelFormat {
+        PixelFormat::Gray8
+    }
+    /// Uses the luma weights of BT.601
+    fn from_rgba8(r: u8, g: u8, b: u8, _a: u8) -> Self {
+        let luma = 77 * u32::from(r) + 150 * u32::from(g) + 29 * u32::from(b);
+        Self {
+            v: ((luma + 128) >> 8) as u8,
+        }
+    }
+    fn r8(&self) -> u8 {
+        self.v
+    }
+    fn g8(&self) -> u8 {
+        self.v
+    }
+    fn b8(&self) -> u8 {
+        self.v
+    }
+    fn a8(&self) -> u8 {
+        255
+    }
+}
+
+impl PackedColor for ColorGray8 {
+    const SIZE: usize = 1;
+    fn write_bytes(&self, out: &mut [u8]) {
+        out[0] = self.v;
+    }
+    fn read_bytes(bytes: &[u8]) -> Self {
+        Self { v: bytes[0] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes<T: PackedColor>(c: &ColorRgba32) -> Vec<u8> {
+        let mut out = vec![0; T::SIZE];
+        c.convert::<T>().write_bytes(&mut out);
+        out
+    }
+
+    #[test]
+    fn rgb565_rounds_to_the_nearest_value() {
+        let pack = |r, g, b| ColorRgb565::from_rgba8(r, g, b, 0).0;
+        assert_eq!(
+            (pack(255, 0, 0), pack(0, 255, 0), pack(0, 0, 255)),
+            (0xf800, 0x07e0, 0x001f)
+        );
+        assert_eq!((pack(255, 255, 255), pack(0, 0, 0)), (0xffff, 0));
+        assert_eq!((pack(4, 0, 0), pack(5, 0, 0)), (0, 0x0800));
+        assert_eq!(pack(0, 128, 0), 32 << 5);
+        // the maximum of each channel becomes 255 again
+        let white = ColorRgb565(0xffff);
+        assert_eq!(
+            (white.r8(), white.g8(), white.b8(), white.a8()),
+            (255, 255, 255, 255)
+        );
+        assert_eq!(
+            (ColorRgb565(0x0800).r8(), ColorRgb565(32 << 5).g8()),
+            (8, 130)
+        );
+    }
+
+    #[test]
+    fn packed_colors_have_their_byte_order() {
+        let c = ColorRgba32::from_rgba8(1, 2, 3, 4);
+        assert_eq!(bytes::<ColorRgba32>(&c), [1, 2, 3, 4]);
+        assert_eq!(bytes::<ColorBgra32>(&c), [3, 2, 1, 4]);
+        assert_eq!(bytes::<ColorArgb32>(&c), [4, 1, 2, 3]);
+        assert_eq!(bytes::<ColorRgb24>(&c), [1, 2, 3]);
+        let red = ColorRgba32::from_rgba8(255, 0, 0, 255);
+        assert_eq!(bytes::<ColorRgb565>(&red), [0x00, 0xf8]);
+        assert_eq!(ColorBgra32::read_bytes(&[3, 2, 1, 4]).as_rgba32(), c);
+        assert_eq!(ColorArgb32::read_bytes(&[4, 1, 2, 3]).as_rgba32(), c);
+    }
+
+    #[test]
+    fn gray_uses_the_luma_of_the_channels() {
+        let gray = |r, g, b| ColorGray8::from_rgba8(r, g, b, 0).v;
+        assert_eq!((gray(255, 255, 255), gray(0, 0, 0)), (255, 0));
+        // green appears brighter than red, which appears brighter than blue
+        assert_eq!(
+            (gray(255, 0, 0), gray(0, 255, 0), gray(0, 0, 255)),
+            (77, 149, 29)
+        );
+        let c = ColorGray8 { v: 42 };
+        assert_eq!((c.r8(), c.g8(), c.b8(), c.a8()), (42, 42, 42, 255));
+        assert_eq!(bytes::<ColorGray8>(&c.as_rgba32()), [42]);
+    }
+
+    #[test]
+    fn encoded_colors_decode_to_the_same_colors() {
+        let colors: Vec<ColorRgba32> = (0..4u8)
+            .map(|i| ColorRgba32::from_rgba8(i * 60, 255 - i * 60, i, 255 - i))
+            .collect();
+        let mut decoded = vec![ColorRgba32::default(); 4];
+        let mut bgra = vec![0; 16];
+        encode_colors::<_, ColorBgra32>(&colors, &mut bgra);
+        decode_colors::<ColorBgra32, _>(&bgra, &mut decoded);
+        assert_eq!(decoded, colors);
+        let mut argb = vec![0; 16];
+        encode_colors::<_, ColorArgb32>(&colors, &mut argb);
+        decode_colors::<ColorArgb32, _>(&argb, &mut decoded);
+        assert_eq!(decoded, colors);
+
+        // formats without alpha decode opaque colors
+        let mut rgb = vec![0; 12];
+        encode_colors::<_, ColorRgb24>(&colors, &mut rgb);
+        decode_colors::<ColorRgb24, _>(&rgb, &mut decoded);
+        assert!(decoded.iter().all(|c| c.a == 255));
+        assert!(decoded
+            .iter()
+            .zip(&colors)
+            .all(|(d, c)| (d.r, d.g, d.b) == (c.r, c.g, c.b)));
+
+        // the shorter side limits the conversion
+        let mut short = vec![0; 6];
+        encode_colors::<_, ColorRgb565>(&colors, &mut short);
+        let mut decoded = vec![ColorRgba32::default(); 4];
+        decode_colors::<ColorRgb565, _>(&short, &mut decoded);
+        // 120 has no exact 5 bit representation
+        assert_eq!((decoded[2].r, decoded[2].g, decoded[2].a), (123, 134, 255));
+        assert_eq!(decoded[3], ColorRgba32::default());
+    }
+}
@@ -0,0 +1,275 @@
+//! Color spaces and the arithmetic behind the color operations of [`Color`]
+
+use super::Color;
+
+/// A color in the HSL model
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Hsl {
+    /// hue in degrees from `0.0` to `360.0`
+    pub h: f32,
+    /// saturation from `0.0` to `1.0`
+    pub s: f32,
+    /// lightness from `0.0` to `1.0`
+    pub l: f32,
+    /// alpha from `0.0` to `1.0`
+    pub a: f32,
+}
+
+impl Hsl {
+    pub const fn new(h: f32, s: f32, l: f32, a: f32) -> Self {
+        Self { h, s, l, a }
+    }
+}
+
+/// A color in the HSV model
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Hsv {
+    /// hue in degrees from `0.0` to `360.0`
+    pub h: f32,
+    /// saturation from `0.0` to `1.0`
+    pub s: f32,
+    /// value from `0.0` to `1.0`
+    pub v: f32,
+    /// alpha from `0.0` to `1.0`
+    pub a: f32,
+}
+
+impl Hsv {
+    pub const fn new(h: f32, s: f32, v: f32, a: f32) -> Self {
+        Self { h, s, v, a }
+    }
+}
+
+/// Converts an 8 bit sRGB channel to linear light from `0.0` to `1.0`
+pub fn srgb_to_linear(v: u8) -> f32 {
+    let v = f32::from(v) / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts linear light from `0.0` to `1.0` to an 8 bit sRGB channel
+pub fn linear_to_srgb(v: f32) -> u8 {
+    let v = v.clamp(0.0, 1.0);
+    let v = if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    to_channel(v)
+}
+
+/// Scales a value from `0.0` to `1.0` to an 8 bit channel
+fn to_channel(v: f32) -> u8 {
+    (v * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+fn channels<C: Color>(c: &C) -> [f32; 4] {
+    [c.r8(), c.g8(), c.b8(), c.a8()].map(|v| f32::from(v) / 255.0)
+}
+
+/// Returns the hue in degrees, the largest and the smallest channel
+fn hue_max_min(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let chroma = max - min;
+    let hue = if chroma == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / chroma + 2.0)
+    } else {
+        60.0 * ((r - g) / chroma + 4.0)
+    };
+    (hue, max, min)
+}
+
+/// Builds a color from a hue, its chroma and the amount added to every channel
+fn from_hue<C: Color>(h: f32, chroma: f32, m: f32, a: f32) -> C {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    C::from_rgba8(
+        to_channel(r + m),
+        to_channel(g + m),
+        to_channel(b + m),
+        to_channel(a),
+    )
+}
+
+pub(super) fn to_hsl<C: Color>(c: &C) -> Hsl {
+    let [r, g, b, a] = channels(c);
+    let (h, max, min) = hue_max_min(r, g, b);
+    let l = (max + min) / 2.0;
+    let s = if max == min {
+        0.0
+    } else {
+        (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+    };
+    Hsl { h, s, l, a }
+}
+
+pub(super) fn from_hsl<C: Color>(hsl: Hsl) -> C {
+    let (s, l) = (hsl.s.clamp(0.0, 1.0), hsl.l.clamp(0.0, 1.0));
+    let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    from_hue(hsl.h, chroma, l - chroma / 2.0, hsl.a)
+}
+
+pub(super) fn to_hsv<C: Color>(c: &C) -> Hsv {
+    let [r, g, b, a] = channels(c);
+    let (h, max, min) = hue_max_min(r, g, b);
+    let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+    Hsv { h, s, v: max, a }
+}
+
+pub(super) fn from_hsv<C: Color>(hsv: Hsv) -> C {
+    let (s, v) = (hsv.s.clamp(0.0, 1.0), hsv.v.clamp(0.0, 1.0));
+    let chroma = v * s;
+    from_hue(hsv.h, chroma, v - chroma, hsv.a)
+}
+
+/// Interpolates premultiplied colors in linear light,
+/// so that a transparent end does not darken the other one
+pub(super) fn mix<C: Color>(from: &C, to: &C, t: f32) -> C {
+    let t = t.clamp(0.0, 1.0);
+    let premultiplied = |c: &C| {
+        let a = f32::from(c.a8()) / 255.0;
+        ([c.r8(), c.g8(), c.b8()].map(|v| srgb_to_linear(v) * a), a)
+    };
+    let ((from, from_a), (to, to_a)) = (premultiplied(from), premultiplied(to));
+    let a = from_a + (to_a - from_a) * t;
+    let [r, g, b] = [0, 1, 2].map(|i| {
+        let v = from[i] + (to[i] - from[i]) * t;
+        if a > 0.0 {
+            linear_to_srgb(v / a)
+        } else {
+            0
+        }
+    });
+    C::from_rgba8(r, g, b, to_channel(a))
+}
+
+/// Picks the color for `value` on a scale of `(value, color)` stops sorted by value,
+/// mixing the two stops around it, e.g. green at `0.0` to red at `100.0` for a CPU load.
+/// Values outside of the scale get the color of the nearest end.
+pub fn color_at<C: Color>(stops: &[(f32, C)], value: f32) -> C {
+    let next = stops.iter().position(|(v, _)| *v > value);
+    match next {
+        None => stops.last().map(|(_, c)| c.clone()).unwrap_or_default(),
+        Some(0) => stops[0].1.clone(),
+        Some(i) => {
+            let ((v0, c0), (v1, c1)) = (&stops[i - 1], &stops[i]);
+            c0.mix(c1, (value - v0) / (v1 - v0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{ColorRgb24, ColorRgba32};
+    use super::*;
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> ColorRgba32 {
+        ColorRgba32 { r, g, b, a }
+    }
+
+    #[test]
+    fn hsl_and_hsv_round_trip() {
+        let colors = [
+            rgba(255, 0, 0, 255),
+            rgba(0, 255, 0, 255),
+            rgba(0, 0, 255, 255),
+            rgba(0, 0, 0, 255),
+            rgba(128, 128, 128, 255),
+            rgba(255, 255, 255, 255),
+            rgba(255, 128, 0, 255),
+            rgba(12, 200, 99, 7),
+        ];
+        for c in colors.iter() {
+            assert_eq!(&ColorRgba32::from_hsl(c.to_hsl()), c);
+            assert_eq!(&ColorRgba32::from_hsv(c.to_hsv()), c);
+        }
+        assert_eq!(
+            rgba(0, 0, 255, 255).to_hsl(),
+            Hsl::new(240.0, 1.0, 0.5, 1.0)
+        );
+        assert_eq!(
+            rgba(0, 255, 0, 255).to_hsv(),
+            Hsv::new(120.0, 1.0, 1.0, 1.0)
+        );
+        // grays have neither hue nor saturation
+        assert_eq!(rgba(51, 51, 51, 255).to_hsl(), Hsl::new(0.0, 0.0, 0.2, 1.0));
+        assert_eq!(rgba(51, 51, 51, 255).to_hsv(), Hsv::new(0.0, 0.0, 0.2, 1.0));
+    }
+
+    #[test]
+    fn mixing_happens_in_linear_light() {
+        let (black, white) = (rgba(0, 0, 0, 255), rgba(255, 255, 255, 255));
+        let mid = black.mix(&white, 0.5);
+        // an sRGB lerp would give 128, half of the light is brighter
+        assert_eq!(mid, rgba(188, 188, 188, 255));
+        assert_eq!(black.mix(&white, 0.0), black);
+        assert_eq!(black.mix(&white, 1.0), white);
+        assert_eq!(black.mix(&white, 2.0), white);
+        // a transparent end only fades the other one, without darkening it
+        let red = rgba(255, 0, 0, 255);
+        assert_eq!(red.mix(&rgba(0, 0, 0, 0), 0.5), rgba(255, 0, 0, 128));
+        assert_eq!(rgba(0, 0, 0, 0).mix(&red, 0.25), rgba(255, 0, 0, 64));
+    }
+
+    #[test]
+    fn color_scales() {
+        let (green, yellow, red) = (
+            rgba(0, 255, 0, 255),
+            rgba(255, 255, 0, 255),
+            rgba(255, 0, 0, 255),
+        );
+        let scale = [
+            (0.0, green.clone()),
+            (50.0, yellow.clone()),
+            (100.0, red.clone()),
+        ];
+        assert_eq!(color_at(&scale, -5.0), green);
+        assert_eq!(color_at(&scale, 0.0), green);
+        assert_eq!(color_at(&scale, 50.0), yellow);
+        assert_eq!(color_at(&scale, 75.0), yellow.mix(&red, 0.5));
+        assert_eq!(color_at(&scale, 100.0), red);
+        assert_eq!(color_at(&scale, 120.0), red);
+        assert_eq!(color_at::<ColorRgba32>(&[], 1.0), ColorRgba32::default());
+    }
+
+    #[test]
+    fn operations() {
+        let gray = rgba(128, 128, 128, 255);
+        assert_eq!(gray.lighten(0.2), rgba(179, 179, 179, 255));
+        assert_eq!(gray.darken(1.0), rgba(0, 0, 0, 255));
+        assert_eq!(
+            rgba(200, 100, 100, 255).grayscale(),
+            rgba(150, 150, 150, 255)
+        );
+        let muted = rgba(150, 120, 120, 255);
+        let saturated = muted.saturate(0.3).to_hsl();
+        assert!((saturated.s - muted.to_hsl().s - 0.3).abs() < 0.01);
+        assert!((saturated.l - muted.to_hsl().l).abs() < 0.01);
+        let red = rgba(255, 0, 0, 255);
+        assert_eq!(red.multiply_alpha(0.5), rgba(255, 0, 0, 128));
+        assert_eq!(red.multiply_alpha(-1.0), rgba(255, 0, 0, 0));
+        assert_eq!(
+            rgba(255, 0, 0, 200).multiply_alpha(2.0),
+            rgba(255, 0, 0, 255)
+        );
+        // colors without alpha stay opaque
+        let opaque = ColorRgb24 { r: 1, g: 2, b: 3 };
+        assert_eq!(opaque.multiply_alpha(0.0), opaque);
+    }
+}
@@ -0,0 +1,357 @@
+//! Parsing colors from text and formatting them as hex
+
+use super::names::{NAMES, X11_OVERRIDES};
+use super::{Color, ColorRgba32, Hsl};
+
+/// The order of the channels of hex colors with alpha
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexOrder {
+    /// `#rgba` and `#rrggbbaa` like in CSS
+    Rgba,
+    /// `#argb` and `#aarrggbb` like in i3 and polybar
+    Argb,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorParseError {
+    Empty,
+    /// A hex color with a length other than 3, 4, 6 or 8 digits or with non-hex characters
+    InvalidHex(String),
+    /// A malformed `rgb()`, `rgba()`, `hsl()` or `hsla()` color and the reason
+    InvalidFunction(String, String),
+    UnknownName(String),
+}
+
+impl std::fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty color"),
+            Self::InvalidHex(s) => write!(
+                f,
+                "invalid hex color \"{}\", expected 3, 4, 6 or 8 hex digits after '#'",
+                s
+            ),
+            Self::InvalidFunction(s, reason) => write!(f, "invalid color \"{}\": {}", s, reason),
+            Self::UnknownName(s) => write!(f, "unknown color name \"{}\"", s),
+        }
+    }
+}
+
+impl std::error::Error for ColorParseError {}
+
+fn find(table: &[(&str, [u8; 3])], name: &str) -> Option<ColorRgba32> {
+    let [r, g, b] = table
+        .binary_search_by(|(n, _)| n.cmp(&name))
+        .ok()
+        .map(|i| table[i].1)?;
+    Some(ColorRgba32 { r, g, b, a: 255 })
+}
+
+/// Removes spaces and lowercases a name, so that `Light Gray` matches `lightgray`
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Looks up an X11 or CSS color name, ignoring case and spaces.
+/// Names defined differently by both, like `gray` and `green`, have their CSS value.
+pub fn named_color(name: &str) -> Option<ColorRgba32> {
+    let name = normalize(name);
+    if name == "transparent" {
+        return Some(ColorRgba32::default());
+    }
+    find(&NAMES, &name)
+}
+
+/// Looks up a color name like X11 does, e.g. `gray` is `#bebebe` instead of `#808080`
+pub fn x11_named_color(name: &str) -> Option<ColorRgba32> {
+    let name = normalize(name);
+    find(&X11_OVERRIDES, &name).or_else(|| find(&NAMES, &name))
+}
+
+fn parse_hex(s: &str, order: HexOrder) -> Result<ColorRgba32, ColorParseError> {
+    let invalid = || ColorParseError::InvalidHex(s.to_string());
+    let digits = &s[1..];
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    // short forms repeat every digit
+    let width = match digits.len() {
+        3 | 4 => 1,
+        6 | 8 => 2,
+        _ => return Err(invalid()),
+    };
+    let channels: Vec<u8> = (0..digits.len() / width)
+        .map(|i| {
+            let v = u8::from_str_radix(&digits[i * width..(i + 1) * width], 16).unwrap_or(0);
+            if width == 1 {
+                v * 17
+            } else {
+                v
+            }
+        })
+        .collect();
+    let (r, g, b, a) = match (channels.as_slice(), order) {
+        (&[r, g, b], _) => (r, g, b, 255),
+        (&[r, g, b, a], HexOrder::Rgba) => (r, g, b, a),
+        (&[a, r, g, b], HexOrder::Argb) => (r, g, b, a),
+        _ => return Err(invalid()),
+    };
+    Ok(ColorRgba32 { r, g, b, a })
+}
+
+/// Splits the arguments of a color function by commas or, in the newer CSS syntax,
+/// by spaces with the alpha value separated by a slash
+fn split_arguments(args: &str) -> Vec<&str> {
+    if args.contains(',') {
+        args.split(',').map(str::trim).collect()
+    } else {
+        args.split(|c: char| c.is_whitespace() || c == '/')
+            .filter(|a| !a.is_empty())
+            .collect()
+    }
+}
+
+/// Parses a number or a percentage, which is scaled so that `100%` becomes `scale`
+fn parse_number(arg: &str, scale: f64) -> Option<f64> {
+    let value = match arg.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().ok()? / 100.0 * scale,
+        None => arg.parse().ok()?,
+    };
+    if value.is_finite() {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+fn channel(value: f64) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+fn parse_function(s: &str, name: &str, args: &str) -> Result<ColorRgba32, ColorParseError> {
+    let error = |reason: &str| ColorParseError::InvalidFunction(s.to_string(), reason.to_string());
+    let args = split_arguments(args);
+    if args.len() != 3 && args.len() != 4 {
+        return Err(error("expected 3 or 4 values"));
+    }
+    let alpha = match args.get(3) {
+        Some(arg) => {
+            channel(parse_number(arg, 1.0).ok_or_else(|| error("malformed alpha value"))? * 255.0)
+        }
+        None => 255,
+    };
+    match name {
+        "rgb" | "rgba" => {
+            let mut rgb = [0; 3];
+            for (c, arg) in rgb.iter_mut().zip(&args) {
+                *c = channel(parse_number(arg, 255.0).ok_or_else(|| error("malformed channel"))?);
+            }
+            let [r, g, b] = rgb;
+            Ok(ColorRgba32 { r, g, b, a: alpha })
+        }
+        _ => {
+            let hue = args[0].strip_suffix("deg").unwrap_or(args[0]).trim();
+            let hue = parse_number(hue, 1.0)
+                .filter(|_| !hue.ends_with('%'))
+                .ok_or_else(|| error("malformed hue"))?;
+            let percent = |arg: &str| {
+                arg.strip_suffix('%')
+                    .and_then(|_| parse_number(arg, 1.0))
+                    .ok_or_else(|| error("saturation and lightness must be percentages"))
+            };
+            let hsl = Hsl::new(
+                hue as f32,
+                percent(args[1])? as f32,
+                percent(args[2])? as f32,
+                1.0,
+            );
+            Ok(ColorRgba32 {
+                a: alpha,
+                ..ColorRgba32::from_hsl(hsl)
+            })
+        }
+    }
+}
+
+/// Parses a color in one of the notations
+/// `#rgb`, `#rrggbb`, `#rgba`/`#argb`, `#rrggbbaa`/`#aarrggbb` (by `order`),
+/// `rgb(r, g, b)`, `rgba(r, g, b, a)`, `hsl(h, s%, l%)`, `hsla(h, s%, l%, a)`
+/// or an X11 or CSS color name.
+/// Channels of `rgb()` go from 0 to 255 or 0% to 100%, alpha values from 0 to 1 or 0% to 100%.
+pub fn parse_color_with<C: Color>(s: &str, order: HexOrder) -> Result<C, ColorParseError> {
+    let s = s.trim();
+    let color = if s.is_empty() {
+        return Err(ColorParseError::Empty);
+    } else if s.starts_with('#') {
+        parse_hex(s, order)?
+    } else if let Some(open) = s.find('(') {
+        let name = s[..open].trim().to_ascii_lowercase();
+        let args = s[open + 1..].strip_suffix(')').ok_or_else(|| {
+            ColorParseError::InvalidFunction(s.to_string(), String::from("missing ')'"))
+        })?;
+        match name.as_str() {
+            "rgb" | "rgba" | "hsl" | "hsla" => parse_function(s, &name, args)?,
+            _ => {
+                return Err(ColorParseError::InvalidFunction(
+                    s.to_string(),
+                    format!("unknown function \"{}\"", name),
+                ))
+            }
+        }
+    } else {
+        named_color(s).ok_or_else(|| ColorParseError::UnknownName(s.to_string()))?
+    };
+    Ok(color.convert())
+}
+
+/// Parses a color with hex colors in CSS order, see [`parse_color_with`]
+pub fn parse_color<C: Color>(s: &str) -> Result<C, ColorParseError> {
+    parse_color_with(s, HexOrder::Rgba)
+}
+
+/// Formats a color as `#rrggbb`, or with alpha in the given order if it is not opaque
+pub fn format_hex_with<C: Color>(c: &C, order: HexOrder) -> String {
+    let (r, g, b, a) = (c.r8(), c.g8(), c.b8(), c.a8());
+    match order {
+        _ if a == 255 => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        HexOrder::Rgba => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+        HexOrder::Argb => format!("#{:02x}{:02x}{:02x}{:02x}", a, r, g, b),
+    }
+}
+
+/// Formats a color as `#rrggbb` or `#rrggbbaa`, which [`parse_color`] reads back
+pub fn format_hex<C: Color>(c: &C) -> String {
+    format_hex_with(c, HexOrder::Rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ColorRgb24;
+    use super::*;
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> ColorRgba32 {
+        ColorRgba32 { r, g, b, a }
+    }
+
+    fn parse(s: &str) -> Result<ColorRgba32, ColorParseError> {
+        parse_color(s)
+    }
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(parse("#f80"), Ok(rgba(255, 136, 0, 255)));
+        assert_eq!(parse(" #FF8000 "), Ok(rgba(255, 128, 0, 255)));
+        assert_eq!(parse("#f808"), Ok(rgba(255, 136, 0, 136)));
+        assert_eq!(parse("#ff800080"), Ok(rgba(255, 128, 0, 128)));
+        let argb = |s| parse_color_with::<ColorRgba32>(s, HexOrder::Argb);
+        assert_eq!(argb("#8f80"), Ok(rgba(255, 136, 0, 136)));
+        assert_eq!(argb("#80ff8000"), Ok(rgba(255, 128, 0, 128)));
+        // the order only matters for colors with alpha
+        assert_eq!(argb("#ff8000"), Ok(rgba(255, 128, 0, 255)));
+    }
+
+    #[test]
+    fn rgb_functions() {
+        assert_eq!(parse("rgb(255, 0, 10)"), Ok(rgba(255, 0, 10, 255)));
+        assert_eq!(
+            parse("rgba(100%, 0%, 50%, 0.5)"),
+            Ok(rgba(255, 0, 128, 128))
+        );
+        assert_eq!(parse("RGB(20%,40%,60%)"), Ok(rgba(51, 102, 153, 255)));
+        // the space separated syntax with a slash before alpha
+        assert_eq!(parse("rgb(255 0 0 / 25%)"), Ok(rgba(255, 0, 0, 64)));
+        assert_eq!(parse("rgb(0 128 255)"), Ok(rgba(0, 128, 255, 255)));
+        // channels out of range are clamped
+        assert_eq!(parse("rgb(300, -5, 0)"), Ok(rgba(255, 0, 0, 255)));
+    }
+
+    #[test]
+    fn hsl_functions() {
+        assert_eq!(parse("hsl(120, 100%, 50%)"), Ok(rgba(0, 255, 0, 255)));
+        assert_eq!(
+            parse("HSLA(240deg, 100%, 25%, 1)"),
+            Ok(rgba(0, 0, 128, 255))
+        );
+        assert_eq!(parse("hsl(0 0% 100% / 0.5)"), Ok(rgba(255, 255, 255, 128)));
+        assert_eq!(parse("hsl(-120, 100%, 50%)"), Ok(rgba(0, 0, 255, 255)));
+    }
+
+    #[test]
+    fn names_prefer_css_over_x11() {
+        assert_eq!(
+            parse("Light Goldenrod Yellow"),
+            Ok(rgba(250, 250, 210, 255))
+        );
+        assert_eq!(parse("gray"), Ok(rgba(128, 128, 128, 255)));
+        assert_eq!(named_color("gray"), Some(rgba(128, 128, 128, 255)));
+        assert_eq!(x11_named_color("gray"), Some(rgba(190, 190, 190, 255)));
+        assert_eq!(x11_named_color("gray50"), Some(rgba(127, 127, 127, 255)));
+        assert_eq!(parse("rebeccapurple"), Ok(rgba(102, 51, 153, 255)));
+        assert_eq!(parse("transparent"), Ok(rgba(0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn malformed_colors_report_what_is_wrong() {
+        assert_eq!(parse("  "), Err(ColorParseError::Empty));
+        for hex in ["#12", "#12345", "#12345g", "#ü12", "#"].iter() {
+            assert_eq!(
+                parse(hex),
+                Err(ColorParseError::InvalidHex(hex.to_string()))
+            );
+        }
+        let function = |s: &str, reason: &str| {
+            assert_eq!(
+                parse(s),
+                Err(ColorParseError::InvalidFunction(
+                    s.to_string(),
+                    reason.to_string()
+                ))
+            )
+        };
+        function("rgb(1, 2)", "expected 3 or 4 values");
+        function("rgb(1, 2, x)", "malformed channel");
+        function("rgba(1, 2, 3, half)", "malformed alpha value");
+        function("hsl(10%, 50%, 50%)", "malformed hue");
+        function(
+            "hsl(1, 2, 3)",
+            "saturation and lightness must be percentages",
+        );
+        function("rgb(1, 2, 3", "missing ')'");
+        function("foo(1, 2, 3)", "unknown function \"foo\"");
+        assert_eq!(
+            parse("notacolor"),
+            Err(ColorParseError::UnknownName(String::from("notacolor")))
+        );
+        assert_eq!(
+            ColorParseError::InvalidFunction("rgb(1)".into(), "expected 3 or 4 values".into())
+                .to_string(),
+            "invalid color \"rgb(1)\": expected 3 or 4 values"
+        );
+    }
+
+    #[test]
+    fn hex_formatting_round_trips() {
+        let colors = [
+            rgba(0x10, 0x20, 0x30, 255),
+            rgba(255, 136, 0, 255),
+            rgba(1, 2, 3, 4),
+            rgba(0xab, 0xcd, 0xef, 0x80),
+            rgba(0, 0, 0, 0),
+        ];
+        for c in colors.iter() {
+            assert_eq!(parse(&format_hex(c)).as_ref(), Ok(c));
+            let argb = format_hex_with(c, HexOrder::Argb);
+            assert_eq!(parse_color_with(&argb, HexOrder::Argb).as_ref(), Ok(c));
+        }
+        assert_eq!(format_hex(&rgba(1, 2, 3, 4)), "#01020304");
+        assert_eq!(
+            format_hex_with(&rgba(1, 2, 3, 4), HexOrder::Argb),
+            "#04010203"
+        );
+        let opaque: ColorRgb24 = parse_color("#102030").unwrap();
+        assert_eq!(format_hex(&opaque), "#102030");
+    }
+}
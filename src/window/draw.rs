@@ -1,33 +1,477 @@
-use super::color::Color;
+use super::color::{Color, ColorRgba32};
+use super::font::FontHandle;
+use super::Image;
+use core::convert::TryInto;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+/// An axis aligned rectangle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct Rect {
-    x: i32,
-    y: i32,
-    w: u32,
-    h: u32,
+    /// x-coordinate of the top left corner
+    pub x: i32,
+    /// y-coordinate of the top left corner
+    pub y: i32,
+    /// width
+    pub w: u32,
+    /// height
+    pub h: u32,
+}
+
+impl Rect {
+    pub const fn new(x: i32, y: i32, w: u32, h: u32) -> Self {
+        Self { x, y, w, h }
+    }
+}
+
+/// The radii of the corners of a rounded rectangle.
+/// Radii too large for the rectangle are scaled down proportionally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Radii {
+    pub top_left: u32,
+    pub top_right: u32,
+    pub bottom_right: u32,
+    pub bottom_left: u32,
+}
+
+impl Radii {
+    pub const fn new(top_left: u32, top_right: u32, bottom_right: u32, bottom_left: u32) -> Self {
+        Self {
+            top_left,
+            top_right,
+            bottom_right,
+            bottom_left,
+        }
+    }
+
+    /// The same radius for every corner
+    pub const fn all(r: u32) -> Self {
+        Self::new(r, r, r, r)
+    }
+}
+
+/// A circle around the top left corner of the pixel `(x, y)`,
+/// so that a circle of radius `r` exactly fills a square of `2 * r` pixels
+#[derive(Debug, Clone)]
+pub struct Circle {
+    /// x-coordinate of the center
+    pub x: i32,
+    /// y-coordinate of the center
+    pub y: i32,
+    /// radius
+    pub r: u32,
 }
 
+impl Circle {
+    pub const fn new(x: i32, y: i32, r: u32) -> Self {
+        Self { x, y, r }
+    }
+}
+
+/// A part of a circle's outline.
+/// Angles are given in degrees clockwise, starting at the 3 o'clock position.
+#[derive(Debug, Clone)]
+pub struct CircleArc {
+    pub circle: Circle,
+    /// The angle the arc starts at
+    pub start: f32,
+    /// The angle the arc spans, negative sweeps go counterclockwise
+    pub sweep: f32,
+}
+
+impl CircleArc {
+    pub const fn new(circle: Circle, start: f32, sweep: f32) -> Self {
+        Self {
+            circle,
+            start,
+            sweep,
+        }
+    }
+}
+
+/// A line segment from `(x, y)` to `(x + w, y + h)`
 #[derive(Debug, Clone)]
 pub struct Line {
-    x: i32,
-    y: i32,
-    w: i32,
-    h: i32,
+    /// x-coordinate of the starting point
+    pub x: i32,
+    /// y-coordinate of the starting point
+    pub y: i32,
+    /// horizontal distance to the end point
+    pub w: i32,
+    /// vertical distance to the end point
+    pub h: i32,
+}
+
+impl Line {
+    pub const fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    /// Creates a line between two points
+    pub const fn between(x1: i32, y1: i32, x2: i32, y2: i32) -> Self {
+        Self::new(x1, y1, x2 - x1, y2 - y1)
+    }
+}
+
+/// A closed shape through a list of corners, the last corner connects to the first.
+/// Corners lie in continuous coordinates, where the pixel `(x, y)` covers the square
+/// from `(x, y)` to `(x + 1, y + 1)`. Self-intersecting parts are filled by the non-zero rule.
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    pub points: Vec<(f32, f32)>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<(f32, f32)>) -> Self {
+        Self { points }
+    }
+
+    pub fn triangle(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> Self {
+        Self::new(vec![a, b, c])
+    }
+}
+
+/// Decides which parts of a shape crossing itself or holding other shapes are inside
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    /// Points are inside if the outlines wind around them more often
+    /// in one direction than in the other
+    #[default]
+    NonZero,
+    /// Points are inside if a ray from them crosses the outlines an odd number of times,
+    /// e.g. to cut holes by drawing them in the same direction
+    EvenOdd,
+}
+
+/// A step of a [`Path`], points are in continuous coordinates like the corners of a [`Polygon`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    /// Starts a new subpath at a point
+    MoveTo(f32, f32),
+    /// A straight line to a point
+    LineTo(f32, f32),
+    /// A quadratic bézier curve with a control point, to a point
+    QuadTo((f32, f32), (f32, f32)),
+    /// A cubic bézier curve with two control points, to a point
+    CubicTo((f32, f32), (f32, f32), (f32, f32)),
+    /// A straight line back to the start of the subpath, which ends it
+    Close,
+}
+
+/// Outlines made of lines and bézier curves.
+/// Paths not starting with [`PathSegment::MoveTo`] start at `(0.0, 0.0)`.
+#[derive(Debug, Clone, Default)]
+pub struct Path {
+    pub segments: Vec<PathSegment>,
 }
 
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A subpath through a list of points, e.g. for a graph
+    pub fn polyline<I: IntoIterator<Item = (f32, f32)>>(points: I) -> Self {
+        let mut points = points.into_iter();
+        let path = match points.next() {
+            Some((x, y)) => Self::new().move_to(x, y),
+            None => Self::new(),
+        };
+        points.fold(path, |path, (x, y)| path.line_to(x, y))
+    }
+
+    pub fn move_to(mut self, x: f32, y: f32) -> Self {
+        self.segments.push(PathSegment::MoveTo(x, y));
+        self
+    }
+
+    pub fn line_to(mut self, x: f32, y: f32) -> Self {
+        self.segments.push(PathSegment::LineTo(x, y));
+        self
+    }
+
+    pub fn quad_to(mut self, cx: f32, cy: f32, x: f32, y: f32) -> Self {
+        self.segments.push(PathSegment::QuadTo((cx, cy), (x, y)));
+        self
+    }
+
+    pub fn cubic_to(mut self, c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32) -> Self {
+        self.segments
+            .push(PathSegment::CubicTo((c1x, c1y), (c2x, c2y), (x, y)));
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        self.segments.push(PathSegment::Close);
+        self
+    }
+
+    /// Appends the subpaths of another path
+    pub fn append(mut self, other: Path) -> Self {
+        self.segments.extend(other.segments);
+        self
+    }
+
+    /// Iterates over all end and control points
+    pub fn points(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        self.segments.iter().flat_map(|segment| {
+            let points = match *segment {
+                PathSegment::MoveTo(x, y) | PathSegment::LineTo(x, y) => vec![(x, y)],
+                PathSegment::QuadTo(c, p) => vec![c, p],
+                PathSegment::CubicTo(c1, c2, p) => vec![c1, c2, p],
+                PathSegment::Close => Vec::new(),
+            };
+            points.into_iter()
+        })
+    }
+}
+
+impl From<Polygon> for Path {
+    fn from(polygon: Polygon) -> Self {
+        Path::polyline(polygon.points).close()
+    }
+}
+
+/// Describes how lines and outlines are stroked
 #[derive(Debug, Clone)]
 pub struct LineInfo<C: Color> {
-    width: u32,
-    color: C,
+    /// The line width in pixels, `0` draws the thinnest possible line
+    pub width: u32,
+    /// The line color
+    pub color: C,
+}
+
+impl<C: Color> LineInfo<C> {
+    pub const fn new(width: u32, color: C) -> Self {
+        Self { width, color }
+    }
+
+    /// Converts the line color
+    pub fn map_color<T: Color, F: Fn(C) -> T>(self, f: &F) -> LineInfo<T> {
+        LineInfo::new(self.width, f(self.color))
+    }
 }
 
+/// A color at a position of a gradient
+#[derive(Debug, Clone)]
+pub struct GradientStop<C: Color> {
+    /// The position along the gradient from `0.0` to `1.0`
+    pub offset: f32,
+    pub color: C,
+}
+
+impl<C: Color> GradientStop<C> {
+    pub const fn new(offset: f32, color: C) -> Self {
+        Self { offset, color }
+    }
+
+    pub fn map_color<T: Color, F: Fn(C) -> T>(self, f: &F) -> GradientStop<T> {
+        GradientStop::new(self.offset, f(self.color))
+    }
+}
+
+/// A gradient along a straight line through the filled shape.
+/// The line is chosen like CSS does, so that the first and last stop
+/// touch the corners of the shape's bounding box.
+#[derive(Debug, Clone)]
+pub struct LinearGradient<C: Color> {
+    /// The direction the colors change in, in degrees clockwise,
+    /// `0.0` goes from left to right and `90.0` from top to bottom
+    pub angle: f32,
+    /// Stops ordered by their offset, colors between them are interpolated
+    pub stops: Vec<GradientStop<C>>,
+}
+
+impl<C: Color> LinearGradient<C> {
+    pub fn new(angle: f32, stops: Vec<GradientStop<C>>) -> Self {
+        Self { angle, stops }
+    }
+
+    /// A gradient between two colors
+    pub fn between(angle: f32, from: C, to: C) -> Self {
+        Self::new(
+            angle,
+            vec![GradientStop::new(0.0, from), GradientStop::new(1.0, to)],
+        )
+    }
+}
+
+/// A gradient of circles around a center
+#[derive(Debug, Clone)]
+pub struct RadialGradient<C: Color> {
+    /// The horizontal position of the center relative to the bounding box,
+    /// `0.0` is the left and `1.0` the right edge
+    pub x: f32,
+    /// The vertical position of the center relative to the bounding box
+    pub y: f32,
+    /// The radius of the last stop, `1.0` reaches the farthest corner of the bounding box
+    pub radius: f32,
+    /// Stops ordered by their offset, colors between them are interpolated
+    pub stops: Vec<GradientStop<C>>,
+}
+
+impl<C: Color> RadialGradient<C> {
+    pub fn new(x: f32, y: f32, radius: f32, stops: Vec<GradientStop<C>>) -> Self {
+        Self {
+            x,
+            y,
+            radius,
+            stops,
+        }
+    }
+
+    /// A gradient from the center of the bounding box to its corners
+    pub fn centered(stops: Vec<GradientStop<C>>) -> Self {
+        Self::new(0.5, 0.5, 1.0, stops)
+    }
+}
+
+/// Describes the color of every pixel of a filled shape
+#[derive(Debug, Clone)]
+pub enum Paint<C: Color> {
+    Solid(C),
+    Linear(LinearGradient<C>),
+    Radial(RadialGradient<C>),
+}
+
+impl<C: Color> Paint<C> {
+    /// Converts every color of the paint
+    pub fn map_color<T: Color, F: Fn(C) -> T>(self, f: &F) -> Paint<T> {
+        let stops =
+            |stops: Vec<GradientStop<C>>| stops.into_iter().map(|s| s.map_color(f)).collect();
+        match self {
+            Self::Solid(c) => Paint::Solid(f(c)),
+            Self::Linear(g) => Paint::Linear(LinearGradient::new(g.angle, stops(g.stops))),
+            Self::Radial(g) => {
+                Paint::Radial(RadialGradient::new(g.x, g.y, g.radius, stops(g.stops)))
+            }
+        }
+    }
+}
+
+impl<C: Color> From<C> for Paint<C> {
+    fn from(color: C) -> Self {
+        Self::Solid(color)
+    }
+}
+
+impl<C: Color> From<LinearGradient<C>> for Paint<C> {
+    fn from(gradient: LinearGradient<C>) -> Self {
+        Self::Linear(gradient)
+    }
+}
+
+impl<C: Color> From<RadialGradient<C>> for Paint<C> {
+    fn from(gradient: RadialGradient<C>) -> Self {
+        Self::Radial(gradient)
+    }
+}
+
+/// A single line of text
+#[derive(Debug, Clone)]
+pub struct Text {
+    /// x-coordinate of the left end of the text
+    pub x: i32,
+    /// y-coordinate of the top of the text, the baseline lies one ascent below
+    pub y: i32,
+    pub text: String,
+    pub font: FontHandle,
+}
+
+impl Text {
+    pub fn new<S: Into<String>>(x: i32, y: i32, text: S, font: FontHandle) -> Self {
+        Self {
+            x,
+            y,
+            text: text.into(),
+            font,
+        }
+    }
+}
+
+/// An image placed onto a surface
+#[derive(Debug, Clone)]
+pub struct Blit {
+    /// x-coordinate of the top left corner
+    pub x: i32,
+    /// y-coordinate of the top left corner
+    pub y: i32,
+    /// The size the image is scaled to, `None` keeps its own size
+    pub size: Option<(u32, u32)>,
+    /// The opacity the image's own alpha is multiplied with
+    pub alpha: u8,
+    /// The image is shared, so that icons are decoded once and drawn every frame
+    pub image: Arc<Image<ColorRgba32>>,
+}
+
+impl Blit {
+    /// Places an opaque image in its own size
+    pub fn new(x: i32, y: i32, image: Arc<Image<ColorRgba32>>) -> Self {
+        Self {
+            x,
+            y,
+            size: None,
+            alpha: 255,
+            image,
+        }
+    }
+
+    /// Scales the image to `w` x `h` pixels
+    pub fn scaled(mut self, w: u32, h: u32) -> Self {
+        self.size = Some((w, h));
+        self
+    }
+
+    pub fn alpha(mut self, alpha: u8) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// The size the image is drawn in
+    pub fn get_size(&self) -> (u32, u32) {
+        self.size.unwrap_or_else(|| {
+            (
+                self.image.res.0.try_into().unwrap_or(u32::MAX),
+                self.image.res.1.try_into().unwrap_or(u32::MAX),
+            )
+        })
+    }
+}
+
+/// Something to draw onto a surface.
+/// Colors are composited over the existing content according to their alpha.
 #[derive(Debug, Clone)]
 pub enum DrawCommand<C: Color> {
-    FilledRect(Rect, C),
+    /// Replaces every pixel of the clip region including its alpha,
+    /// e.g. to reset a translucent background
+    Clear(C),
+    /// Fills a rectangle, gradients span the rectangle
+    FilledRect(Rect, Paint<C>),
     RectOutline(Rect, LineInfo<C>),
+    FilledRoundedRect(Rect, Radii, Paint<C>),
+    /// The outline is stroked inside of the rounded rectangle
+    RoundedRectOutline(Rect, Radii, LineInfo<C>),
+    FilledCircle(Circle, Paint<C>),
+    /// The outline is stroked inside of the circle
+    CircleOutline(Circle, LineInfo<C>),
+    /// Strokes an arc inside of its circle with flat ends, e.g. for circular gauges
+    Arc(CircleArc, LineInfo<C>),
     Line(Line, LineInfo<C>),
+    /// Fills a polygon with antialiased edges, gradients span its bounding box
+    FilledPolygon(Polygon, Paint<C>),
+    /// Fills the subpaths of a path, open ones are closed by a straight line.
+    /// Gradients span the bounding box of the curves.
+    FilledPath(Path, FillRule, Paint<C>),
+    /// Strokes a path centered on its outline with round joins and ends
+    PathOutline(Path, LineInfo<C>),
     Pixel(i32, i32, C),
+    /// Draws text, gradients span the text's advance and the font's height
+    Text(Text, Paint<C>),
+    /// Draws an image, scaling it smoothly if needed
+    Image(Blit),
+    /// Draws a command with the origin moved to `(x, y)` of the enclosing coordinates
+    Translate(i32, i32, Box<Self>),
+    /// Restricts a command to a rectangle given in the enclosing coordinates
+    Clip(Rect, Box<Self>),
     Chain(Vec<Self>),
 }
 
@@ -36,12 +480,55 @@ impl<C: Color> DrawCommand<C> {
         Self::Chain(Vec::new())
     }
 
+    /// Moves the origin of the command to `(x, y)`,
+    /// so that it can be drawn in local coordinates
+    pub fn translated(self, x: i32, y: i32) -> Self {
+        Self::Translate(x, y, Box::new(self))
+    }
+
+    /// Prevents the command from drawing outside of `rect`
+    pub fn clipped(self, rect: Rect) -> Self {
+        Self::Clip(rect, Box::new(self))
+    }
+
     pub fn iter(&self) -> DrawIter<C> {
         match self {
             Self::Chain(ref vec) => DrawIter::Many(vec.iter()),
             s => DrawIter::Single(core::iter::once(&s)),
         }
     }
+
+    /// Converts every color of the command tree
+    pub fn map_color<T: Color, F: Fn(C) -> T>(self, f: &F) -> DrawCommand<T> {
+        match self {
+            Self::Clear(c) => DrawCommand::Clear(f(c)),
+            Self::FilledRect(rect, p) => DrawCommand::FilledRect(rect, p.map_color(f)),
+            Self::RectOutline(rect, info) => DrawCommand::RectOutline(rect, info.map_color(f)),
+            Self::FilledRoundedRect(rect, radii, p) => {
+                DrawCommand::FilledRoundedRect(rect, radii, p.map_color(f))
+            }
+            Self::RoundedRectOutline(rect, radii, info) => {
+                DrawCommand::RoundedRectOutline(rect, radii, info.map_color(f))
+            }
+            Self::FilledCircle(circle, p) => DrawCommand::FilledCircle(circle, p.map_color(f)),
+            Self::CircleOutline(circle, info) => {
+                DrawCommand::CircleOutline(circle, info.map_color(f))
+            }
+            Self::Arc(arc, info) => DrawCommand::Arc(arc, info.map_color(f)),
+            Self::Line(line, info) => DrawCommand::Line(line, info.map_color(f)),
+            Self::FilledPolygon(polygon, p) => DrawCommand::FilledPolygon(polygon, p.map_color(f)),
+            Self::FilledPath(path, rule, p) => DrawCommand::FilledPath(path, rule, p.map_color(f)),
+            Self::PathOutline(path, info) => DrawCommand::PathOutline(path, info.map_color(f)),
+            Self::Pixel(x, y, c) => DrawCommand::Pixel(x, y, f(c)),
+            Self::Text(text, p) => DrawCommand::Text(text, p.map_color(f)),
+            Self::Image(blit) => DrawCommand::Image(blit),
+            Self::Translate(x, y, cmd) => DrawCommand::Translate(x, y, Box::new(cmd.map_color(f))),
+            Self::Clip(rect, cmd) => DrawCommand::Clip(rect, Box::new(cmd.map_color(f))),
+            Self::Chain(cmds) => {
+                DrawCommand::Chain(cmds.into_iter().map(|c| c.map_color(f)).collect())
+            }
+        }
+    }
 }
 
 impl<C: Color> core::ops::Add for DrawCommand<C> {
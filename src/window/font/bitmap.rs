@@ -0,0 +1,747 @@
+//! Loaders for the BDF and PCF bitmap font formats
+
+use super::{Font, FontError, FontMetrics, GlyphBitmap, GlyphId};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// The properties of a bitmap font used to look it up
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitmapFontInfo {
+    pub family: String,
+    pub weight: String,
+    pub italic: bool,
+    pub pixel_size: u16,
+}
+
+struct BitmapGlyph {
+    advance: i32,
+    bitmap: GlyphBitmap,
+}
+
+/// A font of prerendered glyphs, which are drawn without antialiasing
+pub struct BitmapFont {
+    name: String,
+    info: BitmapFontInfo,
+    ascent: i32,
+    descent: i32,
+    /// The glyph with the id 0 is the default character
+    glyphs: Vec<BitmapGlyph>,
+    chars: HashMap<char, GlyphId>,
+}
+
+fn invalid<S: Into<String>>(msg: S) -> FontError {
+    FontError::Invalid(msg.into())
+}
+
+/// Collects glyphs and properties while parsing
+#[derive(Default)]
+struct Builder {
+    properties: HashMap<String, String>,
+    glyphs: Vec<(Option<char>, BitmapGlyph)>,
+    default_char: Option<u32>,
+    ascent: Option<i32>,
+    descent: Option<i32>,
+}
+
+impl Builder {
+    fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(String::as_str)
+    }
+
+    fn int_property(&self, name: &str) -> Option<i32> {
+        self.property(name).and_then(|v| v.parse().ok())
+    }
+
+    /// Fills in missing properties from the XLFD font name
+    fn xlfd_field(&self, n: usize) -> Option<&str> {
+        self.property("FONT")
+            .and_then(|name| name.split('-').nth(n))
+            .filter(|f| !f.is_empty() && *f != "*")
+    }
+
+    fn ascent(&self) -> Result<i32, FontError> {
+        self.ascent
+            .or_else(|| self.int_property("FONT_ASCENT"))
+            .ok_or_else(|| invalid("missing font ascent"))
+    }
+
+    fn descent(&self) -> Result<i32, FontError> {
+        self.descent
+            .or_else(|| self.int_property("FONT_DESCENT"))
+            .ok_or_else(|| invalid("missing font descent"))
+    }
+
+    fn info(&self, name: &str) -> Result<BitmapFontInfo, FontError> {
+        let family = self
+            .property("FAMILY_NAME")
+            .or_else(|| self.xlfd_field(2))
+            .unwrap_or(name)
+            .to_owned();
+        let weight = self
+            .property("WEIGHT_NAME")
+            .or_else(|| self.xlfd_field(3))
+            .unwrap_or("Medium")
+            .to_owned();
+        let slant = self
+            .property("SLANT")
+            .or_else(|| self.xlfd_field(4))
+            .unwrap_or("R");
+        let italic = slant.eq_ignore_ascii_case("I") || slant.eq_ignore_ascii_case("O");
+        let pixel_size = match self
+            .int_property("PIXEL_SIZE")
+            .or_else(|| self.xlfd_field(7).and_then(|s| s.parse().ok()))
+        {
+            Some(size) => size,
+            None => self.ascent()?.saturating_add(self.descent()?),
+        };
+        Ok(BitmapFontInfo {
+            family,
+            weight,
+            italic,
+            pixel_size: pixel_size.clamp(0, i32::from(u16::MAX)) as u16,
+        })
+    }
+
+    fn build(self, name: &str) -> Result<BitmapFont, FontError> {
+        let info = self.info(name)?;
+        let (ascent, descent) = (self.ascent()?, self.descent()?);
+        let default = self.default_char.and_then(std::char::from_u32);
+        let default_glyph = self
+            .glyphs
+            .iter()
+            .position(|(c, _)| c.is_some() && *c == default);
+        let mut glyphs = Vec::with_capacity(self.glyphs.len() + 1);
+        glyphs.push(match default_glyph {
+            Some(i) => BitmapGlyph {
+                advance: self.glyphs[i].1.advance,
+                bitmap: self.glyphs[i].1.bitmap.clone(),
+            },
+            None => BitmapGlyph {
+                advance: 0,
+                bitmap: GlyphBitmap {
+                    left: 0,
+                    top: 0,
+                    width: 0,
+                    height: 0,
+                    coverage: Vec::new(),
+                },
+            },
+        });
+        let mut chars = HashMap::with_capacity(self.glyphs.len());
+        for (c, glyph) in self.glyphs {
+            if let Some(c) = c {
+                let id = GlyphId::try_from(glyphs.len())
+                    .map_err(|_| invalid("too many glyphs for a single font"))?;
+                chars.insert(c, id);
+                glyphs.push(glyph);
+            }
+        }
+        Ok(BitmapFont {
+            name: format!("{} {} {}px", info.family, info.weight, info.pixel_size),
+            info,
+            ascent,
+            descent,
+            glyphs,
+            chars,
+        })
+    }
+}
+
+/// Reads a set bit of a row, `msb_first` describes the order of pixels inside of a byte
+fn bit(row: &[u8], x: usize, msb_first: bool) -> bool {
+    let byte = row[x / 8];
+    if msb_first {
+        byte & (0x80 >> (x % 8)) != 0
+    } else {
+        byte & (1 << (x % 8)) != 0
+    }
+}
+
+/// Parses a BDF font, the glyphs are skipped unless `glyphs` is set
+fn read_bdf(text: &str, glyphs: bool) -> Result<Builder, FontError> {
+    let mut builder = Builder::default();
+    let mut lines = text.lines().map(str::trim);
+    let mut in_properties = false;
+    while let Some(line) = lines.next() {
+        let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        if in_properties {
+            if key == "ENDPROPERTIES" {
+                in_properties = false;
+            } else {
+                let value = rest.trim_matches('"').to_owned();
+                builder.properties.insert(key.to_owned(), value);
+            }
+            continue;
+        }
+        match key {
+            "FONT" => {
+                builder
+                    .properties
+                    .insert(String::from("FONT"), rest.to_owned());
+            }
+            "STARTPROPERTIES" => in_properties = true,
+            "CHARS" | "STARTCHAR" if !glyphs => break,
+            "STARTCHAR" => {
+                // every hex digit of a bitmap row holds at most four pixels
+                let max_pixels = text.len().saturating_mul(4);
+                let (c, glyph) = parse_bdf_char(&mut lines, max_pixels)?;
+                builder.glyphs.push((c, glyph));
+            }
+            _ => (),
+        }
+    }
+    builder.default_char = builder
+        .int_property("DEFAULT_CHAR")
+        .and_then(|c| u32::try_from(c).ok());
+    Ok(builder)
+}
+
+fn parse_ints(s: &str) -> Result<Vec<i32>, FontError> {
+    s.split_whitespace()
+        .map(|n| {
+            n.parse()
+                .map_err(|_| invalid(format!("invalid number {}", n)))
+        })
+        .collect()
+}
+
+/// Parses a glyph from `STARTCHAR` up to `ENDCHAR`,
+/// `max_pixels` limits the memory reserved before the bitmap rows are read
+fn parse_bdf_char<'a, I: Iterator<Item = &'a str>>(
+    lines: &mut I,
+    max_pixels: usize,
+) -> Result<(Option<char>, BitmapGlyph), FontError> {
+    let mut encoding = None;
+    let mut advance = 0;
+    let mut bbx = [0; 4];
+    let mut coverage = Vec::new();
+    while let Some(line) = lines.next() {
+        let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "ENCODING" => {
+                // glyphs without a standard encoding are stored as `-1` or `-1 n`
+                encoding = parse_ints(rest)?
+                    .first()
+                    .and_then(|&e| u32::try_from(e).ok())
+                    .and_then(std::char::from_u32);
+            }
+            "DWIDTH" => advance = *parse_ints(rest)?.first().unwrap_or(&0),
+            "BBX" => {
+                let values = parse_ints(rest)?;
+                if values.len() != 4 || values[0] < 0 || values[1] < 0 {
+                    return Err(invalid(format!("invalid bounding box \"{}\"", rest)));
+                }
+                bbx.copy_from_slice(&values);
+            }
+            "BITMAP" => {
+                let (w, h) = (bbx[0] as usize, bbx[1] as usize);
+                let area = w
+                    .checked_mul(h)
+                    .ok_or_else(|| invalid(format!("bitmap of {}x{} pixels is too large", w, h)))?;
+                coverage = Vec::with_capacity(area.min(max_pixels));
+                for _ in 0..h {
+                    let row = lines.next().ok_or_else(|| invalid("truncated bitmap"))?;
+                    if !row.is_ascii() {
+                        return Err(invalid(format!("invalid bitmap row \"{}\"", row)));
+                    }
+                    let bytes = (0..row.len() / 2)
+                        .map(|i| u8::from_str_radix(&row[2 * i..2 * i + 2], 16))
+                        .collect::<Result<Vec<u8>, _>>()
+                        .map_err(|_| invalid(format!("invalid bitmap row \"{}\"", row)))?;
+                    if bytes.len() * 8 < w {
+                        return Err(invalid(format!("bitmap row \"{}\" is too short", row)));
+                    }
+                    coverage.extend((0..w).map(|x| if bit(&bytes, x, true) { 255 } else { 0 }));
+                }
+            }
+            "ENDCHAR" => {
+                let bitmap = GlyphBitmap {
+                    left: bbx[2],
+                    top: bbx[3].saturating_add(bbx[1]).saturating_neg(),
+                    width: bbx[0] as u32,
+                    height: bbx[1] as u32,
+                    coverage,
+                };
+                return Ok((encoding, BitmapGlyph { advance, bitmap }));
+            }
+            _ => (),
+        }
+    }
+    Err(invalid("unterminated glyph"))
+}
+
+const PCF_PROPERTIES: u32 = 1 << 0;
+const PCF_ACCELERATORS: u32 = 1 << 1;
+const PCF_METRICS: u32 = 1 << 2;
+const PCF_BITMAPS: u32 = 1 << 3;
+const PCF_BDF_ENCODINGS: u32 = 1 << 5;
+const PCF_BDF_ACCELERATORS: u32 = 1 << 8;
+
+const PCF_COMPRESSED_METRICS: u32 = 0x100;
+const PCF_BYTE_MSB_FIRST: u32 = 1 << 2;
+const PCF_BIT_MSB_FIRST: u32 = 1 << 3;
+
+/// A reader for a single PCF table, which has its own byte order
+struct PcfTable<'a> {
+    data: &'a [u8],
+    pos: usize,
+    format: u32,
+}
+
+impl<'a> PcfTable<'a> {
+    /// Looks up a table in the table of contents
+    fn find(data: &'a [u8], kind: u32) -> Result<Option<Self>, FontError> {
+        let mut toc = PcfTable {
+            data,
+            pos: 4,
+            format: 0,
+        };
+        let count = toc.u32()?;
+        for _ in 0..count {
+            let (entry_kind, _format, size, offset) =
+                (toc.u32()?, toc.u32()?, toc.u32()?, toc.u32()?);
+            if entry_kind == kind {
+                let (start, end) = (offset as usize, offset as usize + size as usize);
+                let data = data
+                    .get(start..end)
+                    .ok_or_else(|| invalid("table exceeds the file"))?;
+                let mut table = PcfTable {
+                    data,
+                    pos: 0,
+                    format: 0,
+                };
+                // the format is always stored least significant byte first
+                table.format = table.u32()?;
+                return Ok(Some(table));
+            }
+        }
+        Ok(None)
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], FontError> {
+        let bytes = self
+            .pos
+            .checked_add(n)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| invalid("truncated table"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// The number of bytes after the current position
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    fn msb_first(&self) -> bool {
+        self.format & PCF_BYTE_MSB_FIRST != 0
+    }
+
+    fn u8(&mut self) -> Result<u8, FontError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, FontError> {
+        let msb = self.msb_first();
+        let b = self.bytes(2)?;
+        let b = [b[0], b[1]];
+        Ok(if msb {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn u32(&mut self) -> Result<u32, FontError> {
+        let msb = self.msb_first();
+        let b = self.bytes(4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if msb {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    fn i16(&mut self) -> Result<i16, FontError> {
+        self.u16().map(|v| v as i16)
+    }
+
+    fn i32(&mut self) -> Result<i32, FontError> {
+        self.u32().map(|v| v as i32)
+    }
+}
+
+/// The metrics of a PCF glyph
+struct PcfMetrics {
+    left_bearing: i32,
+    right_bearing: i32,
+    width: i32,
+    ascent: i32,
+    descent: i32,
+}
+
+fn parse_pcf_properties(table: &mut PcfTable) -> Result<HashMap<String, String>, FontError> {
+    let count = table.i32()?.max(0) as usize;
+    // every property takes nine bytes, so malformed counts can not reserve more
+    let mut props = Vec::with_capacity(count.min(table.remaining() / 9));
+    for _ in 0..count {
+        props.push((table.i32()?, table.u8()? != 0, table.i32()?));
+    }
+    if !count.is_multiple_of(4) {
+        table.bytes(4 - count % 4)?;
+    }
+    let size = table.i32()?.max(0) as usize;
+    let strings = table.bytes(size)?;
+    let string = |offset: i32| -> Result<String, FontError> {
+        let rest = strings
+            .get(offset.max(0) as usize..)
+            .ok_or_else(|| invalid("property string out of bounds"))?;
+        let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+    };
+    props
+        .into_iter()
+        .map(|(name, is_string, value)| {
+            let value = if is_string {
+                string(value)?
+            } else {
+                value.to_string()
+            };
+            Ok((string(name)?, value))
+        })
+        .collect()
+}
+
+fn parse_pcf_metrics(table: &mut PcfTable) -> Result<Vec<PcfMetrics>, FontError> {
+    if table.format & PCF_COMPRESSED_METRICS != 0 {
+        let count = table.u16()?;
+        (0..count)
+            .map(|_| {
+                let mut v = || table.u8().map(|b| i32::from(b) - 0x80);
+                Ok(PcfMetrics {
+                    left_bearing: v()?,
+                    right_bearing: v()?,
+                    width: v()?,
+                    ascent: v()?,
+                    descent: v()?,
+                })
+            })
+            .collect()
+    } else {
+        let count = table.i32()?.max(0);
+        (0..count)
+            .map(|_| {
+                let metrics = PcfMetrics {
+                    left_bearing: table.i16()?.into(),
+                    right_bearing: table.i16()?.into(),
+                    width: table.i16()?.into(),
+                    ascent: table.i16()?.into(),
+                    descent: table.i16()?.into(),
+                };
+                // attributes
+                table.u16()?;
+                Ok(metrics)
+            })
+            .collect()
+    }
+}
+
+fn parse_pcf_bitmaps(
+    table: &mut PcfTable,
+    metrics: &[PcfMetrics],
+) -> Result<Vec<GlyphBitmap>, FontError> {
+    let count = table.i32()?.max(0) as usize;
+    if count != metrics.len() {
+        return Err(invalid("glyph count of bitmaps and metrics differ"));
+    }
+    let offsets = (0..count)
+        .map(|_| table.i32().map(|o| o.max(0) as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+    let pad_index = (table.format & 3) as usize;
+    let mut sizes = [0; 4];
+    for size in sizes.iter_mut() {
+        *size = table.i32()?.max(0) as usize;
+    }
+    let data = table.bytes(sizes[pad_index])?;
+    let pad = 1 << pad_index;
+    let unit = 1 << ((table.format >> 4) & 3);
+    let bit_msb = table.format & PCF_BIT_MSB_FIRST != 0;
+    // bytes are swapped inside of a scan unit if byte and bit order differ
+    let swap = unit > 1 && (table.format & PCF_BYTE_MSB_FIRST != 0) != bit_msb;
+
+    offsets
+        .iter()
+        .zip(metrics)
+        .map(|(&offset, m)| {
+            let w = (m.right_bearing - m.left_bearing).max(0) as usize;
+            let h = (m.ascent + m.descent).max(0) as usize;
+            let stride = w.div_ceil(8);
+            let stride = stride.div_ceil(pad) * pad;
+            let bytes = data
+                .get(offset..offset + stride * h)
+                .ok_or_else(|| invalid("glyph bitmap out of bounds"))?;
+            let mut coverage = Vec::with_capacity(w * h);
+            let mut row = vec![0; stride];
+            for y in 0..h {
+                row.copy_from_slice(&bytes[y * stride..(y + 1) * stride]);
+                if swap {
+                    row.chunks_mut(unit).for_each(|c| c.reverse());
+                }
+                coverage.extend((0..w).map(|x| if bit(&row, x, bit_msb) { 255 } else { 0 }));
+            }
+            Ok(GlyphBitmap {
+                left: m.left_bearing,
+                top: -m.ascent,
+                width: w as u32,
+                height: h as u32,
+                coverage,
+            })
+        })
+        .collect()
+}
+
+/// Parses a PCF font as produced by `bdftopcf`, the glyphs are skipped unless `glyphs` is set
+fn read_pcf(data: &[u8], glyphs: bool) -> Result<Builder, FontError> {
+    if !data.starts_with(b"\x01fcp") {
+        return Err(invalid("missing PCF signature"));
+    }
+    let table = |kind| {
+        PcfTable::find(data, kind)?.ok_or_else(|| invalid(format!("missing PCF table {}", kind)))
+    };
+    let mut builder = Builder {
+        properties: parse_pcf_properties(&mut table(PCF_PROPERTIES)?)?,
+        ..Default::default()
+    };
+    let accelerators = match PcfTable::find(data, PCF_BDF_ACCELERATORS)? {
+        Some(table) => Some(table),
+        None => PcfTable::find(data, PCF_ACCELERATORS)?,
+    };
+    if let Some(mut acc) = accelerators {
+        // skip the eight flag bytes
+        acc.bytes(8)?;
+        builder.ascent = Some(acc.i32()?);
+        builder.descent = Some(acc.i32()?);
+    }
+    if !glyphs {
+        return Ok(builder);
+    }
+
+    let metrics = parse_pcf_metrics(&mut table(PCF_METRICS)?)?;
+    let bitmaps = parse_pcf_bitmaps(&mut table(PCF_BITMAPS)?, &metrics)?;
+    let mut enc = table(PCF_BDF_ENCODINGS)?;
+    let (min_b2, max_b2) = (enc.i16()?, enc.i16()?);
+    let (min_b1, max_b1) = (enc.i16()?, enc.i16()?);
+    let default_char = enc.i16()?;
+    builder.default_char = u32::try_from(default_char).ok();
+    let mut glyphs: Vec<Option<BitmapGlyph>> = metrics
+        .iter()
+        .zip(bitmaps)
+        .map(|(m, bitmap)| {
+            Some(BitmapGlyph {
+                advance: m.width,
+                bitmap,
+            })
+        })
+        .collect();
+    for b1 in min_b1..=max_b1 {
+        for b2 in min_b2..=max_b2 {
+            let index = enc.u16()?;
+            let code = (i32::from(b1) << 8) | i32::from(b2);
+            let c = u32::try_from(code).ok().and_then(std::char::from_u32);
+            if let Some(glyph) = glyphs.get_mut(usize::from(index)).and_then(Option::take) {
+                builder.glyphs.push((c, glyph));
+            }
+        }
+    }
+    Ok(builder)
+}
+
+/// Parses a BDF or PCF font, the format is detected by its content
+fn read(data: &[u8], name: &str, glyphs: bool) -> Result<Builder, FontError> {
+    if data.starts_with(b"\x01fcp") {
+        read_pcf(data, glyphs)
+    } else if data.starts_with(b"STARTFONT") {
+        let text = std::str::from_utf8(data).map_err(|_| invalid("BDF is not valid UTF-8"))?;
+        read_bdf(text, glyphs)
+    } else {
+        Err(invalid(format!("{} is neither a BDF nor a PCF font", name)))
+    }
+}
+
+/// Opens a font file, which may be compressed with gzip
+fn open(path: &Path) -> Result<Box<dyn BufRead>, FontError> {
+    let mut file = BufReader::new(std::fs::File::open(path)?);
+    if file.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        Ok(Box::new(BufReader::new(flate2::read::GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// The file name without the font extensions
+fn font_name(path: &Path) -> String {
+    path.file_name().map_or_else(String::new, |n| {
+        n.to_string_lossy()
+            .trim_end_matches(".gz")
+            .trim_end_matches(".pcf")
+            .trim_end_matches(".bdf")
+            .to_owned()
+    })
+}
+
+impl BitmapFont {
+    /// Parses a font from memory, the format is detected by its content
+    pub fn from_data(data: &[u8], name: &str) -> Result<Self, FontError> {
+        read(data, name, true)?.build(name)
+    }
+
+    /// Loads a `.bdf` or `.pcf` font, which may be compressed with gzip
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, FontError> {
+        let path = path.as_ref();
+        let mut data = Vec::new();
+        open(path)?.read_to_end(&mut data)?;
+        Self::from_data(&data, &font_name(path))
+    }
+
+    /// Reads the properties of a font file without loading its glyphs.
+    /// BDF files are only read up to their first glyph.
+    pub fn load_info<P: AsRef<Path>>(path: P) -> Result<BitmapFontInfo, FontError> {
+        let path = path.as_ref();
+        let name = font_name(path);
+        let mut file = open(path)?;
+        let mut data = Vec::new();
+        if file.fill_buf()?.starts_with(b"STARTFONT") {
+            loop {
+                let start = data.len();
+                if file.read_until(b'\n', &mut data)? == 0 {
+                    break;
+                }
+                let key = data[start..]
+                    .split(u8::is_ascii_whitespace)
+                    .find(|word| !word.is_empty());
+                if key == Some(b"CHARS") || key == Some(b"STARTCHAR") {
+                    break;
+                }
+            }
+        } else {
+            file.read_to_end(&mut data)?;
+        }
+        read(&data, &name, false)?.info(&name)
+    }
+
+    /// Returns whether a path names a bitmap font file
+    pub fn is_bitmap_font_file(path: &Path) -> bool {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_ascii_lowercase());
+        let name = name.as_deref().unwrap_or("");
+        let name = name.strip_suffix(".gz").unwrap_or(name);
+        name.ends_with(".bdf") || name.ends_with(".pcf")
+    }
+
+    pub fn info(&self) -> &BitmapFontInfo {
+        &self.info
+    }
+}
+
+impl Font for BitmapFont {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn size(&self) -> f32 {
+        f32::from(self.info.pixel_size)
+    }
+
+    fn metrics(&self) -> FontMetrics {
+        FontMetrics {
+            ascent: self.ascent as f32,
+            descent: self.descent as f32,
+            line_gap: 0.0,
+        }
+    }
+
+    fn glyph_id(&self, c: char) -> Option<GlyphId> {
+        self.chars.get(&c).copied()
+    }
+
+    fn advance(&self, glyph: GlyphId) -> f32 {
+        self.glyphs
+            .get(usize::from(glyph))
+            .map_or(0.0, |g| g.advance as f32)
+    }
+
+    /// Returns the prerendered glyph, bitmap glyphs are always aligned to pixels
+    fn rasterize(&self, glyph: GlyphId, _offset: f32) -> Option<GlyphBitmap> {
+        self.glyphs
+            .get(usize::from(glyph))
+            .map(|g| g.bitmap.clone())
+            .filter(|b| b.width > 0 && b.height > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A BDF font with a single glyph made of the given lines
+    fn bdf(glyph: &str) -> Result<BitmapFont, FontError> {
+        let text = format!(
+            "STARTFONT 2.1\nSTARTPROPERTIES 2\nFONT_ASCENT 7\nFONT_DESCENT 1\nENDPROPERTIES\n\
+             CHARS 1\nSTARTCHAR A\nENCODING 65\nDWIDTH 6 0\n{}\nENDCHAR\nENDFONT\n",
+            glyph
+        );
+        BitmapFont::from_data(text.as_bytes(), "test")
+    }
+
+    #[test]
+    fn glyphs_are_read_from_hex_rows() {
+        let font = bdf("BBX 4 2 1 -1\nBITMAP\n90\n60").unwrap();
+        let bitmap = font.rasterize(font.glyph_id('A').unwrap(), 0.0).unwrap();
+        assert_eq!((bitmap.left, bitmap.top), (1, -1));
+        assert_eq!(bitmap.coverage, [255, 0, 0, 255, 0, 255, 255, 0]);
+    }
+
+    #[test]
+    fn non_ascii_rows_are_rejected() {
+        assert!(matches!(
+            bdf("BBX 8 1 0 0\nBITMAP\n€"),
+            Err(FontError::Invalid(_))
+        ));
+        assert!(matches!(
+            bdf("BBX 16 1 0 0\nBITMAP\n0ä0"),
+            Err(FontError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn huge_bounding_boxes_are_rejected_without_allocating() {
+        let max = i32::MAX;
+        let huge = format!("BBX {} {} 0 0\nBITMAP\n00", max, max);
+        assert!(matches!(bdf(&huge), Err(FontError::Invalid(_))));
+        let tall = format!("BBX 8 {} 0 0\nBITMAP\nFF", max);
+        assert!(matches!(bdf(&tall), Err(FontError::Invalid(_))));
+        // offsets at the end of the coordinate space do not overflow
+        let far = format!("BBX 1 1 0 {}\nBITMAP\n80", max);
+        assert!(bdf(&far).is_ok());
+    }
+
+    #[test]
+    fn huge_pcf_property_counts_are_rejected_without_allocating() {
+        let mut pcf = b"\x01fcp".to_vec();
+        // a table of contents with only the properties table
+        for value in [1, PCF_PROPERTIES, 0, 8, 24].iter() {
+            pcf.extend_from_slice(&u32::to_le_bytes(*value));
+        }
+        // the format and a property count of 2^31 - 1
+        pcf.extend_from_slice(&[0, 0, 0, 0, 0xff, 0xff, 0xff, 0x7f]);
+        assert!(matches!(
+            BitmapFont::from_data(&pcf, "test"),
+            Err(FontError::Invalid(_))
+        ));
+    }
+}
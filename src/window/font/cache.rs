@@ -0,0 +1,249 @@
+//! A cache of rasterized glyphs shared by all surfaces
+
+use super::{Font, GlyphBitmap, GlyphId};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+/// The number of subpixel positions glyphs are rasterized at
+pub const SUBPIXEL_STEPS: u8 = 4;
+
+/// The memory budget of the global cache in bytes
+const DEFAULT_BUDGET: usize = 4 << 20;
+
+/// The estimated memory used by an entry besides its coverage
+const ENTRY_OVERHEAD: usize = 96;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    /// The address of the font, which can not be reused while an entry holds a weak reference
+    font: usize,
+    /// The bits of the em size in pixels
+    size: u32,
+    glyph: GlyphId,
+    subpixel: u8,
+}
+
+struct Entry {
+    /// Keeps the address of the font from being reused by another font
+    _font: Weak<dyn Font>,
+    /// `None` for glyphs without an outline like spaces
+    mask: Option<Arc<GlyphBitmap>>,
+    last_use: u64,
+    bytes: usize,
+}
+
+/// Counters to observe the efficiency of a [`GlyphCache`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GlyphCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// The number of glyphs currently cached
+    pub entries: usize,
+    /// The estimated memory currently used in bytes
+    pub bytes: usize,
+}
+
+/// Rasterized glyphs keyed by font, size, glyph and subpixel offset.
+/// The least recently used glyphs are evicted when the memory budget is exceeded.
+pub struct GlyphCache {
+    entries: HashMap<GlyphKey, Entry>,
+    /// Maps the time of the last use to the key of the entry
+    lru: BTreeMap<u64, GlyphKey>,
+    clock: u64,
+    budget: usize,
+    stats: GlyphCacheStats,
+}
+
+impl GlyphCache {
+    /// Creates an empty cache holding at most `budget` bytes
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+            budget,
+            stats: GlyphCacheStats::default(),
+        }
+    }
+
+    /// The cache used when drawing text
+    pub fn global() -> &'static Mutex<GlyphCache> {
+        static CACHE: OnceLock<Mutex<GlyphCache>> = OnceLock::new();
+        CACHE.get_or_init(|| Mutex::new(GlyphCache::new(DEFAULT_BUDGET)))
+    }
+
+    /// Splits a pen position into the pixel the glyph is drawn at
+    /// and the subpixel step it is rasterized with
+    pub fn quantize(pen: f64) -> (i64, u8) {
+        let steps = f64::from(SUBPIXEL_STEPS);
+        let pos = (pen * steps).round() as i64;
+        let steps = i64::from(SUBPIXEL_STEPS);
+        (pos.div_euclid(steps), pos.rem_euclid(steps) as u8)
+    }
+
+    /// Returns the glyph rasterized at the subpixel step `subpixel`,
+    /// rasterizing and inserting it on a miss
+    pub fn get(
+        &mut self,
+        font: &Arc<dyn Font>,
+        glyph: GlyphId,
+        subpixel: u8,
+    ) -> Option<Arc<GlyphBitmap>> {
+        let key = GlyphKey {
+            font: Arc::as_ptr(font) as *const () as usize,
+            size: font.size().to_bits(),
+            glyph,
+            subpixel,
+        };
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            self.stats.hits += 1;
+            self.lru.remove(&entry.last_use);
+            self.lru.insert(self.clock, key);
+            entry.last_use = self.clock;
+            return entry.mask.clone();
+        }
+
+        self.stats.misses += 1;
+        let offset = f32::from(subpixel) / f32::from(SUBPIXEL_STEPS);
+        let mask = font.rasterize(glyph, offset).map(Arc::new);
+        let bytes = ENTRY_OVERHEAD + mask.as_ref().map_or(0, |m| m.coverage.len());
+        self.stats.bytes += bytes;
+        self.entries.insert(
+            key,
+            Entry {
+                _font: Arc::downgrade(font),
+                mask: mask.clone(),
+                last_use: self.clock,
+                bytes,
+            },
+        );
+        self.lru.insert(self.clock, key);
+        self.evict();
+        mask
+    }
+
+    /// Evicts the least recently used entries until the budget is met
+    fn evict(&mut self) {
+        while self.stats.bytes > self.budget {
+            let key = match self.lru.pop_first() {
+                Some((_, key)) => key,
+                None => break,
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.stats.bytes -= entry.bytes;
+                self.stats.evictions += 1;
+            }
+        }
+    }
+
+    /// Changes the memory budget, evicting entries if it shrinks
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    pub fn stats(&self) -> GlyphCacheStats {
+        GlyphCacheStats {
+            entries: self.entries.len(),
+            ..self.stats
+        }
+    }
+
+    /// Resets the hit, miss and eviction counters
+    pub fn reset_stats(&mut self) {
+        self.stats = GlyphCacheStats {
+            bytes: self.stats.bytes,
+            ..Default::default()
+        };
+    }
+
+    /// Removes all entries without counting them as evictions
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.lru.clear();
+        self.stats.bytes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{BitmapFont, FontHandle};
+    use super::*;
+
+    /// A bitmap font drawing every character of the tests as a box
+    fn font() -> FontHandle {
+        let mut bdf = String::from(
+            "STARTFONT 2.1\nSTARTPROPERTIES 2\nFONT_ASCENT 7\nFONT_DESCENT 1\nENDPROPERTIES\n",
+        );
+        for c in "VOLUME 40%".chars() {
+            bdf += &format!(
+                "STARTCHAR box\nENCODING {}\nDWIDTH 6 0\nBBX 5 7 0 0\nBITMAP\n{}ENDCHAR\n",
+                u32::from(c),
+                "F8\n".repeat(7)
+            );
+        }
+        FontHandle::new(BitmapFont::from_data(bdf.as_bytes(), "box").unwrap())
+    }
+
+    /// Looks up the glyphs of a text like drawing it at `x` does
+    fn draw(cache: &mut GlyphCache, font: &FontHandle, text: &str, x: f64) {
+        let (glyphs, _) = font.layout(text);
+        for glyph in glyphs {
+            let (_, subpixel) = GlyphCache::quantize(x + f64::from(glyph.x));
+            cache.get(&font.fonts()[glyph.font], glyph.id, subpixel);
+        }
+    }
+
+    #[test]
+    fn redraws_hit_the_cache() {
+        let (mut cache, font) = (GlyphCache::new(DEFAULT_BUDGET), font());
+        draw(&mut cache, &font, "VOLUME 40%", 0.0);
+        let first = cache.stats();
+        assert_eq!((first.hits, first.misses), (0, 10));
+        cache.reset_stats();
+        draw(&mut cache, &font, "VOLUME 40%", 0.0);
+        let second = cache.stats();
+        assert_eq!((second.hits, second.misses), (10, 0));
+        assert_eq!((second.entries, second.bytes), (first.entries, first.bytes));
+    }
+
+    #[test]
+    fn other_subpixel_offsets_miss() {
+        let (mut cache, font) = (GlyphCache::new(DEFAULT_BUDGET), font());
+        draw(&mut cache, &font, "VOLUME", 0.0);
+        cache.reset_stats();
+        draw(&mut cache, &font, "VOLUME", 0.25);
+        assert_eq!((cache.stats().hits, cache.stats().misses), (0, 6));
+        // whole pixels keep the subpixel step
+        draw(&mut cache, &font, "VOLUME", 3.25);
+        assert_eq!((cache.stats().hits, cache.stats().misses), (6, 6));
+        assert_eq!(cache.stats().entries, 12);
+    }
+
+    #[test]
+    fn shrinking_the_budget_evicts_the_least_recently_used_glyphs() {
+        let (mut cache, font) = (GlyphCache::new(DEFAULT_BUDGET), font());
+        draw(&mut cache, &font, "VOLUME", 0.0);
+        let bytes = cache.stats().bytes;
+        // the first glyph becomes the most recently used one
+        draw(&mut cache, &font, "V", 0.0);
+        cache.set_budget(bytes - 1);
+        let stats = cache.stats();
+        assert_eq!((stats.evictions, stats.entries), (1, 5));
+        assert!(stats.bytes < bytes);
+        cache.reset_stats();
+        draw(&mut cache, &font, "V", 0.0);
+        draw(&mut cache, &font, "O", 0.0);
+        assert_eq!((cache.stats().hits, cache.stats().misses), (1, 1));
+
+        cache.set_budget(0);
+        let stats = cache.stats();
+        assert_eq!((stats.evictions, stats.entries, stats.bytes), (6, 0, 0));
+    }
+}
@@ -0,0 +1,563 @@
+//! Resolving font descriptions to font files of the system
+
+use super::{BitmapFont, Font, FontError, FontHandle, TrueTypeFont};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The size of a font
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontSize {
+    /// Typographic points at 96 dpi
+    Points(f32),
+    /// Pixels per em
+    Pixels(f32),
+}
+
+impl FontSize {
+    pub fn to_pixels(self) -> f32 {
+        match self {
+            Self::Points(pt) => pt * 96.0 / 72.0,
+            Self::Pixels(px) => px,
+        }
+    }
+}
+
+/// Describes a wanted font like fontconfig or pango do
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontDescription {
+    pub family: String,
+    /// A style name as found in the font, e.g. "Solid" or "Bold Italic"
+    pub style: Option<String>,
+    /// The weight from 100 (thin) to 900 (black), 400 is regular
+    pub weight: u16,
+    pub italic: bool,
+    pub size: FontSize,
+}
+
+/// Maps weight names to their numeric weight
+fn parse_weight(name: &str) -> Option<u16> {
+    Some(
+        match name.to_ascii_lowercase().replace(['-', ' '], "").as_str() {
+            "thin" | "hairline" => 100,
+            "extralight" | "ultralight" => 200,
+            "light" => 300,
+            "regular" | "normal" | "book" | "roman" => 400,
+            "medium" => 500,
+            "semibold" | "demibold" => 600,
+            "bold" => 700,
+            "extrabold" | "ultrabold" => 800,
+            "black" | "heavy" => 900,
+            n => return n.parse().ok().filter(|w| (1..=1000).contains(w)),
+        },
+    )
+}
+
+fn is_slant(name: &str) -> bool {
+    name.eq_ignore_ascii_case("italic") || name.eq_ignore_ascii_case("oblique")
+}
+
+/// fontconfig weights and the CSS weights they correspond to,
+/// e.g. fontconfig's bold is 200 while CSS's bold is 700
+const FONTCONFIG_WEIGHTS: [(f32, f32); 12] = [
+    (0.0, 100.0),
+    (40.0, 200.0),
+    (50.0, 300.0),
+    (55.0, 350.0),
+    (75.0, 380.0),
+    (80.0, 400.0),
+    (100.0, 500.0),
+    (180.0, 600.0),
+    (200.0, 700.0),
+    (205.0, 800.0),
+    (210.0, 900.0),
+    (215.0, 1000.0),
+];
+
+/// Converts a weight on fontconfig's numeric scale to a CSS weight,
+/// interpolating between the named weights like fontconfig does
+fn fontconfig_weight(weight: f32) -> Option<u16> {
+    if !weight.is_finite() || weight < 0.0 {
+        return None;
+    }
+    let next = FONTCONFIG_WEIGHTS.iter().position(|&(fc, _)| fc > weight);
+    let css = match next {
+        None => 1000.0,
+        Some(0) => FONTCONFIG_WEIGHTS[0].1,
+        Some(i) => {
+            let ((fc0, css0), (fc1, css1)) = (FONTCONFIG_WEIGHTS[i - 1], FONTCONFIG_WEIGHTS[i]);
+            css0 + (css1 - css0) * (weight - fc0) / (fc1 - fc0)
+        }
+    };
+    Some(css.round() as u16)
+}
+
+/// Parses a fontconfig weight, either a number on fontconfig's scale or a name
+fn parse_fontconfig_weight(value: &str) -> Option<u16> {
+    match value.parse::<f32>() {
+        Ok(weight) => fontconfig_weight(weight),
+        Err(_) => parse_weight(value),
+    }
+}
+
+/// Parses a fontconfig slant, either a name or a number where `0` is roman,
+/// `100` italic and `110` oblique
+fn parse_fontconfig_slant(value: &str) -> Option<bool> {
+    match value.parse::<u32>() {
+        Ok(slant) => Some(slant != 0),
+        Err(_) if value.eq_ignore_ascii_case("roman") => Some(false),
+        Err(_) if is_slant(value) => Some(true),
+        Err(_) => None,
+    }
+}
+
+impl FontDescription {
+    pub fn new<S: Into<String>>(family: S, size: FontSize) -> Self {
+        Self {
+            family: family.into(),
+            style: None,
+            weight: 400,
+            italic: false,
+            size,
+        }
+    }
+
+    /// Parses a font description.
+    /// Both the fontconfig syntax `family[-size][:key=value]...`
+    /// (e.g. "Font Awesome 6 Free:style=Solid") and the pango syntax
+    /// `family [style...] [size]` (e.g. "DejaVu Sans Mono Bold 10") are supported.
+    /// Sizes are given in points, unless they are suffixed with "px".
+    /// An optional "pango:" prefix as used by i3 is ignored.
+    /// X logical font descriptions like "-xos4-terminus-medium-r-normal--14-*"
+    /// select bitmap fonts the same way.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim();
+        let spec = spec.strip_prefix("pango:").unwrap_or(spec);
+        if spec.starts_with('-') {
+            Self::parse_xlfd(spec)
+        } else if spec.contains(':') || spec.contains('=') {
+            Self::parse_fontconfig(spec)
+        } else {
+            Self::parse_pango(spec)
+        }
+    }
+
+    fn parse_size(s: &str) -> Option<FontSize> {
+        match s.strip_suffix("px") {
+            Some(px) => px.parse().ok().map(FontSize::Pixels),
+            None => s.parse().ok().map(FontSize::Points),
+        }
+        .filter(|size| size.to_pixels() > 0.0)
+    }
+
+    /// Parses a size without unit, which has to be positive like in [`Self::parse_size`]
+    fn parse_positive(s: &str) -> Option<f32> {
+        s.parse().ok().filter(|size| *size > 0.0)
+    }
+
+    fn parse_fontconfig(spec: &str) -> Option<Self> {
+        let mut parts = spec.split(':');
+        let name = parts.next()?.trim();
+        let (family, size) = match name.rsplit_once('-') {
+            Some((family, size)) => match Self::parse_size(size.trim()) {
+                Some(size) => (family, size),
+                None => (name, FontSize::Points(10.0)),
+            },
+            None => (name, FontSize::Points(10.0)),
+        };
+        let mut desc = Self::new(family.trim(), size);
+        for prop in parts.filter(|p| !p.is_empty()) {
+            let (key, value) = prop.split_once('=')?;
+            let value = value.trim();
+            match key.trim() {
+                "style" => {
+                    desc.italic |= value.split(' ').any(is_slant);
+                    if let Some(weight) = value.split(' ').find_map(parse_weight) {
+                        desc.weight = weight;
+                    }
+                    desc.style = Some(String::from(value));
+                }
+                "weight" => desc.weight = parse_fontconfig_weight(value)?,
+                "slant" => desc.italic = parse_fontconfig_slant(value)?,
+                "size" => desc.size = FontSize::Points(Self::parse_positive(value)?),
+                "pixelsize" => desc.size = FontSize::Pixels(Self::parse_positive(value)?),
+                // unknown properties like antialiasing hints are ignored
+                _ => (),
+            }
+        }
+        Some(desc)
+    }
+
+    /// Parses `-foundry-family-weight-slant-setwidth-style-pixelsize-pointsize-...`,
+    /// where every field but the family may be omitted or a wildcard
+    fn parse_xlfd(spec: &str) -> Option<Self> {
+        let fields: Vec<&str> = spec.split('-').collect();
+        let field = |n: usize| {
+            fields
+                .get(n)
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty() && *f != "*")
+        };
+        let size = match (field(7), field(8)) {
+            (Some(px), _) => FontSize::Pixels(px.parse().ok()?),
+            (None, Some(decipoints)) => FontSize::Points(decipoints.parse::<f32>().ok()? / 10.0),
+            (None, None) => FontSize::Points(10.0),
+        };
+        let mut desc = Self::new(field(2)?, size);
+        if let Some(weight) = field(3).and_then(parse_weight) {
+            desc.weight = weight;
+        }
+        desc.italic =
+            field(4).is_some_and(|s| s.eq_ignore_ascii_case("i") || s.eq_ignore_ascii_case("o"));
+        Some(desc)
+    }
+
+    fn parse_pango(spec: &str) -> Option<Self> {
+        let mut words: Vec<&str> = spec.split_whitespace().collect();
+        let size = match words.last().and_then(|w| Self::parse_size(w)) {
+            Some(size) => {
+                words.pop();
+                size
+            }
+            None => FontSize::Points(10.0),
+        };
+        let (mut weight, mut italic) = (400, false);
+        // style words are only recognized after the family name
+        while words.len() > 1 {
+            let word = words[words.len() - 1];
+            if is_slant(word) {
+                italic = true;
+            } else if let Some(w) = parse_weight(word).filter(|_| word.parse::<u16>().is_err()) {
+                weight = w;
+            } else {
+                break;
+            }
+            words.pop();
+        }
+        if words.is_empty() {
+            return None;
+        }
+        let mut desc = Self::new(words.join(" "), size);
+        desc.weight = weight;
+        desc.italic = italic;
+        Some(desc)
+    }
+}
+
+/// A font face found in a font file
+#[derive(Debug, Clone)]
+pub struct FaceInfo {
+    pub path: PathBuf,
+    /// The index inside of a font collection
+    pub index: u32,
+    pub family: String,
+    pub style: String,
+    pub weight: u16,
+    pub italic: bool,
+    /// The fixed size of a bitmap font, `None` for scalable fonts
+    pub pixel_size: Option<u16>,
+}
+
+/// An index of the font faces installed on the system
+#[derive(Debug, Clone, Default)]
+pub struct FontDatabase {
+    faces: Vec<FaceInfo>,
+}
+
+fn is_scalable_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| ["ttf", "otf", "ttc", "otc"].contains(&e.to_ascii_lowercase().as_str()))
+}
+
+impl FontDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The directories fonts are installed to by convention
+    pub fn system_dirs() -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+            let data_home = std::env::var_os("XDG_DATA_HOME")
+                .map_or_else(|| home.join(".local/share"), PathBuf::from);
+            dirs.push(data_home.join("fonts"));
+            dirs.push(home.join(".fonts"));
+        }
+        let data_dirs = std::env::var("XDG_DATA_DIRS")
+            .unwrap_or_else(|_| String::from("/usr/local/share:/usr/share"));
+        dirs.extend(
+            data_dirs
+                .split(':')
+                .filter(|d| !d.is_empty())
+                .map(|d| Path::new(d).join("fonts")),
+        );
+        dirs.push(PathBuf::from("/usr/share/fonts"));
+        dirs.push(PathBuf::from("/usr/local/share/fonts"));
+        dirs.dedup();
+        dirs
+    }
+
+    /// Indexes the fonts of all system font directories
+    pub fn scan_system() -> Self {
+        let mut db = Self::new();
+        let mut visited = HashSet::new();
+        for dir in Self::system_dirs() {
+            db.scan_new_dir(&dir, &mut visited);
+        }
+        db
+    }
+
+    /// Recursively indexes all font files in a directory.
+    /// Unreadable files and directories are skipped.
+    pub fn scan_dir(&mut self, dir: &Path) {
+        self.scan_new_dir(dir, &mut HashSet::new());
+    }
+
+    /// Indexes a directory unless it was visited before,
+    /// as symbolic links may lead to it again or even to one of its parents
+    fn scan_new_dir(&mut self, dir: &Path, visited: &mut HashSet<PathBuf>) {
+        let is_new = dir.canonicalize().is_ok_and(|dir| visited.insert(dir));
+        if !is_new {
+            return;
+        }
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        paths.sort();
+        for path in paths {
+            if path.is_dir() {
+                self.scan_new_dir(&path, visited);
+            } else {
+                self.add_file(&path);
+            }
+        }
+    }
+
+    /// Indexes the faces of a single font file, returns whether it contained any
+    pub fn add_file(&mut self, path: &Path) -> bool {
+        if BitmapFont::is_bitmap_font_file(path) {
+            return self.add_bitmap_file(path);
+        }
+        if !is_scalable_font_file(path) {
+            return false;
+        }
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(_) => return false,
+        };
+        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        let before = self.faces.len();
+        for index in 0..count {
+            if let Ok(face) = ttf_parser::Face::parse(&data, index) {
+                let name = |ids: &[u16]| {
+                    ids.iter().find_map(|&id| {
+                        face.names()
+                            .into_iter()
+                            .filter(|n| n.name_id == id && n.is_unicode())
+                            .find_map(|n| n.to_string())
+                    })
+                };
+                let family = name(&[
+                    ttf_parser::name_id::TYPOGRAPHIC_FAMILY,
+                    ttf_parser::name_id::FAMILY,
+                ]);
+                let style = name(&[
+                    ttf_parser::name_id::TYPOGRAPHIC_SUBFAMILY,
+                    ttf_parser::name_id::SUBFAMILY,
+                ]);
+                if let Some(family) = family {
+                    self.faces.push(FaceInfo {
+                        path: path.to_owned(),
+                        index,
+                        family,
+                        style: style.unwrap_or_else(|| String::from("Regular")),
+                        weight: face.weight().to_number(),
+                        italic: face.is_italic() || face.is_oblique(),
+                        pixel_size: None,
+                    });
+                }
+            }
+        }
+        self.faces.len() > before
+    }
+
+    /// Indexes a BDF or PCF font.
+    /// Only the properties are read, the glyphs are loaded once the face is used.
+    fn add_bitmap_file(&mut self, path: &Path) -> bool {
+        let info = match BitmapFont::load_info(path) {
+            Ok(info) => info,
+            Err(_) => return false,
+        };
+        let style = if info.italic {
+            format!("{} Italic", info.weight)
+        } else {
+            info.weight.clone()
+        };
+        self.faces.push(FaceInfo {
+            path: path.to_owned(),
+            index: 0,
+            family: info.family.clone(),
+            style,
+            weight: parse_weight(&info.weight).unwrap_or(400),
+            italic: info.italic,
+            pixel_size: Some(info.pixel_size),
+        });
+        true
+    }
+
+    pub fn faces(&self) -> &[FaceInfo] {
+        &self.faces
+    }
+
+    /// Finds the face matching a description best.
+    /// The family has to match, an explicit style name is preferred over
+    /// the closest slant, size of bitmap fonts and weight.
+    pub fn find(&self, desc: &FontDescription) -> Option<&FaceInfo> {
+        self.faces
+            .iter()
+            .filter(|f| f.family.eq_ignore_ascii_case(&desc.family))
+            .min_by_key(|f| {
+                let style_mismatch = desc
+                    .style
+                    .as_ref()
+                    .is_some_and(|s| !f.style.eq_ignore_ascii_case(s));
+                // scalable fonts always have the wanted size
+                let size_diff = f.pixel_size.map_or(0, |px| {
+                    (f32::from(px) - desc.size.to_pixels()).abs().round() as u32
+                });
+                (
+                    style_mismatch,
+                    f.italic != desc.italic,
+                    size_diff,
+                    (i32::from(f.weight) - i32::from(desc.weight)).abs(),
+                )
+            })
+    }
+
+    /// Loads the face matching a description best.
+    /// Bitmap fonts are loaded in their own size, the nearest one is chosen.
+    pub fn load(&self, desc: &FontDescription) -> Result<Arc<dyn Font>, FontError> {
+        let face = self
+            .find(desc)
+            .ok_or_else(|| FontError::NotFound(desc.family.clone()))?;
+        if face.pixel_size.is_some() {
+            return Ok(Arc::new(BitmapFont::load(&face.path)?));
+        }
+        Ok(Arc::new(TrueTypeFont::from_data(
+            std::fs::read(&face.path)?,
+            face.index,
+            desc.size.to_pixels(),
+            format!("{} {}", face.family, face.style),
+        )?))
+    }
+
+    /// Loads a list of font descriptions, like "DejaVu Sans Mono 10",
+    /// into a handle, which takes each glyph from the first font that contains it
+    pub fn load_fallbacks<S: AsRef<str>>(&self, specs: &[S]) -> Result<FontHandle, FontError> {
+        let fonts = specs
+            .iter()
+            .map(|spec| {
+                let desc = FontDescription::parse(spec.as_ref()).ok_or_else(|| {
+                    FontError::Invalid(format!("malformed description \"{}\"", spec.as_ref()))
+                })?;
+                self.load(&desc)
+            })
+            .collect::<Result<Vec<_>, FontError>>()?;
+        FontHandle::with_fallbacks(fonts)
+            .ok_or_else(|| FontError::Invalid(String::from("empty font list")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fontconfig_numbers_use_fontconfig_scales() {
+        let parse = |spec| FontDescription::parse(spec).unwrap();
+        assert_eq!(parse("Sans:weight=200").weight, 700);
+        assert_eq!(parse("Sans:weight=80").weight, 400);
+        assert_eq!(parse("Sans:weight=0").weight, 100);
+        assert_eq!(parse("Sans:weight=210").weight, 900);
+        assert_eq!(parse("Sans:weight=190").weight, 650);
+        assert_eq!(parse("Sans:weight=bold").weight, 700);
+        assert!(parse("Sans:slant=100").italic);
+        assert!(parse("Sans:slant=110").italic);
+        assert!(!parse("Sans:slant=0").italic);
+        assert!(parse("Sans:slant=oblique").italic);
+        assert!(!parse("Sans:slant=roman").italic);
+        assert!(FontDescription::parse("Sans:slant=sideways").is_none());
+        // pango and X descriptions keep CSS weights
+        assert_eq!(parse("Sans Bold 10").weight, 700);
+    }
+
+    #[test]
+    fn fontconfig_sizes_are_positive() {
+        let size = |spec| FontDescription::parse(spec).map(|desc| desc.size);
+        assert_eq!(size("Sans:size=12"), Some(FontSize::Points(12.0)));
+        assert_eq!(size("Sans:pixelsize=9.5"), Some(FontSize::Pixels(9.5)));
+        for spec in [
+            "Sans:size=0",
+            "Sans:size=-3",
+            "Sans:size=NaN",
+            "Sans:pixelsize=0",
+        ]
+        .iter()
+        {
+            assert_eq!(size(spec), None, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn bitmap_fonts_are_indexed_by_their_properties() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("coffee-bar-bitmaps-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // the glyph is broken, which only matters once the font is loaded
+        let bdf = "STARTFONT 2.1\nFONT -misc-tiny-bold-i-normal--9-90-75-75-c-60-iso10646-1\n\
+                   STARTPROPERTIES 3\nCHARSET_REGISTRY \"ISO10646\"\nFONT_ASCENT 7\n\
+                   FONT_DESCENT 2\nENDPROPERTIES\nCHARS 1\nSTARTCHAR A\nENCODING 65\n\
+                   BBX 8 1 0 0\nBITMAP\nZZ\nENDCHAR\nENDFONT\n";
+        std::fs::write(dir.join("tiny.bdf"), bdf).unwrap();
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gz.write_all(bdf.replace("tiny", "packed").as_bytes())
+            .unwrap();
+        std::fs::write(dir.join("packed.bdf.gz"), gz.finish().unwrap()).unwrap();
+
+        let mut db = FontDatabase::new();
+        db.scan_dir(&dir);
+        let faces: Vec<(&str, &str, Option<u16>)> = db
+            .faces()
+            .iter()
+            .map(|f| (f.family.as_str(), f.style.as_str(), f.pixel_size))
+            .collect();
+        assert_eq!(
+            faces,
+            [
+                ("packed", "bold Italic", Some(9)),
+                ("tiny", "bold Italic", Some(9))
+            ]
+        );
+        let desc = FontDescription::parse("tiny 9px").unwrap();
+        assert!(matches!(db.load(&desc), Err(FontError::Invalid(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scanning_survives_symlink_loops() {
+        let dir = std::env::temp_dir().join(format!("coffee-bar-fonts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::os::unix::fs::symlink("..", dir.join("nested/parent")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("self")).unwrap();
+        let mut db = FontDatabase::new();
+        db.scan_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(db.faces().is_empty());
+    }
+}
@@ -0,0 +1,274 @@
+//! Font loading, glyph rasterization and text measurement
+
+mod bitmap;
+mod cache;
+mod lookup;
+mod truetype;
+mod truncate;
+
+pub use bitmap::{BitmapFont, BitmapFontInfo};
+pub use cache::{GlyphCache, GlyphCacheStats, SUBPIXEL_STEPS};
+pub use lookup::{FaceInfo, FontDatabase, FontDescription, FontSize};
+pub use truetype::TrueTypeFont;
+pub use truncate::Truncate;
+
+use std::sync::Arc;
+
+/// Identifies a glyph inside of a font
+pub type GlyphId = u16;
+
+/// Vertical metrics of a font in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    /// Distance from the baseline to the top of the highest glyph
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of the lowest glyph, positive downwards
+    pub descent: f32,
+    /// Additional space between two lines
+    pub line_gap: f32,
+}
+
+/// The coverage mask of a rasterized glyph
+#[derive(Debug, Clone)]
+pub struct GlyphBitmap {
+    /// Horizontal offset of the left edge from the pen position
+    pub left: i32,
+    /// Vertical offset of the top edge from the baseline, negative upwards
+    pub top: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Row major coverage values, `255` means fully covered
+    pub coverage: Vec<u8>,
+}
+
+/// A font face of a fixed size
+pub trait Font: Send + Sync {
+    /// A human readable name of the font
+    fn name(&self) -> &str;
+    /// The size of an em in pixels
+    fn size(&self) -> f32;
+    fn metrics(&self) -> FontMetrics;
+    /// Returns the glyph representing a character, `None` if the font lacks it
+    fn glyph_id(&self, c: char) -> Option<GlyphId>;
+    /// Horizontal distance to the pen position of the following glyph
+    fn advance(&self, glyph: GlyphId) -> f32;
+    /// Adjustment of the advance between two specific glyphs
+    fn kerning(&self, _left: GlyphId, _right: GlyphId) -> f32 {
+        0.0
+    }
+    /// Rasterizes a glyph with the pen at `offset` pixels right of a pixel boundary,
+    /// where `offset` lies in `0.0..1.0`. Returns `None` for empty glyphs.
+    fn rasterize(&self, glyph: GlyphId, offset: f32) -> Option<GlyphBitmap>;
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(std::io::Error),
+    Invalid(String),
+    NotFound(String),
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read font [{}]", e),
+            Self::Invalid(e) => write!(f, "invalid font: {}", e),
+            Self::NotFound(e) => write!(f, "no installed font matches \"{}\"", e),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+impl From<std::io::Error> for FontError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A glyph placed relative to the start of the baseline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    /// The index of the font in the fallback list the glyph is taken from
+    pub font: usize,
+    pub id: GlyphId,
+    pub x: f32,
+}
+
+/// The size of a rendered text in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextExtents {
+    pub width: u32,
+    /// Height above the baseline
+    pub ascent: u32,
+    /// Depth below the baseline
+    pub descent: u32,
+}
+
+impl TextExtents {
+    pub fn height(&self) -> u32 {
+        self.ascent + self.descent
+    }
+}
+
+/// A cheaply clonable reference to a list of loaded fonts.
+/// Each glyph is taken from the first font that contains it,
+/// so icon fonts can be combined with a regular text font.
+#[derive(Clone)]
+pub struct FontHandle(Arc<[Arc<dyn Font>]>);
+
+impl std::fmt::Debug for FontHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("FontHandle")
+            .field(&self.0.iter().map(|f| f.name()).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl FontHandle {
+    pub fn new<F: Font + 'static>(font: F) -> Self {
+        Self(Arc::new([Arc::new(font) as Arc<dyn Font>]))
+    }
+
+    /// Creates a handle for a fallback list, returns `None` if the list is empty
+    pub fn with_fallbacks(fonts: Vec<Arc<dyn Font>>) -> Option<Self> {
+        if fonts.is_empty() {
+            None
+        } else {
+            Some(Self(fonts.into()))
+        }
+    }
+
+    /// Loads a font file with an em size of `px` pixels.
+    /// Bitmap fonts (BDF and PCF) have a fixed size, so `px` is ignored for them.
+    pub fn load<P: AsRef<std::path::Path>>(path: P, px: f32) -> Result<Self, FontError> {
+        let path = path.as_ref();
+        if BitmapFont::is_bitmap_font_file(path) {
+            BitmapFont::load(path).map(Self::new)
+        } else {
+            TrueTypeFont::load(path, px).map(Self::new)
+        }
+    }
+
+    /// The fallback list, which always contains at least one font
+    pub fn fonts(&self) -> &[Arc<dyn Font>] {
+        &self.0
+    }
+
+    /// The metrics enclosing the metrics of all fonts of the fallback list
+    pub fn metrics(&self) -> FontMetrics {
+        self.0.iter().map(|f| f.metrics()).fold(
+            FontMetrics {
+                ascent: 0.0,
+                descent: 0.0,
+                line_gap: 0.0,
+            },
+            |a, b| FontMetrics {
+                ascent: a.ascent.max(b.ascent),
+                descent: a.descent.max(b.descent),
+                line_gap: a.line_gap.max(b.line_gap),
+            },
+        )
+    }
+
+    /// Finds the first font of the fallback list containing a character.
+    /// Characters missing in every font are replaced with the first font's `.notdef` glyph.
+    fn glyph(&self, c: char) -> (usize, GlyphId) {
+        self.0
+            .iter()
+            .enumerate()
+            .find_map(|(i, f)| f.glyph_id(c).map(|id| (i, id)))
+            .unwrap_or((0, 0))
+    }
+
+    /// Places the glyphs of a text next to each other.
+    /// Returns the glyphs and the advance of the whole text.
+    pub fn layout(&self, text: &str) -> (Vec<PositionedGlyph>, f32) {
+        let mut glyphs = Vec::with_capacity(text.len());
+        let mut pen = 0.0;
+        let mut prev: Option<(usize, GlyphId)> = None;
+        for c in text.chars().filter(|c| !c.is_control()) {
+            let (font, id) = self.glyph(c);
+            match prev {
+                // kerning is only defined between glyphs of the same font
+                Some((prev_font, prev_id)) if prev_font == font => {
+                    pen += self.0[font].kerning(prev_id, id)
+                }
+                _ => (),
+            }
+            glyphs.push(PositionedGlyph { font, id, x: pen });
+            pen += self.0[font].advance(id);
+            prev = Some((font, id));
+        }
+        (glyphs, pen)
+    }
+
+    /// Measures a text without rasterizing it.
+    /// The height only depends on the fonts, so texts of one handle line up.
+    pub fn measure(&self, text: &str) -> TextExtents {
+        let metrics = self.metrics();
+        TextExtents {
+            width: self.layout(text).1.ceil().max(0.0) as u32,
+            ascent: metrics.ascent.ceil().max(0.0) as u32,
+            descent: metrics.descent.ceil().max(0.0) as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bitmap font with a box for every character of `chars`, all `advance` pixels wide
+    fn font(chars: &str, advance: i32, ascent: i32, descent: i32) -> Arc<dyn Font> {
+        let mut bdf = format!(
+            "STARTFONT 2.1\nSTARTPROPERTIES 3\nFONT_ASCENT {}\nFONT_DESCENT {}\n\
+             DEFAULT_CHAR {}\nENDPROPERTIES\n",
+            ascent,
+            descent,
+            chars.chars().next().map_or(0, u32::from)
+        );
+        for c in chars.chars() {
+            bdf += &format!(
+                "STARTCHAR box\nENCODING {}\nDWIDTH {} 0\nBBX 1 1 0 0\nBITMAP\n80\nENDCHAR\n",
+                u32::from(c),
+                advance
+            );
+        }
+        Arc::new(BitmapFont::from_data(bdf.as_bytes(), "box").unwrap())
+    }
+
+    #[test]
+    fn texts_are_measured_by_their_advances() {
+        let handle = FontHandle(Arc::new([font("AB", 6, 7, 1)]));
+        let extents = handle.measure("ABBA");
+        assert_eq!((extents.width, extents.ascent, extents.descent), (24, 7, 1));
+        assert_eq!(extents.height(), 8);
+        // control characters take no space, empty texts keep the height
+        assert_eq!(handle.measure("A\tB\n").width, 12);
+        assert_eq!(
+            handle.measure(""),
+            TextExtents {
+                width: 0,
+                ..extents
+            }
+        );
+    }
+
+    #[test]
+    fn missing_characters_fall_back_to_later_fonts() {
+        let handle =
+            FontHandle::with_fallbacks(vec![font("AB", 6, 7, 1), font("Bx", 4, 9, 2)]).unwrap();
+        let (glyphs, advance) = handle.layout("ABx");
+        let placed: Vec<(usize, f32)> = glyphs.iter().map(|g| (g.font, g.x)).collect();
+        assert_eq!(placed, [(0, 0.0), (0, 6.0), (1, 12.0)]);
+        assert_eq!(advance, 16.0);
+        // the extents enclose the metrics of every font of the list
+        let extents = handle.measure("A");
+        assert_eq!((extents.width, extents.ascent, extents.descent), (6, 9, 2));
+        // characters missing in every font take the default character of the first font
+        assert_eq!(handle.layout("?").0[0].font, 0);
+        assert_eq!(handle.measure("x?").width, 10);
+        assert!(FontHandle::with_fallbacks(Vec::new()).is_none());
+    }
+}
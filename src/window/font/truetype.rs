@@ -0,0 +1,96 @@
+use super::{Font, FontError, FontMetrics, GlyphBitmap, GlyphId};
+use ab_glyph::{Font as _, ScaleFont as _};
+
+/// A scalable TrueType or OpenType font rendered with antialiasing
+pub struct TrueTypeFont {
+    font: ab_glyph::FontVec,
+    scale: ab_glyph::PxScale,
+    px: f32,
+    name: String,
+}
+
+impl TrueTypeFont {
+    /// Parses the font with the index `index` of a font file or collection,
+    /// `px` is the size of an em in pixels
+    pub fn from_data(data: Vec<u8>, index: u32, px: f32, name: String) -> Result<Self, FontError> {
+        let font = ab_glyph::FontVec::try_from_vec_and_index(data, index)
+            .map_err(|e| FontError::Invalid(format!("{} ({})", e, name)))?;
+        // `PxScale` describes the height from descent to ascent instead of the em size
+        let units_per_em = font.units_per_em().unwrap_or(1000.0);
+        let scale = ab_glyph::PxScale::from(px * font.height_unscaled() / units_per_em);
+        Ok(Self {
+            font,
+            scale,
+            px,
+            name,
+        })
+    }
+
+    /// Loads the first font of a file, `px` is the size of an em in pixels
+    pub fn load<P: AsRef<std::path::Path>>(path: P, px: f32) -> Result<Self, FontError> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        Self::from_data(std::fs::read(path)?, 0, px, name)
+    }
+}
+
+impl Font for TrueTypeFont {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn size(&self) -> f32 {
+        self.px
+    }
+
+    fn metrics(&self) -> FontMetrics {
+        let font = self.font.as_scaled(self.scale);
+        FontMetrics {
+            ascent: font.ascent(),
+            descent: -font.descent(),
+            line_gap: font.line_gap(),
+        }
+    }
+
+    fn glyph_id(&self, c: char) -> Option<GlyphId> {
+        match self.font.glyph_id(c) {
+            ab_glyph::GlyphId(0) => None,
+            ab_glyph::GlyphId(id) => Some(id),
+        }
+    }
+
+    fn advance(&self, glyph: GlyphId) -> f32 {
+        self.font
+            .as_scaled(self.scale)
+            .h_advance(ab_glyph::GlyphId(glyph))
+    }
+
+    fn kerning(&self, left: GlyphId, right: GlyphId) -> f32 {
+        self.font
+            .as_scaled(self.scale)
+            .kern(ab_glyph::GlyphId(left), ab_glyph::GlyphId(right))
+    }
+
+    fn rasterize(&self, glyph: GlyphId, offset: f32) -> Option<GlyphBitmap> {
+        let glyph = ab_glyph::GlyphId(glyph)
+            .with_scale_and_position(self.scale, ab_glyph::point(offset, 0.0));
+        let outline = self.font.outline_glyph(glyph)?;
+        let bounds = outline.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        let mut coverage = vec![0; width as usize * height as usize];
+        outline.draw(|x, y, c| {
+            if x < width && y < height {
+                coverage[(y * width + x) as usize] = (c.min(1.0) * 255.0).round() as u8;
+            }
+        });
+        Some(GlyphBitmap {
+            left: bounds.min.x as i32,
+            top: bounds.min.y as i32,
+            width,
+            height,
+            coverage,
+        })
+    }
+}
@@ -0,0 +1,108 @@
+//! Shortening texts to a width by replacing a part of them with an ellipsis
+
+use super::FontHandle;
+
+/// The part of a text that is replaced with an ellipsis when it is too wide
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Truncate {
+    /// Keeps the beginning, like `Long tit…`
+    End,
+    /// Keeps both ends, like `Long…title`
+    Middle,
+    /// Keeps the end, like `…ng title`
+    Start,
+}
+
+impl FontHandle {
+    /// Whether a font of the fallback list contains a character
+    pub fn has_char(&self, c: char) -> bool {
+        self.0.iter().any(|f| f.glyph_id(c).is_some())
+    }
+
+    /// The ellipsis character, or three dots for fonts without it
+    pub fn ellipsis(&self) -> &'static str {
+        if self.has_char('…') {
+            "…"
+        } else {
+            "..."
+        }
+    }
+
+    /// Shortens a text to at most `max_width` pixels by replacing characters with an ellipsis.
+    /// Texts that fit are returned unchanged,
+    /// the result is empty if not even the ellipsis fits.
+    pub fn truncate(&self, text: &str, max_width: u32, mode: Truncate) -> String {
+        if self.measure(text).width <= max_width {
+            return text.to_string();
+        }
+        let chars: Vec<char> = text.chars().collect();
+        let ellipsis = self.ellipsis();
+        // keeps `n` characters, spaces next to the ellipsis are dropped
+        let shorten = |n: usize| -> String {
+            let (front, back) = match mode {
+                Truncate::End => (n, 0),
+                Truncate::Middle => (n.div_ceil(2), n / 2),
+                Truncate::Start => (0, n),
+            };
+            let front: String = chars[..front].iter().collect();
+            let back: String = chars[chars.len() - back..].iter().collect();
+            format!("{}{}{}", front.trim_end(), ellipsis, back.trim_start())
+        };
+        // the width grows with the number of kept characters,
+        // so the longest fitting text is found by bisection
+        let (mut lo, mut hi) = (0, chars.len());
+        while lo < hi {
+            let mid = (lo + hi).div_ceil(2);
+            if self.measure(&shorten(mid)).width <= max_width {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+        let shortened = shorten(lo);
+        if self.measure(&shortened).width <= max_width {
+            shortened
+        } else {
+            String::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> FontHandle {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/font.bdf");
+        FontHandle::load(path, 8.0).unwrap()
+    }
+
+    #[test]
+    fn fitting_texts_stay_unchanged() {
+        let font = font();
+        for mode in [Truncate::End, Truncate::Middle, Truncate::Start].iter() {
+            assert_eq!(font.truncate("VOLUME 40%", 60, *mode), "VOLUME 40%");
+        }
+    }
+
+    #[test]
+    fn texts_keep_the_longest_fitting_part() {
+        // every glyph of the font is 6 pixels wide
+        let font = font();
+        assert_eq!(font.truncate("VOLUME 40%", 30, Truncate::End), "VOLU…");
+        assert_eq!(font.truncate("VOLUME 40%", 30, Truncate::Middle), "VO…0%");
+        assert_eq!(font.truncate("VOLUME 40%", 35, Truncate::Start), "…40%");
+        // the space next to the ellipsis is dropped
+        assert_eq!(font.truncate("VOLUME 40%", 45, Truncate::End), "VOLUME…");
+    }
+
+    #[test]
+    fn texts_are_empty_without_room_for_the_ellipsis() {
+        let font = font();
+        for mode in [Truncate::End, Truncate::Middle, Truncate::Start].iter() {
+            assert_eq!(font.truncate("VOLUME 40%", 6, *mode), "…");
+            assert_eq!(font.truncate("VOLUME 40%", 5, *mode), "");
+            assert_eq!(font.truncate("VOLUME 40%", 0, *mode), "");
+        }
+    }
+}
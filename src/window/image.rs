@@ -0,0 +1,504 @@
+//! Views into [`Image`]s, copying and converting between images and resizing
+
+use super::color::{self, Color, ColorRgba32, PackedColor};
+use super::draw::DrawCommand;
+use super::{raster, Image, ImageDrawError, PixelFormat, Surface};
+
+/// How the pixels of a blitted image are combined with the pixels below
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Replaces the pixels including their alpha
+    Replace,
+    /// Composites the pixels over the existing ones by their alpha
+    Over,
+}
+
+/// How the colors of a resized image are sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Takes the pixel closest to the sample position, keeping hard edges
+    Nearest,
+    /// Interpolates between the four pixels around the sample position
+    Bilinear,
+}
+
+/// A rectangle inside of an image as `(x, y, w, h)`, `None` if it exceeds the image
+fn region<C: Color>(image: &Image<C>, x: u64, y: u64, w: u64, h: u64) -> Option<[u64; 4]> {
+    let (width, height) = image.res;
+    if x.checked_add(w)? > width || y.checked_add(h)? > height {
+        return None;
+    }
+    Some([x, y, w, h])
+}
+
+/// A read-only rectangular part of an image
+pub struct SubImage<'a, C: Color> {
+    image: &'a Image<C>,
+    rect: [u64; 4],
+}
+
+impl<'a, C: Color> Clone for SubImage<'a, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, C: Color> Copy for SubImage<'a, C> {}
+
+impl<'a, C: Color> From<&'a Image<C>> for SubImage<'a, C> {
+    fn from(image: &'a Image<C>) -> Self {
+        let (w, h) = image.res;
+        Self {
+            image,
+            rect: [0, 0, w, h],
+        }
+    }
+}
+
+impl<'a, C: Color> SubImage<'a, C> {
+    pub fn get_width(&self) -> u64 {
+        self.rect[2]
+    }
+
+    pub fn get_height(&self) -> u64 {
+        self.rect[3]
+    }
+
+    pub fn get_size(&self) -> (u64, u64) {
+        (self.rect[2], self.rect[3])
+    }
+
+    /// Returns a pixel relative to the view's top left corner
+    pub fn get_pixel_at(&self, x: u64, y: u64) -> Option<&'a C> {
+        let [rx, ry, w, h] = self.rect;
+        if x >= w || y >= h {
+            return None;
+        }
+        self.image.get_pixel_at(rx + x, ry + y)
+    }
+
+    /// Iterates over the rows of the view from top to bottom
+    pub fn rows(&self) -> impl Iterator<Item = &'a [C]> + 'a {
+        let [x, y, w, h] = self.rect;
+        let (x, w) = (x as usize, w as usize);
+        self.image
+            .rows()
+            .skip(y as usize)
+            .take(h as usize)
+            .map(move |row| &row[x..x + w])
+    }
+
+    /// A part of the view, `(x, y)` is relative to the view's top left corner
+    pub fn sub_image(&self, x: u64, y: u64, w: u64, h: u64) -> Option<Self> {
+        let [rx, ry, rw, rh] = self.rect;
+        if x.checked_add(w)? > rw || y.checked_add(h)? > rh {
+            return None;
+        }
+        Some(Self {
+            image: self.image,
+            rect: [rx + x, ry + y, w, h],
+        })
+    }
+
+    /// Copies the view into a new image
+    pub fn to_image(&self) -> Image<C> {
+        let data = self.rows().flatten().cloned().collect();
+        // a view is never larger than its image
+        Image::from_data(self.rect[2], self.rect[3], data).unwrap()
+    }
+
+    /// Returns the view scaled to `width` x `height` pixels,
+    /// `None` if such an image can not be allocated
+    pub fn resize(&self, width: u64, height: u64, filter: Filter) -> Option<Image<C>> {
+        let mut out = Image::new(width, height)?;
+        let (sw, sh) = self.get_size();
+        if sw == 0 || sh == 0 {
+            return Some(out);
+        }
+        let pixel = |x: u64, y: u64| self.get_pixel_at(x, y).unwrap();
+        let (scale_x, scale_y) = (sw as f64 / width as f64, sh as f64 / height as f64);
+        for (y, row) in out.rows_mut().enumerate() {
+            for (x, dst) in row.iter_mut().enumerate() {
+                *dst = match filter {
+                    Filter::Nearest => {
+                        let sx = (((x as f64 + 0.5) * scale_x) as u64).min(sw - 1);
+                        let sy = (((y as f64 + 0.5) * scale_y) as u64).min(sh - 1);
+                        pixel(sx, sy).clone()
+                    }
+                    Filter::Bilinear => {
+                        let cx = ((x as f64 + 0.5) * scale_x - 0.5).clamp(0.0, (sw - 1) as f64);
+                        let cy = ((y as f64 + 0.5) * scale_y - 0.5).clamp(0.0, (sh - 1) as f64);
+                        let (x0, y0) = (cx.floor() as u64, cy.floor() as u64);
+                        let (fx, fy) = (cx - x0 as f64, cy - y0 as f64);
+                        let (x1, y1) = ((x0 + 1).min(sw - 1), (y0 + 1).min(sh - 1));
+                        // interpolates premultiplied colors, so that transparent pixels
+                        // do not bleed their color into the edges
+                        let mut sum = [0.0; 4];
+                        for (px, py, weight) in [
+                            (x0, y0, (1.0 - fx) * (1.0 - fy)),
+                            (x1, y0, fx * (1.0 - fy)),
+                            (x0, y1, (1.0 - fx) * fy),
+                            (x1, y1, fx * fy),
+                        ] {
+                            let p = pixel(px, py);
+                            let a = f64::from(p.a8());
+                            sum[0] += f64::from(p.r8()) * a * weight;
+                            sum[1] += f64::from(p.g8()) * a * weight;
+                            sum[2] += f64::from(p.b8()) * a * weight;
+                            sum[3] += a * weight;
+                        }
+                        let a = sum[3];
+                        let channel = |c: f64| {
+                            if a > 0.0 {
+                                (c / a).round().clamp(0.0, 255.0) as u8
+                            } else {
+                                0
+                            }
+                        };
+                        C::from_rgba8(
+                            channel(sum[0]),
+                            channel(sum[1]),
+                            channel(sum[2]),
+                            a.round() as u8,
+                        )
+                    }
+                };
+            }
+        }
+        Some(out)
+    }
+}
+
+/// A mutable rectangular part of an image, which is drawn onto like a [`Surface`].
+/// Draw commands use the top left corner of the part as origin and are clipped to it.
+pub struct ImageDraw<'s, C: Color> {
+    image: &'s mut Image<C>,
+    rect: [u64; 4],
+}
+
+impl<'s, C: Color> ImageDraw<'s, C> {
+    /// A read-only view of the same part
+    pub fn as_sub_image(&self) -> SubImage<'_, C> {
+        SubImage {
+            image: &*self.image,
+            rect: self.rect,
+        }
+    }
+
+    pub fn get_pixel_at(&self, x: u64, y: u64) -> Option<&C> {
+        let [rx, ry, w, h] = self.rect;
+        if x >= w || y >= h {
+            return None;
+        }
+        self.image.get_pixel_at(rx + x, ry + y)
+    }
+
+    pub fn get_pixel_at_mut(&mut self, x: u64, y: u64) -> Option<&mut C> {
+        let [rx, ry, w, h] = self.rect;
+        if x >= w || y >= h {
+            return None;
+        }
+        self.image.get_pixel_at_mut(rx + x, ry + y)
+    }
+
+    /// Replaces a pixel without blending, returns the previous color
+    /// or `None` if the pixel is outside of the part
+    pub fn set_pixel(&mut self, x: u64, y: u64, color: C) -> Option<C> {
+        self.get_pixel_at_mut(x, y)
+            .map(|pixel| std::mem::replace(pixel, color))
+    }
+
+    /// Iterates over the rows of the part from top to bottom
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [C]> + '_ {
+        let [x, y, w, h] = self.rect;
+        let (x, w) = (x as usize, w as usize);
+        self.image
+            .rows_mut()
+            .skip(y as usize)
+            .take(h as usize)
+            .map(move |row| &mut row[x..x + w])
+    }
+
+    /// Replaces every pixel of the part without blending
+    pub fn fill(&mut self, color: C) {
+        for row in self.rows_mut() {
+            row.iter_mut().for_each(|pixel| *pixel = color.clone());
+        }
+    }
+
+    /// A part of this part, `(x, y)` is relative to its top left corner
+    pub fn sub_image_mut(&mut self, x: u64, y: u64, w: u64, h: u64) -> Option<ImageDraw<'_, C>> {
+        let [rx, ry, rw, rh] = self.rect;
+        if x.checked_add(w)? > rw || y.checked_add(h)? > rh {
+            return None;
+        }
+        Some(ImageDraw {
+            image: &mut *self.image,
+            rect: [rx + x, ry + y, w, h],
+        })
+    }
+
+    /// Copies an image of any color type with its top left corner at `(x, y)`.
+    /// Pixels outside of this part are skipped.
+    pub fn blit<'i, S: Color + 'i, I: Into<SubImage<'i, S>>>(
+        &mut self,
+        x: i64,
+        y: i64,
+        src: I,
+        mode: BlendMode,
+    ) {
+        let src = src.into();
+        let [_, _, w, h] = self.rect;
+        let (sw, sh) = src.get_size();
+        // the part of the source landing inside of the destination
+        let sx0 = x.saturating_neg().clamp(0, sw as i64) as u64;
+        let sy0 = y.saturating_neg().clamp(0, sh as i64) as u64;
+        let sx1 = (w as i64).saturating_sub(x).clamp(0, sw as i64) as u64;
+        let sy1 = (h as i64).saturating_sub(y).clamp(0, sh as i64) as u64;
+        for sy in sy0..sy1 {
+            for sx in sx0..sx1 {
+                let color = src.get_pixel_at(sx, sy).unwrap();
+                let dx = (x + sx as i64) as u64;
+                let dy = (y + sy as i64) as u64;
+                let dst = self.get_pixel_at_mut(dx, dy).unwrap();
+                *dst = match mode {
+                    BlendMode::Replace => color.convert(),
+                    BlendMode::Over => {
+                        // blends in a format with alpha, as the destination may lack it
+                        let mut out: ColorRgba32 = dst.convert();
+                        raster::blend(&mut out, &color.convert(), 255);
+                        out.convert()
+                    }
+                };
+            }
+        }
+    }
+}
+
+impl<'s, C: Color + 'static> Surface<C> for ImageDraw<'s, C> {
+    type Error = ImageDrawError;
+    fn get_width(&self) -> u64 {
+        self.rect[2]
+    }
+    fn get_height(&self) -> u64 {
+        self.rect[3]
+    }
+    fn get_pixel_format(&self) -> PixelFormat {
+        C::get_format()
+    }
+    fn draw(&mut self, draw: DrawCommand<C>) -> Result<(), ImageDrawError> {
+        // the part lies inside of the image, whose size fits into an usize
+        let [x, y, w, h] = self.rect.map(|v| v as i64);
+        raster::draw_region(self.image, &draw, (x, y, w, h))
+    }
+}
+
+impl<C: Color> Image<C> {
+    /// A read-only view of the whole image
+    pub fn view(&self) -> SubImage<'_, C> {
+        self.into()
+    }
+
+    /// A read-only view of the rectangle `(x, y, w, h)`, `None` if it exceeds the image
+    pub fn sub_image(&self, x: u64, y: u64, w: u64, h: u64) -> Option<SubImage<'_, C>> {
+        Some(SubImage {
+            rect: region(self, x, y, w, h)?,
+            image: self,
+        })
+    }
+
+    /// A drawing handle for the whole image
+    pub fn image_draw(&mut self) -> ImageDraw<'_, C> {
+        let (w, h) = self.res;
+        ImageDraw {
+            image: self,
+            rect: [0, 0, w, h],
+        }
+    }
+
+    /// A drawing handle for the rectangle `(x, y, w, h)`, `None` if it exceeds the image
+    pub fn sub_image_mut(&mut self, x: u64, y: u64, w: u64, h: u64) -> Option<ImageDraw<'_, C>> {
+        Some(ImageDraw {
+            rect: region(self, x, y, w, h)?,
+            image: self,
+        })
+    }
+
+    /// Copies an image of any color type with its top left corner at `(x, y)`.
+    /// Pixels outside of this image are skipped.
+    pub fn blit<'i, S: Color + 'i, I: Into<SubImage<'i, S>>>(
+        &mut self,
+        x: i64,
+        y: i64,
+        src: I,
+        mode: BlendMode,
+    ) {
+        self.image_draw().blit(x, y, src, mode)
+    }
+
+    /// Returns the image scaled to `width` x `height` pixels,
+    /// `None` if such an image can not be allocated
+    pub fn resize(&self, width: u64, height: u64, filter: Filter) -> Option<Self> {
+        self.view().resize(width, height, filter)
+    }
+
+    /// Converts every pixel into another color type.
+    /// Alpha is dropped when converting into a format without alpha.
+    pub fn convert<T: Color>(&self) -> Image<T> {
+        let mut data = vec![T::default(); self.data.len()];
+        color::convert_colors(&self.data, &mut data);
+        Image {
+            data,
+            res: self.res,
+        }
+    }
+
+    /// Encodes the pixels in the memory representation of `T` in row major order,
+    /// e.g. to hand them to a library expecting BGRA bytes
+    pub fn to_bytes<T: PackedColor>(&self) -> Vec<u8> {
+        let mut out = vec![0; self.data.len() * T::SIZE];
+        color::encode_colors::<C, T>(&self.data, &mut out);
+        out
+    }
+
+    /// Creates an image from pixels stored in the memory representation of `T`,
+    /// returns `None` if the length of `bytes` does not match the size
+    pub fn from_bytes<T: PackedColor>(width: u64, height: u64, bytes: &[u8]) -> Option<Self> {
+        let area = super::get_area(width, height)?;
+        if area.checked_mul(T::SIZE)? != bytes.len() {
+            return None;
+        }
+        let mut data = vec![C::default(); area];
+        color::decode_colors::<T, C>(bytes, &mut data);
+        Self::from_data(width, height, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::color::ColorRgb24;
+    use super::*;
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> ColorRgba32 {
+        ColorRgba32::from_rgba8(r, g, b, a)
+    }
+
+    /// A 3x2 image whose red channels count the pixels in row major order
+    fn numbered() -> Image<ColorRgba32> {
+        let data = (0..6).map(|i| rgba(i, 0, 0, 255)).collect();
+        Image::from_data(3, 2, data).unwrap()
+    }
+
+    fn reds<'a, I: IntoIterator<Item = &'a [ColorRgba32]>>(rows: I) -> Vec<Vec<u8>> {
+        rows.into_iter()
+            .map(|row| row.iter().map(|p| p.r).collect())
+            .collect()
+    }
+
+    #[test]
+    fn pixels_are_addressed_in_row_major_order() {
+        let image = numbered();
+        for (x, y) in (0..2).flat_map(|y| (0..3).map(move |x| (x, y))) {
+            assert_eq!(image.get_pixel_at(x, y).unwrap().r, (y * 3 + x) as u8);
+        }
+        assert!(image.get_pixel_at(3, 0).is_none());
+        assert!(image.get_pixel_at(0, 2).is_none());
+
+        let mut image = Image::<ColorRgba32>::new(2, 3).unwrap();
+        let red = rgba(255, 0, 0, 255);
+        assert_eq!(image.set_pixel(1, 2, red.clone()), Some(rgba(0, 0, 0, 0)));
+        assert_eq!(image.set_pixel(2, 1, red.clone()), None);
+        assert_eq!(image.get_pixel_by_offset(5), Some(&red));
+        assert_eq!(image.data().iter().filter(|&p| p == &red).count(), 1);
+    }
+
+    #[test]
+    fn sub_images_stay_inside_of_their_image() {
+        let image = numbered();
+        assert!(image.sub_image(1, 1, 3, 1).is_none());
+        assert!(image.sub_image(0, 1, 1, 2).is_none());
+        assert!(image.sub_image(u64::MAX, 0, 2, 1).is_none());
+        let view = image.sub_image(1, 0, 2, 2).unwrap();
+        assert_eq!(reds(view.rows()), [[1, 2], [4, 5]]);
+        assert_eq!(view.get_pixel_at(1, 1).unwrap().r, 5);
+        assert!(view.get_pixel_at(2, 0).is_none());
+        // nested views are relative to their parent
+        let nested = view.sub_image(1, 1, 1, 1).unwrap();
+        assert_eq!(reds(nested.to_image().rows()), [[5]]);
+        assert!(view.sub_image(1, 0, 2, 1).is_none());
+        assert_eq!(image.sub_image(3, 2, 0, 0).unwrap().get_size(), (0, 0));
+
+        let mut image = numbered();
+        let mut part = image.sub_image_mut(1, 1, 2, 1).unwrap();
+        assert!(part.set_pixel(0, 1, rgba(9, 0, 0, 255)).is_none());
+        part.sub_image_mut(1, 0, 1, 1)
+            .unwrap()
+            .fill(rgba(9, 0, 0, 255));
+        assert!(part.sub_image_mut(1, 0, 2, 1).is_none());
+        assert_eq!(reds(image.rows()), [[0, 1, 2], [3, 4, 9]]);
+    }
+
+    #[test]
+    fn blits_convert_and_blend_between_color_types() {
+        let src = Image::from_data(2, 1, vec![rgba(255, 0, 0, 128), rgba(0, 255, 0, 255)]).unwrap();
+        let rgb = |mode| {
+            let white = ColorRgb24::from_rgba8(255, 255, 255, 255);
+            let mut dst = Image::from_data(3, 1, vec![white; 3]).unwrap();
+            dst.blit(1, 0, &src, mode);
+            let channels = |p: &ColorRgb24| (p.r, p.g, p.b);
+            dst.data().iter().map(channels).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rgb(BlendMode::Replace),
+            [(255, 255, 255), (255, 0, 0), (0, 255, 0)]
+        );
+        assert_eq!(
+            rgb(BlendMode::Over),
+            [(255, 255, 255), (255, 127, 127), (0, 255, 0)]
+        );
+
+        // replacing keeps the alpha of the source, blending composites it
+        let rgba_dst = |mode| {
+            let mut dst = Image::from_data(2, 1, vec![rgba(0, 0, 255, 255); 2]).unwrap();
+            dst.blit(-1, 0, &src, mode);
+            dst.blit(0, 0, src.sub_image(0, 0, 1, 1).unwrap(), mode);
+            let channels = |p: &ColorRgba32| (p.r, p.g, p.b, p.a);
+            dst.data().iter().map(channels).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rgba_dst(BlendMode::Replace),
+            [(255, 0, 0, 128), (0, 0, 255, 255)]
+        );
+        assert_eq!(
+            rgba_dst(BlendMode::Over),
+            [(128, 127, 0, 255), (0, 0, 255, 255)]
+        );
+    }
+
+    #[test]
+    fn resizing_samples_nearest_or_interpolated_pixels() {
+        let src = Image::from_data(2, 1, vec![rgba(0, 0, 0, 255), rgba(255, 0, 0, 255)]).unwrap();
+        let red = |image: Image<ColorRgba32>| reds(image.rows());
+        assert_eq!(
+            red(src.resize(4, 2, Filter::Nearest).unwrap()),
+            [[0, 0, 255, 255]; 2]
+        );
+        assert_eq!(
+            red(src.resize(4, 1, Filter::Bilinear).unwrap()),
+            [[0, 64, 191, 255]]
+        );
+        let shrunk = numbered().resize(1, 1, Filter::Nearest).unwrap();
+        assert_eq!(red(shrunk), [[4]]);
+
+        // transparent pixels fade out without bleeding their color
+        let src = Image::from_data(2, 1, vec![rgba(255, 0, 0, 255), rgba(0, 0, 255, 0)]).unwrap();
+        let faded = src.resize(4, 1, Filter::Bilinear).unwrap();
+        let channels = |p: &ColorRgba32| (p.r, p.b, p.a);
+        assert_eq!(
+            faded.data().iter().map(channels).collect::<Vec<_>>(),
+            [(255, 0, 255), (255, 0, 191), (255, 0, 64), (0, 0, 0)]
+        );
+        let empty = Image::<ColorRgba32>::new(0, 3).unwrap();
+        assert_eq!(empty.resize(2, 2, Filter::Bilinear).unwrap().data()[0].a, 0);
+    }
+}
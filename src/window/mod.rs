@@ -1,13 +1,20 @@
 //! Tools for managing windows platform independently
 
+pub mod codec;
 pub mod color;
 pub mod draw;
 pub mod event;
+pub mod font;
+mod image;
+mod raster;
 pub mod xwindow;
 
-use color::{Color, PixelFormat};
+use color::{Color, ColorRgba32, PixelFormat};
 use core::convert::TryInto;
 use draw::DrawCommand;
+use std::time::Duration;
+
+pub use image::{BlendMode, Filter, ImageDraw, SubImage};
 
 #[derive(Clone, Copy)]
 pub enum WindowType {
@@ -178,9 +185,23 @@ pub trait Window<'a, D: Display>: Iterator<Item = Result<event::Event, D::Error>
     fn new(wb: WindowBuilder<'a, D>) -> Result<Self, Self::Error>
     where
         Self: Sized;
+    /// Waits for the next event, but no longer than `timeout` if one is given.
+    /// Returns `Ok(None)` if the time ran out.
+    fn wait_event(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Option<event::Event>, Self::Error>;
+    /// Draws onto the window, the result becomes visible on `present`
+    fn draw(&mut self, draw: DrawCommand<ColorRgba32>) -> Result<(), Self::Error>;
+    /// The image of the window's size everything is drawn onto before `present`,
+    /// so that content can be rendered into it without going through draw commands
+    fn canvas(&mut self) -> &mut Image<ColorRgba32>;
+    /// Makes everything drawn so far visible
+    fn present(&mut self) -> Result<(), Self::Error>;
 }
 
 /// An image stored as an 1D array of colors
+#[derive(Clone)]
 pub struct Image<C: Color> {
     data: Vec<C>,
     /// The following are guaranteed to fit into an usize:
@@ -192,6 +213,16 @@ fn get_area(width: u64, height: u64) -> Option<usize> {
     usize::checked_mul(width.try_into().ok()?, height.try_into().ok()?)
 }
 
+impl<C: Color> std::fmt::Debug for Image<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Image")
+            .field("format", &C::get_format())
+            .field("width", &self.res.0)
+            .field("height", &self.res.1)
+            .finish()
+    }
+}
+
 impl<C: Color> Image<C> {
     pub fn new(width: u64, height: u64) -> Option<Self> {
         let area = get_area(width, height)?;
@@ -204,18 +235,75 @@ impl<C: Color> Image<C> {
         })
     }
 
+    /// Creates an image from row major pixels, returns `None` if the sizes do not match
+    pub fn from_data(width: u64, height: u64, data: Vec<C>) -> Option<Self> {
+        if get_area(width, height)? != data.len() {
+            return None;
+        }
+        Some(Self {
+            data,
+            res: (width, height),
+        })
+    }
+
     pub fn get_pixel_by_offset(&self, off: usize) -> Option<&C> {
         self.data.get(off)
     }
 
+    /// Calculates the offset of a pixel, returns `None` if it is outside of the image
+    fn get_offset(&self, x: u64, y: u64) -> Option<usize> {
+        if x >= self.res.0 || y >= self.res.1 {
+            return None;
+        }
+        // the area is guaranteed to fit into an usize by all constructors
+        Some(y as usize * self.res.0 as usize + x as usize)
+    }
+
     pub fn get_pixel_at(&self, x: u64, y: u64) -> Option<&C> {
-        // the width is guaranteed to fit into an usize by all constructors
-        let width = self.res.0.try_into().unwrap();
-        let off = usize::checked_mul(
-            usize::checked_mul(y.try_into().ok()?, width)?,
-            x.try_into().ok()?,
-        )?;
-        self.get_pixel_by_offset(off)
+        self.get_offset(x, y)
+            .and_then(|off| self.get_pixel_by_offset(off))
+    }
+
+    pub fn get_pixel_at_mut(&mut self, x: u64, y: u64) -> Option<&mut C> {
+        let off = self.get_offset(x, y)?;
+        self.data.get_mut(off)
+    }
+
+    /// Replaces a pixel without blending, returns the previous color
+    /// or `None` if the pixel is outside of the image
+    pub fn set_pixel(&mut self, x: u64, y: u64, color: C) -> Option<C> {
+        self.get_pixel_at_mut(x, y)
+            .map(|pixel| std::mem::replace(pixel, color))
+    }
+
+    /// The pixels in row major order
+    pub fn data(&self) -> &[C] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [C] {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> Vec<C> {
+        self.data
+    }
+
+    /// Iterates over the rows from top to bottom
+    pub fn rows(&self) -> std::slice::ChunksExact<'_, C> {
+        // images without columns have no pixels, so any chunk size yields no rows
+        self.data.chunks_exact((self.res.0 as usize).max(1))
+    }
+
+    pub fn rows_mut(&mut self) -> std::slice::ChunksExactMut<'_, C> {
+        self.data.chunks_exact_mut((self.res.0 as usize).max(1))
+    }
+
+    /// Replaces every pixel without blending
+    pub fn fill(&mut self, color: C) {
+        self.data
+            .iter_mut()
+            .for_each(|pixel| *pixel = color.clone());
     }
 }
 
@@ -227,11 +315,17 @@ pub trait Surface<C: Color> {
         (self.get_width(), self.get_height())
     }
     fn get_pixel_format(&self) -> PixelFormat;
-    fn draw(&self, draw: DrawCommand<C>) -> Result<(), Self::Error>;
+    fn draw(&mut self, draw: DrawCommand<C>) -> Result<(), Self::Error>;
+    /// Makes everything drawn so far visible, surfaces without a front buffer do nothing
+    fn present(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 pub enum ImageDrawError {
+    /// The geometry starting at the given point exceeds the coordinate space.
+    /// Geometry that just lies outside of the image is clipped instead.
     OutOfBounds(i32, i32),
 }
 
@@ -256,9 +350,7 @@ impl<C: Color + 'static> Surface<C> for Image<C> {
     fn get_pixel_format(&self) -> PixelFormat {
         C::get_format()
     }
-    fn draw(&self, draw: DrawCommand<C>) -> Result<(), ImageDrawError> {
-        todo!();
+    fn draw(&mut self, draw: DrawCommand<C>) -> Result<(), ImageDrawError> {
+        raster::draw(self, &draw)
     }
 }
-
-pub struct ImageDraw<'s, C: Color>(&'s mut Image<C>);
@@ -0,0 +1,1019 @@
+//! Software rasterization of [`DrawCommand`]s into [`Image`]s
+
+use super::color::{Color, ColorRgba32};
+use super::draw::{
+    Blit, Circle, CircleArc, DrawCommand, FillRule, Line, LineInfo, Paint, Path, Polygon, Radii,
+    Rect, Text,
+};
+use super::font::{GlyphBitmap, GlyphCache};
+use super::{Image, ImageDrawError};
+
+mod paint;
+mod path;
+mod polygon;
+
+use paint::Sampler;
+
+/// Draws a command tree into an image.
+/// Everything outside of the image is clipped, the image is only left untouched
+/// if the command contains geometry that can not be represented in 32 bit coordinates.
+pub fn draw<C: Color>(img: &mut Image<C>, cmd: &DrawCommand<C>) -> Result<(), ImageDrawError> {
+    let (width, height) = size(img);
+    draw_region(img, cmd, (0, 0, width, height))
+}
+
+/// Draws a command tree into the region `(x, y, w, h)` of an image,
+/// which must lie inside of the image. The region's top left corner is the origin.
+pub fn draw_region<C: Color>(
+    img: &mut Image<C>,
+    cmd: &DrawCommand<C>,
+    (x, y, w, h): (i64, i64, i64, i64),
+) -> Result<(), ImageDrawError> {
+    validate(cmd)?;
+    raster(
+        &mut Canvas {
+            img,
+            clip: (x, y, x + w, y + h),
+            origin: (x, y),
+        },
+        cmd,
+    );
+    Ok(())
+}
+
+/// An image with the state of the scopes enclosing a command
+struct Canvas<'a, C: Color> {
+    img: &'a mut Image<C>,
+    /// The pixels that may be changed as `(x0, y0, x1, y1)`, always inside of the image
+    clip: (i64, i64, i64, i64),
+    /// The position of the local origin in the image
+    origin: (i64, i64),
+}
+
+impl<'a, C: Color> Canvas<'a, C> {
+    /// A canvas for a nested scope
+    fn scope(&mut self, clip: (i64, i64, i64, i64), origin: (i64, i64)) -> Canvas<'_, C> {
+        Canvas {
+            img: self.img,
+            clip,
+            origin,
+        }
+    }
+
+    /// Converts a local rectangle `(x0, y0, x1, y1)` to the pixels of the image
+    /// inside of the clip region, the result may be empty
+    fn to_device(&self, (x0, y0, x1, y1): (i64, i64, i64, i64)) -> (i64, i64, i64, i64) {
+        let (ox, oy) = self.origin;
+        let (cx0, cy0, cx1, cy1) = self.clip;
+        (
+            (x0 + ox).max(cx0),
+            (y0 + oy).max(cy0),
+            (x1 + ox).min(cx1),
+            (y1 + oy).min(cy1),
+        )
+    }
+
+    fn pixel(&mut self, x: i64, y: i64) -> &mut C {
+        let width = size(self.img).0;
+        &mut self.img.data[(y * width + x) as usize]
+    }
+}
+
+fn validate<C: Color>(cmd: &DrawCommand<C>) -> Result<(), ImageDrawError> {
+    match cmd {
+        DrawCommand::FilledRect(rect, _)
+        | DrawCommand::RectOutline(rect, _)
+        | DrawCommand::FilledRoundedRect(rect, ..)
+        | DrawCommand::RoundedRectOutline(rect, ..) => validate_rect(rect),
+        DrawCommand::FilledCircle(circle, _) | DrawCommand::CircleOutline(circle, _) => {
+            validate_circle(circle)
+        }
+        DrawCommand::Arc(arc, _) => validate_circle(&arc.circle),
+        DrawCommand::Line(line, _) => {
+            match (line.x.checked_add(line.w), line.y.checked_add(line.h)) {
+                (Some(_), Some(_)) => Ok(()),
+                _ => Err(ImageDrawError::OutOfBounds(line.x, line.y)),
+            }
+        }
+        DrawCommand::FilledPolygon(polygon, _) => validate_points(polygon.points.iter().copied()),
+        DrawCommand::FilledPath(path, ..) | DrawCommand::PathOutline(path, _) => {
+            validate_points(path.points())
+        }
+        DrawCommand::Clear(_) | DrawCommand::Pixel(..) | DrawCommand::Text(..) => Ok(()),
+        DrawCommand::Image(blit) => {
+            let (w, h) = blit.get_size();
+            validate_rect(&Rect::new(blit.x, blit.y, w, h))
+        }
+        DrawCommand::Translate(_, _, cmd) => validate(cmd),
+        DrawCommand::Clip(rect, cmd) => validate_rect(rect).and_then(|_| validate(cmd)),
+        DrawCommand::Chain(cmds) => cmds.iter().try_for_each(validate),
+    }
+}
+
+/// Checks that the last row and column of a rectangle are valid coordinates
+fn validate_rect(rect: &Rect) -> Result<(), ImageDrawError> {
+    let max = i64::from(i32::MAX);
+    if i64::from(rect.x) + i64::from(rect.w) - 1 > max
+        || i64::from(rect.y) + i64::from(rect.h) - 1 > max
+    {
+        Err(ImageDrawError::OutOfBounds(rect.x, rect.y))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that points are finite and lie inside of the coordinate space
+fn validate_points<I: IntoIterator<Item = (f32, f32)>>(points: I) -> Result<(), ImageDrawError> {
+    let range = i32::MIN as f32..=i32::MAX as f32;
+    match points
+        .into_iter()
+        .find(|(x, y)| !range.contains(x) || !range.contains(y))
+    {
+        // NaN is saturated to 0 by the conversion
+        Some((x, y)) => Err(ImageDrawError::OutOfBounds(x as i32, y as i32)),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the bounding square of a circle lies inside of the coordinate space
+fn validate_circle(circle: &Circle) -> Result<(), ImageDrawError> {
+    let r = i64::from(circle.r);
+    let range = i64::from(i32::MIN)..=i64::from(i32::MAX);
+    let (x, y) = (i64::from(circle.x), i64::from(circle.y));
+    if [x - r, x + r, y - r, y + r]
+        .iter()
+        .all(|c| range.contains(c))
+    {
+        Ok(())
+    } else {
+        Err(ImageDrawError::OutOfBounds(circle.x, circle.y))
+    }
+}
+
+fn raster<C: Color>(img: &mut Canvas<C>, cmd: &DrawCommand<C>) {
+    match cmd {
+        DrawCommand::Clear(color) => {
+            let (x0, y0, x1, y1) = img.clip;
+            for row in y0..y1 {
+                for col in x0..x1 {
+                    *img.pixel(col, row) = color.clone();
+                }
+            }
+        }
+        DrawCommand::FilledRect(rect, paint) => {
+            let (x, y) = (i64::from(rect.x), i64::from(rect.y));
+            let (x1, y1) = (x + i64::from(rect.w), y + i64::from(rect.h));
+            let extent = (x as f64, y as f64, x1 as f64, y1 as f64);
+            match Sampler::new(paint, extent) {
+                Sampler::Solid(color) => fill_rect(img, x, y, x1 - x, y1 - y, &color),
+                sampler => fill_shape(img, (x, y, x1, y1), &sampler, |_, _| 1.0),
+            }
+        }
+        DrawCommand::RectOutline(rect, info) => rect_outline(img, rect, info),
+        DrawCommand::FilledRoundedRect(rect, radii, paint) => {
+            fill_rounded(img, RoundedRect::from_rect(rect, radii), paint)
+        }
+        DrawCommand::RoundedRectOutline(rect, radii, info) => {
+            rounded_outline(img, RoundedRect::from_rect(rect, radii), info)
+        }
+        DrawCommand::FilledCircle(circle, paint) => {
+            fill_rounded(img, RoundedRect::circle(circle), paint)
+        }
+        DrawCommand::CircleOutline(circle, info) => {
+            rounded_outline(img, RoundedRect::circle(circle), info)
+        }
+        DrawCommand::Arc(arc, info) => draw_arc(img, arc, info),
+        DrawCommand::Line(line, info) => draw_line(img, line, info),
+        DrawCommand::FilledPolygon(polygon, paint) => fill_polygon(img, polygon, paint),
+        DrawCommand::FilledPath(path, rule, paint) => {
+            fill_contours(img, &path::flatten(path), *rule, paint)
+        }
+        DrawCommand::PathOutline(path, info) => stroke_path(img, path, info),
+        DrawCommand::Pixel(x, y, color) => fill_rect(img, (*x).into(), (*y).into(), 1, 1, color),
+        DrawCommand::Text(text, paint) => draw_text(img, text, paint),
+        DrawCommand::Image(blit) => draw_image(img, blit),
+        DrawCommand::Translate(x, y, cmd) => {
+            let origin = (img.origin.0 + i64::from(*x), img.origin.1 + i64::from(*y));
+            raster(&mut img.scope(img.clip, origin), cmd)
+        }
+        DrawCommand::Clip(rect, cmd) => {
+            let (x, y) = (i64::from(rect.x), i64::from(rect.y));
+            let clip = img.to_device((x, y, x + i64::from(rect.w), y + i64::from(rect.h)));
+            if clip.0 < clip.2 && clip.1 < clip.3 {
+                raster(&mut img.scope(clip, img.origin), cmd)
+            }
+        }
+        DrawCommand::Chain(cmds) => cmds.iter().for_each(|cmd| raster(img, cmd)),
+    }
+}
+
+fn size<C: Color>(img: &Image<C>) -> (i64, i64) {
+    // all constructors guarantee that the dimensions fit into an usize
+    (img.res.0 as i64, img.res.1 as i64)
+}
+
+/// Fills a rectangle clipped to the clip region
+fn fill_rect<C: Color>(img: &mut Canvas<C>, x: i64, y: i64, w: i64, h: i64, color: &C) {
+    let width = size(img.img).0;
+    let (x0, y0, x1, y1) = img.to_device((x, y, x + w, y + h));
+    if x0 >= x1 || y0 >= y1 || color.a8() == 0 {
+        return;
+    }
+    let img = &mut *img.img;
+    for row in y0..y1 {
+        let start = (row * width) as usize;
+        let pixels = &mut img.data[start + x0 as usize..start + x1 as usize];
+        if color.a8() == 255 {
+            pixels.fill(color.clone());
+        } else {
+            pixels.iter_mut().for_each(|dst| blend(dst, color, 255));
+        }
+    }
+}
+
+/// Strokes the inside of a rectangle, so that the outline never exceeds the rectangle
+fn rect_outline<C: Color>(img: &mut Canvas<C>, rect: &Rect, info: &LineInfo<C>) {
+    let lw = i64::from(info.width.max(1));
+    let (x, y, w, h) = (
+        i64::from(rect.x),
+        i64::from(rect.y),
+        i64::from(rect.w),
+        i64::from(rect.h),
+    );
+    if 2 * lw >= w || 2 * lw >= h {
+        return fill_rect(img, x, y, w, h, &info.color);
+    }
+    fill_rect(img, x, y, w, lw, &info.color);
+    fill_rect(img, x, y + h - lw, w, lw, &info.color);
+    fill_rect(img, x, y + lw, lw, h - 2 * lw, &info.color);
+    fill_rect(img, x + w - lw, y + lw, lw, h - 2 * lw, &info.color);
+}
+
+/// Blends a shape given by its coverage of each pixel into the image.
+/// `coverage` is called with the local center of every pixel inside of `bounds`
+/// (`x0, y0, x1, y1`, exclusive) and returns the covered fraction of that pixel.
+fn fill_shape<C: Color, F: FnMut(f64, f64) -> f64>(
+    img: &mut Canvas<C>,
+    bounds: (i64, i64, i64, i64),
+    paint: &Sampler<C>,
+    mut coverage: F,
+) {
+    let (x0, y0, x1, y1) = img.to_device(bounds);
+    let (ox, oy) = img.origin;
+    for row in y0..y1 {
+        for col in x0..x1 {
+            let (x, y) = ((col - ox) as f64 + 0.5, (row - oy) as f64 + 0.5);
+            let c = (coverage(x, y).clamp(0.0, 1.0) * 255.0).round() as u8;
+            if c > 0 {
+                blend(img.pixel(col, row), &paint.at(x, y), c);
+            }
+        }
+    }
+}
+
+/// Draws an antialiased line including both end points.
+/// The stroke is a rectangle of the line width centered on the segment,
+/// which extends half the width beyond both end points like a square cap.
+/// Pixel coordinates address the pixel centers, so that horizontal and vertical
+/// lines of any width cover whole pixels just like a filled rectangle.
+fn draw_line<C: Color>(img: &mut Canvas<C>, line: &Line, info: &LineInfo<C>) {
+    let lw = f64::from(info.width.max(1));
+    // a stroke of an even width lies right of and below the pixel it starts at
+    let center = (info.width.max(1) as i64 - 1) / 2;
+    let shift = lw / 2.0 - center as f64;
+    let (x0, y0) = (f64::from(line.x) + shift, f64::from(line.y) + shift);
+    let (dx, dy) = (f64::from(line.w), f64::from(line.h));
+    let len = dx.hypot(dy);
+    // the unit vector along the segment, degenerate lines become a square
+    let (ux, uy) = if len > 0.0 {
+        (dx / len, dy / len)
+    } else {
+        (1.0, 0.0)
+    };
+    let (mx, my) = (x0 + dx / 2.0, y0 + dy / 2.0);
+    let (half_len, half_width) = ((len + lw) / 2.0, lw / 2.0);
+    let reach = (half_len + half_width).ceil() as i64 + 1;
+    let bounds = (
+        mx.floor() as i64 - reach,
+        my.floor() as i64 - reach,
+        mx.floor() as i64 + reach + 1,
+        my.floor() as i64 + reach + 1,
+    );
+    fill_shape(img, bounds, &Sampler::solid(&info.color), |px, py| {
+        let (rx, ry) = (px - mx, py - my);
+        let along = (rx * ux + ry * uy).abs();
+        let across = (ry * ux - rx * uy).abs();
+        // approximates the covered area by the distance of the pixel center to the edges
+        (half_len + 0.5 - along).clamp(0.0, 1.0) * (half_width + 0.5 - across).clamp(0.0, 1.0)
+    });
+}
+
+/// Fills a polygon, gradients span its bounding box
+fn fill_polygon<C: Color>(img: &mut Canvas<C>, polygon: &Polygon, paint: &Paint<C>) {
+    let points: Vec<_> = polygon
+        .points
+        .iter()
+        .map(|&(x, y)| (f64::from(x), f64::from(y)))
+        .collect();
+    fill_contours(img, &[points], FillRule::NonZero, paint);
+}
+
+/// Fills closed contours, gradients span their bounding box
+fn fill_contours<C: Color>(
+    img: &mut Canvas<C>,
+    contours: &[Vec<(f64, f64)>],
+    rule: FillRule,
+    paint: &Paint<C>,
+) {
+    if let Some(extent) = polygon::extent(contours) {
+        polygon::fill(img, contours, rule, &Sampler::new(paint, extent));
+    }
+}
+
+/// Strokes a path by filling the union of its widened segments and round joins
+fn stroke_path<C: Color>(img: &mut Canvas<C>, path: &Path, info: &LineInfo<C>) {
+    let outline = path::stroke(path, f64::from(info.width.max(1)));
+    polygon::fill(
+        img,
+        &outline,
+        FillRule::NonZero,
+        &Sampler::solid(&info.color),
+    );
+}
+
+/// A rectangle with elliptical corners in continuous coordinates
+#[derive(Debug, Clone, Copy)]
+struct RoundedRect {
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+    /// top left, top right, bottom right and bottom left radius
+    radii: [f64; 4],
+}
+
+impl RoundedRect {
+    /// Creates the shape, scaling down radii that do not fit like CSS does
+    fn new(x0: f64, y0: f64, x1: f64, y1: f64, radii: [f64; 4]) -> Self {
+        let [tl, tr, br, bl] = radii;
+        let (w, h) = (x1 - x0, y1 - y0);
+        let scale = [(w, tl + tr), (h, tr + br), (w, br + bl), (h, bl + tl)]
+            .iter()
+            .filter(|(_, sum)| *sum > 0.0)
+            .map(|(side, sum)| side / sum)
+            .fold(1.0, f64::min);
+        Self {
+            x0,
+            y0,
+            x1,
+            y1,
+            radii: [tl * scale, tr * scale, br * scale, bl * scale],
+        }
+    }
+
+    fn from_rect(rect: &Rect, radii: &Radii) -> Self {
+        let (x, y) = (f64::from(rect.x), f64::from(rect.y));
+        let radii = [
+            radii.top_left,
+            radii.top_right,
+            radii.bottom_right,
+            radii.bottom_left,
+        ];
+        Self::new(
+            x,
+            y,
+            x + f64::from(rect.w),
+            y + f64::from(rect.h),
+            radii.map(f64::from),
+        )
+    }
+
+    fn circle(circle: &Circle) -> Self {
+        let (x, y, r) = (
+            f64::from(circle.x),
+            f64::from(circle.y),
+            f64::from(circle.r),
+        );
+        Self::new(x - r, y - r, x + r, y + r, [r; 4])
+    }
+
+    /// Moves every edge inwards by `d`, so that corners stay concentric
+    fn inset(&self, d: f64) -> Self {
+        Self::new(
+            self.x0 + d,
+            self.y0 + d,
+            self.x1 - d,
+            self.y1 - d,
+            self.radii.map(|r| (r - d).max(0.0)),
+        )
+    }
+
+    fn is_empty(&self) -> bool {
+        self.x0 >= self.x1 || self.y0 >= self.y1
+    }
+
+    /// The bounding box of the shape as `(x0, y0, x1, y1)`
+    fn extent(&self) -> (f64, f64, f64, f64) {
+        (self.x0, self.y0, self.x1, self.y1)
+    }
+
+    /// The pixels that may be covered as `(x0, y0, x1, y1)`
+    fn bounds(&self) -> (i64, i64, i64, i64) {
+        (
+            self.x0.floor() as i64,
+            self.y0.floor() as i64,
+            self.x1.ceil() as i64,
+            self.y1.ceil() as i64,
+        )
+    }
+
+    /// Approximates the covered fraction of the pixel centered at `(x, y)`
+    fn coverage(&self, x: f64, y: f64) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let edge = (x - self.x0)
+            .min(self.x1 - x)
+            .min(y - self.y0)
+            .min(self.y1 - y)
+            + 0.5;
+        let [tl, tr, br, bl] = self.radii;
+        let corner = if x < self.x0 + tl && y < self.y0 + tl {
+            Some((self.x0 + tl, self.y0 + tl, tl))
+        } else if x > self.x1 - tr && y < self.y0 + tr {
+            Some((self.x1 - tr, self.y0 + tr, tr))
+        } else if x > self.x1 - br && y > self.y1 - br {
+            Some((self.x1 - br, self.y1 - br, br))
+        } else if x < self.x0 + bl && y > self.y1 - bl {
+            Some((self.x0 + bl, self.y1 - bl, bl))
+        } else {
+            None
+        };
+        let coverage = match corner {
+            Some((cx, cy, r)) => edge.min(r - (x - cx).hypot(y - cy) + 0.5),
+            None => edge,
+        };
+        coverage.clamp(0.0, 1.0)
+    }
+}
+
+/// Fills a rounded shape, gradients span its bounding box
+fn fill_rounded<C: Color>(img: &mut Canvas<C>, shape: RoundedRect, paint: &Paint<C>) {
+    let paint = Sampler::new(paint, shape.extent());
+    fill_shape(img, shape.bounds(), &paint, |x, y| shape.coverage(x, y))
+}
+
+/// Strokes the inside of a rounded shape
+fn rounded_outline<C: Color>(img: &mut Canvas<C>, shape: RoundedRect, info: &LineInfo<C>) {
+    let inner = shape.inset(f64::from(info.width.max(1)));
+    fill_shape(img, shape.bounds(), &Sampler::solid(&info.color), |x, y| {
+        shape.coverage(x, y) - inner.coverage(x, y)
+    });
+}
+
+/// Strokes an arc inside of its circle with flat ends
+fn draw_arc<C: Color>(img: &mut Canvas<C>, arc: &CircleArc, info: &LineInfo<C>) {
+    let shape = RoundedRect::circle(&arc.circle);
+    let inner = shape.inset(f64::from(info.width.max(1)));
+    let (mut start, mut sweep) = (f64::from(arc.start), f64::from(arc.sweep));
+    if sweep == 0.0 || !start.is_finite() || !sweep.is_finite() {
+        return;
+    }
+    if sweep < 0.0 {
+        start += sweep;
+        sweep = -sweep;
+    }
+    let full = sweep >= 360.0;
+    let (start, end) = (start.to_radians(), (start + sweep).to_radians());
+    let (cx, cy) = (f64::from(arc.circle.x), f64::from(arc.circle.y));
+    fill_shape(img, shape.bounds(), &Sampler::solid(&info.color), |x, y| {
+        let ring = shape.coverage(x, y) - inner.coverage(x, y);
+        if full || ring <= 0.0 {
+            return ring;
+        }
+        let (dx, dy) = (x - cx, y - cy);
+        // signed distances to the lines through both ends, positive towards the arc
+        let after_start = start.cos() * dy - start.sin() * dx;
+        let before_end = dx * end.sin() - dy * end.cos();
+        // an arc of up to a half circle is the intersection of both half planes,
+        // a larger one their union
+        let inside = if sweep <= 180.0 {
+            after_start.min(before_end)
+        } else {
+            after_start.max(before_end)
+        };
+        ring * (inside + 0.5).clamp(0.0, 1.0)
+    });
+}
+
+/// Composites `color` with the given coverage over a pixel with the source-over operator.
+/// The colors are premultiplied with their alpha for compositing,
+/// the result is stored with straight alpha again.
+pub fn blend<C: Color>(dst: &mut C, color: &C, coverage: u8) {
+    let sa = (u32::from(coverage) * u32::from(color.a8()) + 127) / 255;
+    if sa == 0 {
+        return;
+    }
+    if sa == 255 {
+        *dst = color.clone();
+        return;
+    }
+    // the premultiplied channels are scaled by 255 * 255 to stay in integers:
+    // out = src * src_alpha + dst * dst_alpha * (1 - src_alpha)
+    let da = u32::from(dst.a8()) * (255 - sa);
+    let out = sa * 255 + da;
+    let mix = |s: u8, d: u8| ((u32::from(s) * sa * 255 + u32::from(d) * da + out / 2) / out) as u8;
+    *dst = C::from_rgba8(
+        mix(color.r8(), dst.r8()),
+        mix(color.g8(), dst.g8()),
+        mix(color.b8(), dst.b8()),
+        ((out + 127) / 255) as u8,
+    );
+}
+
+/// Blends a coverage mask with its top left corner at the local position `(x, y)`
+fn fill_coverage<C: Color>(
+    img: &mut Canvas<C>,
+    x: i64,
+    y: i64,
+    mask: &GlyphBitmap,
+    paint: &Sampler<C>,
+) {
+    let (w, h) = (i64::from(mask.width), i64::from(mask.height));
+    let (x0, y0, x1, y1) = img.to_device((x, y, x + w, y + h));
+    let (ox, oy) = img.origin;
+    for row in y0..y1 {
+        for col in x0..x1 {
+            let (lx, ly) = (col - ox, row - oy);
+            let coverage = mask.coverage[((ly - y) * w + lx - x) as usize];
+            if coverage > 0 {
+                let color = paint.at(lx as f64 + 0.5, ly as f64 + 0.5);
+                blend(img.pixel(col, row), &color, coverage);
+            }
+        }
+    }
+}
+
+/// Draws a line of text, glyphs are positioned with subpixel precision
+/// and taken from the global glyph cache
+fn draw_text<C: Color>(img: &mut Canvas<C>, text: &Text, paint: &Paint<C>) {
+    let fonts = text.font.fonts();
+    let extents = text.font.measure("");
+    let baseline = i64::from(text.y) + i64::from(extents.ascent);
+    let (glyphs, advance) = text.font.layout(&text.text);
+    let masks = {
+        let mut cache = GlyphCache::global()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        glyphs
+            .into_iter()
+            .filter_map(|glyph| {
+                let (origin, subpixel) =
+                    GlyphCache::quantize(f64::from(text.x) + f64::from(glyph.x));
+                let mask = cache.get(&fonts[glyph.font], glyph.id, subpixel)?;
+                Some((origin, mask))
+            })
+            .collect::<Vec<_>>()
+    };
+    let (x, y) = (f64::from(text.x), f64::from(text.y));
+    let extent = (
+        x,
+        y,
+        x + f64::from(advance),
+        y + f64::from(extents.height()),
+    );
+    let paint = Sampler::new(paint, extent);
+    for (origin, mask) in masks {
+        let x = origin + i64::from(mask.left);
+        fill_coverage(img, x, baseline + i64::from(mask.top), &mask, &paint);
+    }
+}
+
+/// Reads a pixel of an image with premultiplied channels from `0.0` to `255.0`
+fn premultiplied(img: &Image<ColorRgba32>, x: i64, y: i64) -> [f64; 4] {
+    let p = &img.data[(y * size(img).0 + x) as usize];
+    let a = f64::from(p.a) / 255.0;
+    [
+        f64::from(p.r) * a,
+        f64::from(p.g) * a,
+        f64::from(p.b) * a,
+        f64::from(p.a),
+    ]
+}
+
+/// Samples the area `x0..x1`, `y0..y1` of an image given in source pixels.
+/// Areas of at least a pixel are averaged, smaller ones are interpolated bilinearly,
+/// so that icons stay smooth when they are scaled in either direction.
+fn sample_area(img: &Image<ColorRgba32>, (x0, y0, x1, y1): (f64, f64, f64, f64)) -> [f64; 4] {
+    let (w, h) = size(img);
+    let mut sum = [0.0; 4];
+    if x1 - x0 >= 1.0 && y1 - y0 >= 1.0 {
+        let mut total = 0.0;
+        for sy in (y0.floor() as i64).max(0)..(y1.ceil() as i64).min(h) {
+            let wy = (y1.min(sy as f64 + 1.0) - y0.max(sy as f64)).max(0.0);
+            for sx in (x0.floor() as i64).max(0)..(x1.ceil() as i64).min(w) {
+                let weight = wy * (x1.min(sx as f64 + 1.0) - x0.max(sx as f64)).max(0.0);
+                let p = premultiplied(img, sx, sy);
+                (0..4).for_each(|i| sum[i] += p[i] * weight);
+                total += weight;
+            }
+        }
+        if total > 0.0 {
+            sum.iter_mut().for_each(|c| *c /= total);
+        }
+    } else {
+        // the center in coordinates of pixel centers
+        let (cx, cy) = ((x0 + x1) / 2.0 - 0.5, (y0 + y1) / 2.0 - 0.5);
+        let (fx, fy) = (cx - cx.floor(), cy - cy.floor());
+        let (px, py) = (cx.floor() as i64, cy.floor() as i64);
+        for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+            for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+                let p = premultiplied(img, (px + dx).clamp(0, w - 1), (py + dy).clamp(0, h - 1));
+                (0..4).for_each(|i| sum[i] += p[i] * wx * wy);
+            }
+        }
+    }
+    sum
+}
+
+/// Draws an image scaled to the size of the blit
+fn draw_image<C: Color>(img: &mut Canvas<C>, blit: &Blit) {
+    let src = &*blit.image;
+    let (sw, sh) = size(src);
+    let (w, h) = blit.get_size();
+    let (w, h) = (i64::from(w), i64::from(h));
+    if sw == 0 || sh == 0 || w == 0 || h == 0 || blit.alpha == 0 {
+        return;
+    }
+    let (x, y) = (i64::from(blit.x), i64::from(blit.y));
+    let (x0, y0, x1, y1) = img.to_device((x, y, x + w, y + h));
+    let (ox, oy) = img.origin;
+    let (scale_x, scale_y) = (sw as f64 / w as f64, sh as f64 / h as f64);
+    for row in y0..y1 {
+        for col in x0..x1 {
+            let (lx, ly) = (col - ox - x, row - oy - y);
+            let color = if (sw, sh) == (w, h) {
+                let p = &src.data[(ly * sw + lx) as usize];
+                C::from_rgba8(p.r, p.g, p.b, p.a)
+            } else {
+                let area = (
+                    lx as f64 * scale_x,
+                    ly as f64 * scale_y,
+                    (lx + 1) as f64 * scale_x,
+                    (ly + 1) as f64 * scale_y,
+                );
+                let [r, g, b, a] = sample_area(src, area);
+                let channel = |c: f64| {
+                    if a > 0.0 {
+                        (c / a * 255.0).round().clamp(0.0, 255.0) as u8
+                    } else {
+                        0
+                    }
+                };
+                C::from_rgba8(channel(r), channel(g), channel(b), a.round() as u8)
+            };
+            blend(img.pixel(col, row), &color, blit.alpha);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> ColorRgba32 {
+        ColorRgba32::from_rgba8(r, g, b, a)
+    }
+
+    fn fill(x: i32, y: i32, w: u32, h: u32, color: ColorRgba32) -> DrawCommand<ColorRgba32> {
+        DrawCommand::FilledRect(Rect::new(x, y, w, h), color.into())
+    }
+
+    /// The coordinates of every pixel that is not transparent
+    fn painted(img: &Image<ColorRgba32>) -> Vec<(u64, u64)> {
+        let (w, h) = img.res;
+        (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .filter(|&(x, y)| img.get_pixel_at(x, y).unwrap().a > 0)
+            .collect()
+    }
+
+    #[test]
+    fn geometry_beyond_the_coordinate_space_is_rejected() {
+        let mut img = Image::new(4, 4).unwrap();
+        let red = rgba(255, 0, 0, 255);
+        // the last column of the rectangle is still a valid coordinate
+        assert!(draw(&mut img, &fill(i32::MAX, 0, 1, 1, red.clone())).is_ok());
+        let rejected = [
+            fill(i32::MAX, 0, 2, 1, red.clone()),
+            fill(0, i32::MAX - 1, 1, 3, red.clone()),
+            DrawCommand::Line(Line::new(i32::MAX, 0, 1, 0), LineInfo::new(1, red.clone())),
+            DrawCommand::FilledPolygon(
+                Polygon::triangle((0.0, 0.0), (f32::NAN, 1.0), (1.0, 1.0)),
+                red.clone().into(),
+            ),
+            DrawCommand::FilledPolygon(
+                Polygon::triangle((0.0, 0.0), (1e10, 1.0), (1.0, 1.0)),
+                red.clone().into(),
+            ),
+        ];
+        for cmd in rejected.iter() {
+            assert!(matches!(
+                draw(&mut img, cmd),
+                Err(ImageDrawError::OutOfBounds(..))
+            ));
+        }
+        // nothing of a command is drawn if a part of it is rejected
+        let chain = fill(0, 0, 4, 4, red.clone()) + fill(i32::MAX, 0, 2, 1, red);
+        assert!(draw(&mut img, &chain).is_err());
+        assert!(painted(&img).is_empty());
+    }
+
+    #[test]
+    fn drawing_is_clipped_at_the_image_edges() {
+        let mut img = Image::new(4, 3).unwrap();
+        let red = rgba(255, 0, 0, 255);
+        draw(&mut img, &fill(-2, -1, 4, 3, red.clone())).unwrap();
+        assert_eq!(painted(&img), [(0, 0), (1, 0), (0, 1), (1, 1)]);
+
+        let mut img = Image::new(4, 3).unwrap();
+        let outside = fill(3, 2, 10, 10, red.clone())
+            + fill(-10, 0, 5, 5, red.clone())
+            + fill(10, 10, 5, 5, red.clone())
+            + DrawCommand::Pixel(-1, 0, red.clone())
+            + DrawCommand::Pixel(4, 0, red);
+        draw(&mut img, &outside).unwrap();
+        assert_eq!(painted(&img), [(3, 2)]);
+    }
+
+    #[test]
+    fn scopes_move_the_origin_and_narrow_the_clip_region() {
+        let mut img = Image::new(5, 4).unwrap();
+        let red = rgba(255, 0, 0, 255);
+        let cmd = fill(-5, -5, 20, 20, red.clone())
+            .clipped(Rect::new(0, 0, 2, 1))
+            .translated(1, 2);
+        draw(&mut img, &cmd).unwrap();
+        assert_eq!(painted(&img), [(1, 2), (2, 2)]);
+
+        // nested clips intersect, translations add up
+        let mut img = Image::new(5, 4).unwrap();
+        let cmd = fill(0, 0, 1, 1, red.clone())
+            .translated(1, 1)
+            .clipped(Rect::new(0, 0, 3, 3))
+            .translated(1, 1)
+            .clipped(Rect::new(0, 0, 10, 10));
+        draw(&mut img, &cmd).unwrap();
+        assert_eq!(painted(&img), [(2, 2)]);
+        let mut img = Image::new(5, 4).unwrap();
+        let cmd = fill(0, 0, 5, 5, red)
+            .clipped(Rect::new(2, 0, 3, 3))
+            .clipped(Rect::new(0, 1, 3, 3));
+        draw(&mut img, &cmd).unwrap();
+        assert_eq!(painted(&img), [(2, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn translucent_colors_are_composited_source_over() {
+        let channels = |c: &ColorRgba32| (c.r, c.g, c.b, c.a);
+        let mut dst = rgba(255, 255, 255, 255);
+        blend(&mut dst, &rgba(255, 0, 0, 128), 255);
+        assert_eq!(channels(&dst), (255, 127, 127, 255));
+
+        // transparent pixels take the color, alpha is not premultiplied in storage
+        let mut dst = rgba(0, 0, 0, 0);
+        blend(&mut dst, &rgba(255, 0, 0, 128), 255);
+        assert_eq!(channels(&dst), (255, 0, 0, 128));
+
+        let mut dst = rgba(0, 0, 255, 128);
+        blend(&mut dst, &rgba(255, 0, 0, 128), 255);
+        assert_eq!(channels(&dst), (170, 0, 85, 192));
+
+        // the coverage scales the alpha of the source
+        let mut dst = rgba(0, 0, 255, 128);
+        blend(&mut dst, &rgba(255, 0, 0, 255), 128);
+        assert_eq!(channels(&dst), (170, 0, 85, 192));
+        blend(&mut dst, &rgba(255, 255, 255, 255), 0);
+        assert_eq!(channels(&dst), (170, 0, 85, 192));
+        blend(&mut dst, &rgba(0, 255, 0, 255), 255);
+        assert_eq!(channels(&dst), (0, 255, 0, 255));
+    }
+
+    #[test]
+    fn translucent_rectangles_blend_instead_of_replacing() {
+        let mut img = Image::new(2, 1).unwrap();
+        let cmd = DrawCommand::Clear(rgba(255, 255, 255, 255))
+            + fill(0, 0, 1, 1, rgba(255, 0, 0, 128))
+            + fill(1, 0, 1, 1, rgba(255, 0, 0, 0));
+        draw(&mut img, &cmd).unwrap();
+        let pixel = |x| img.get_pixel_at(x, 0).unwrap().clone();
+        assert_eq!((pixel(0).g, pixel(0).a), (127, 255));
+        assert_eq!((pixel(1).r, pixel(1).g, pixel(1).b), (255, 255, 255));
+    }
+
+    /// The alpha channel of every pixel, row by row
+    fn alphas(img: &Image<ColorRgba32>) -> Vec<Vec<u8>> {
+        let (w, h) = img.res;
+        (0..h)
+            .map(|y| (0..w).map(|x| img.get_pixel_at(x, y).unwrap().a).collect())
+            .collect()
+    }
+
+    fn line(x: i32, y: i32, w: i32, h: i32, width: u32) -> DrawCommand<ColorRgba32> {
+        DrawCommand::Line(
+            Line::new(x, y, w, h),
+            LineInfo::new(width, rgba(0, 0, 0, 255)),
+        )
+    }
+
+    #[test]
+    fn straight_lines_cover_whole_pixels() {
+        let mut img = Image::new(6, 3).unwrap();
+        draw(&mut img, &line(1, 1, 3, 0, 1)).unwrap();
+        assert_eq!(alphas(&img), [[0; 6], [0, 255, 255, 255, 255, 0], [0; 6]]);
+        // strokes of an even width lie below the pixels they start at
+        let mut img = Image::new(4, 5).unwrap();
+        draw(&mut img, &line(1, 1, 0, 1, 2)).unwrap();
+        let covered: Vec<usize> = alphas(&img)
+            .iter()
+            .map(|row| row.iter().filter(|&&a| a == 255).count())
+            .collect();
+        assert_eq!(covered, [0, 2, 2, 2, 0]);
+        assert!(alphas(&img).iter().flatten().all(|&a| a == 0 || a == 255));
+    }
+
+    #[test]
+    fn diagonal_lines_are_antialiased() {
+        let mut img = Image::new(5, 5).unwrap();
+        draw(&mut img, &line(0, 0, 4, 4, 1)).unwrap();
+        let a = alphas(&img);
+        assert!(a.iter().enumerate().all(|(i, row)| row[i] == 255));
+        // the neighbors of the diagonal are partially covered, symmetrically
+        assert!(a[1][2] > 0 && a[1][2] < 255);
+        assert_eq!(a[1][2], a[2][1]);
+        assert_eq!((a[0][3], a[4][0]), (0, 0));
+    }
+
+    #[test]
+    fn lines_are_composited_over_the_image() {
+        let mut img = Image::new(3, 1).unwrap();
+        let cmd = DrawCommand::Clear(rgba(255, 0, 0, 255))
+            + DrawCommand::Line(
+                Line::new(0, 0, 2, 0),
+                LineInfo::new(1, rgba(0, 0, 255, 128)),
+            );
+        draw(&mut img, &cmd).unwrap();
+        let c = img.get_pixel_at(1, 0).unwrap();
+        assert_eq!((c.r, c.g, c.b, c.a), (127, 0, 128, 255));
+    }
+
+    fn black() -> Paint<ColorRgba32> {
+        rgba(0, 0, 0, 255).into()
+    }
+
+    /// Whether an image looks the same mirrored horizontally and vertically
+    fn is_symmetric(a: &[Vec<u8>]) -> bool {
+        let mirrored: Vec<Vec<u8>> = a
+            .iter()
+            .rev()
+            .map(|row| row.iter().rev().copied().collect())
+            .collect();
+        a == &mirrored[..]
+    }
+
+    #[test]
+    fn circles_fill_the_square_around_their_center() {
+        let mut img = Image::new(10, 10).unwrap();
+        draw(
+            &mut img,
+            &DrawCommand::FilledCircle(Circle::new(5, 5, 4), black()),
+        )
+        .unwrap();
+        let a = alphas(&img);
+        assert!(is_symmetric(&a));
+        assert_eq!((a[4][4], a[5][2], a[2][5]), (255, 255, 255));
+        // the corners of the square stay empty, the edge is antialiased
+        assert_eq!((a[1][1], a[0][5], a[5][9]), (0, 0, 0));
+        assert!(a[2][2] > 0 && a[2][2] < 255 && a[5][1] < 255);
+
+        let mut img = Image::new(10, 10).unwrap();
+        let outline =
+            DrawCommand::CircleOutline(Circle::new(5, 5, 4), LineInfo::new(1, rgba(0, 0, 0, 255)));
+        draw(&mut img, &outline).unwrap();
+        let a = alphas(&img);
+        assert!(is_symmetric(&a));
+        assert!(a[5][1] > 0 && a[4][4] == 0);
+    }
+
+    #[test]
+    fn rounded_rectangles_scale_down_oversized_radii() {
+        let rounded = |r| {
+            let mut img = Image::new(10, 6).unwrap();
+            let cmd =
+                DrawCommand::FilledRoundedRect(Rect::new(0, 0, 10, 6), Radii::all(r), black());
+            draw(&mut img, &cmd).unwrap();
+            alphas(&img)
+        };
+        let a = rounded(3);
+        assert!(is_symmetric(&a));
+        assert_eq!((a[0][0], a[0][5], a[3][3], a[3][5]), (0, 255, 255, 255));
+        assert!(a[0][1] > 0 && a[0][1] < 255);
+        // the radii of all corners are shrunk by the same factor until they fit
+        assert_eq!(rounded(100), a);
+        assert!(rounded(0).iter().flatten().all(|&a| a == 255));
+    }
+
+    /// Whether the top left, top right, bottom right and bottom left quarter are painted
+    fn quarters(a: &[Vec<u8>]) -> [bool; 4] {
+        let (w, h) = (a[0].len(), a.len());
+        let painted = |xs: std::ops::Range<usize>, ys: std::ops::Range<usize>| {
+            ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+                .any(|(x, y)| a[y][x] > 0)
+        };
+        [
+            painted(0..w / 2, 0..h / 2),
+            painted(w / 2..w, 0..h / 2),
+            painted(w / 2..w, h / 2..h),
+            painted(0..w / 2, h / 2..h),
+        ]
+    }
+
+    #[test]
+    fn arcs_only_cover_their_sweep() {
+        let arc = |start, sweep| {
+            let mut img = Image::new(10, 10).unwrap();
+            let info = LineInfo::new(2, rgba(0, 0, 0, 255));
+            let arc = CircleArc::new(Circle::new(5, 5, 5), start, sweep);
+            draw(&mut img, &DrawCommand::Arc(arc, info)).unwrap();
+            alphas(&img)
+        };
+        // clockwise from 3 o'clock to 6 o'clock is the bottom right quarter
+        let a = arc(0.0, 90.0);
+        assert_eq!(quarters(&a), [false, false, true, false]);
+        assert_eq!((a[7][7], a[5][5]), (255, 0));
+        assert_eq!(arc(90.0, -90.0), a);
+        // arcs longer than a half circle are the union of both half planes
+        assert_eq!(quarters(&arc(0.0, 270.0)), [true, false, true, true]);
+        assert_eq!(quarters(&arc(0.0, 0.0)), [false; 4]);
+        assert_eq!(arc(45.0, 360.0), arc(0.0, 720.0));
+        assert!(is_symmetric(&arc(0.0, 360.0)));
+    }
+
+    #[test]
+    fn clip_regions_cut_every_kind_of_command() {
+        let red = rgba(255, 0, 0, 255);
+        let clip = Rect::new(0, 0, 5, 10);
+        let mut img = Image::new(10, 10).unwrap();
+        let circle = DrawCommand::FilledCircle(Circle::new(5, 5, 4), black()).clipped(clip);
+        draw(&mut img, &circle).unwrap();
+        // the left half is drawn like without the clip region
+        let mut full = Image::new(10, 10).unwrap();
+        draw(
+            &mut full,
+            &DrawCommand::FilledCircle(Circle::new(5, 5, 4), black()),
+        )
+        .unwrap();
+        let (a, b) = (alphas(&img), alphas(&full));
+        for (row, full) in a.iter().zip(b.iter()) {
+            assert_eq!(row[..5], full[..5]);
+            assert!(row[5..].iter().all(|&a| a == 0));
+        }
+
+        // clearing only affects the clip region
+        let mut img = Image::new(4, 2).unwrap();
+        draw(
+            &mut img,
+            &DrawCommand::Clear(red.clone()).clipped(Rect::new(1, 1, 2, 5)),
+        )
+        .unwrap();
+        assert_eq!(painted(&img), [(1, 1), (2, 1)]);
+
+        // empty clip regions and ones beyond the image hide everything
+        let mut img = Image::new(4, 2).unwrap();
+        let hidden = DrawCommand::Clear(red.clone()).clipped(Rect::new(1, 1, 0, 5))
+            + DrawCommand::Clear(red.clone()).clipped(Rect::new(4, 0, 5, 5))
+            + DrawCommand::Clear(red.clone()).clipped(Rect::new(-5, 0, 5, 5))
+            + fill(0, 0, 4, 2, red)
+                .clipped(Rect::new(0, 0, 2, 2))
+                .clipped(Rect::new(2, 0, 2, 2));
+        draw(&mut img, &hidden).unwrap();
+        assert!(painted(&img).is_empty());
+    }
+
+    #[test]
+    fn images_are_placed_in_local_coordinates() {
+        // a 3x2 image with distinct red channels
+        let data = (0..6).map(|i| rgba(i * 10 + 10, 0, 0, 255)).collect();
+        let image = Arc::new(Image::from_data(3, 2, data).unwrap());
+        let mut img = Image::new(6, 4).unwrap();
+        let cmd = DrawCommand::Image(Blit::new(-1, 0, image))
+            .clipped(Rect::new(0, 0, 1, 2))
+            .translated(2, 1);
+        draw(&mut img, &cmd).unwrap();
+        assert_eq!(painted(&img), [(2, 1), (2, 2)]);
+        let red = |x, y| img.get_pixel_at(x, y).unwrap().r;
+        // the second column of the image lands on the local origin
+        assert_eq!((red(2, 1), red(2, 2)), (20, 50));
+    }
+}
@@ -0,0 +1,269 @@
+//! Evaluation of [`Paint`]s at pixel positions
+
+use super::super::color::Color;
+use super::super::draw::{GradientStop, Paint};
+
+/// A premultiplied color with channels from `0.0` to `1.0`
+type Premultiplied = [f64; 4];
+
+fn premultiply<C: Color>(c: &C) -> Premultiplied {
+    let a = f64::from(c.a8()) / 255.0;
+    [
+        f64::from(c.r8()) / 255.0 * a,
+        f64::from(c.g8()) / 255.0 * a,
+        f64::from(c.b8()) / 255.0 * a,
+        a,
+    ]
+}
+
+fn unpremultiply<C: Color>([r, g, b, a]: Premultiplied) -> C {
+    let channel = |c: f64| {
+        if a > 0.0 {
+            (c / a * 255.0).round().clamp(0.0, 255.0) as u8
+        } else {
+            0
+        }
+    };
+    C::from_rgba8(
+        channel(r),
+        channel(g),
+        channel(b),
+        (a * 255.0).round() as u8,
+    )
+}
+
+/// Gradient stops prepared for interpolation
+pub struct Stops(Vec<(f64, Premultiplied)>);
+
+impl Stops {
+    /// Offsets smaller than a previous offset are moved to the previous one like in CSS
+    fn new<C: Color>(stops: &[GradientStop<C>]) -> Self {
+        let mut last = 0.0f64;
+        Self(
+            stops
+                .iter()
+                .map(|stop| {
+                    let offset = f64::from(stop.offset);
+                    last = if offset.is_finite() {
+                        last.max(offset)
+                    } else {
+                        last
+                    };
+                    (last, premultiply(&stop.color))
+                })
+                .collect(),
+        )
+    }
+
+    /// Interpolates the premultiplied colors of the stops around `t`,
+    /// so that fading into transparency does not darken the colors
+    fn at(&self, t: f64) -> Premultiplied {
+        let stops = &self.0;
+        let next = stops.iter().position(|(offset, _)| *offset > t);
+        match next {
+            None => stops.last().map_or([0.0; 4], |s| s.1),
+            Some(0) => stops[0].1,
+            Some(i) => {
+                let ((o0, c0), (o1, c1)) = (stops[i - 1], stops[i]);
+                let f = (t - o0) / (o1 - o0);
+                [0, 1, 2, 3].map(|n| c0[n] + (c1[n] - c0[n]) * f)
+            }
+        }
+    }
+}
+
+/// The gradient parameter of a position
+pub enum Geometry {
+    /// `t` is the projection onto the gradient line from `(x, y)` in direction `(dx, dy)`,
+    /// which is scaled by the inverse of the line's length
+    Linear { x: f64, y: f64, dx: f64, dy: f64 },
+    /// `t` is the distance to `(x, y)` divided by `radius`
+    Radial { x: f64, y: f64, radius: f64 },
+}
+
+/// Computes the color of a [`Paint`] for every pixel of a shape
+pub enum Sampler<C: Color> {
+    Solid(C),
+    Gradient(Geometry, Stops),
+}
+
+impl<C: Color> Sampler<C> {
+    /// Prepares a paint spanning the bounding box `(x0, y0, x1, y1)`
+    pub fn new(paint: &Paint<C>, (x0, y0, x1, y1): (f64, f64, f64, f64)) -> Self {
+        let (cx, cy) = ((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+        let (w, h) = (x1 - x0, y1 - y0);
+        match paint {
+            Paint::Solid(color) => Self::Solid(color.clone()),
+            Paint::Linear(gradient) => {
+                let angle = f64::from(gradient.angle);
+                let angle = if angle.is_finite() { angle } else { 0.0 };
+                let (sin, cos) = angle.to_radians().sin_cos();
+                // the length for the corners to lie on the lines of the first and last stop
+                let len = (w * cos).abs() + (h * sin).abs();
+                let scale = if len > 0.0 { 1.0 / len } else { 0.0 };
+                Self::Gradient(
+                    Geometry::Linear {
+                        x: cx - cos * len / 2.0,
+                        y: cy - sin * len / 2.0,
+                        dx: cos * scale,
+                        dy: sin * scale,
+                    },
+                    Stops::new(&gradient.stops),
+                )
+            }
+            Paint::Radial(gradient) => {
+                let x = x0 + w * f64::from(gradient.x);
+                let y = y0 + h * f64::from(gradient.y);
+                let corner = (x - x0)
+                    .abs()
+                    .max((x1 - x).abs())
+                    .hypot((y - y0).abs().max((y1 - y).abs()));
+                Self::Gradient(
+                    Geometry::Radial {
+                        x,
+                        y,
+                        radius: corner * f64::from(gradient.radius),
+                    },
+                    Stops::new(&gradient.stops),
+                )
+            }
+        }
+    }
+
+    /// A single color, e.g. for strokes
+    pub fn solid(color: &C) -> Self {
+        Self::Solid(color.clone())
+    }
+
+    /// The color at a position, usually the center of a pixel
+    pub fn at(&self, px: f64, py: f64) -> C {
+        let (geometry, stops) = match self {
+            Self::Solid(color) => return color.clone(),
+            Self::Gradient(geometry, stops) => (geometry, stops),
+        };
+        let t = match *geometry {
+            Geometry::Linear { x, y, dx, dy } => (px - x) * dx + (py - y) * dy,
+            Geometry::Radial { x, y, radius } if radius > 0.0 => (px - x).hypot(py - y) / radius,
+            // a degenerate gradient shows its last stop
+            Geometry::Radial { .. } => 1.0,
+        };
+        unpremultiply(stops.at(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::color::ColorRgba32;
+    use super::super::super::draw::{LinearGradient, RadialGradient};
+    use super::*;
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> ColorRgba32 {
+        ColorRgba32::from_rgba8(r, g, b, a)
+    }
+
+    fn channels(c: ColorRgba32) -> (u8, u8, u8, u8) {
+        (c.r, c.g, c.b, c.a)
+    }
+
+    fn linear(angle: f32, stops: Vec<GradientStop<ColorRgba32>>) -> Sampler<ColorRgba32> {
+        Sampler::new(
+            &Paint::Linear(LinearGradient::new(angle, stops)),
+            (0.0, 0.0, 10.0, 10.0),
+        )
+    }
+
+    fn black_to_white(angle: f32) -> Sampler<ColorRgba32> {
+        linear(
+            angle,
+            vec![
+                GradientStop::new(0.0, rgba(0, 0, 0, 255)),
+                GradientStop::new(1.0, rgba(255, 255, 255, 255)),
+            ],
+        )
+    }
+
+    #[test]
+    fn linear_gradients_span_the_bounding_box() {
+        let gray = |sampler: &Sampler<ColorRgba32>, x, y| sampler.at(x, y).r;
+        let horizontal = black_to_white(0.0);
+        assert_eq!(
+            (gray(&horizontal, 0.0, 3.0), gray(&horizontal, 5.0, 7.0)),
+            (0, 128)
+        );
+        assert_eq!(gray(&horizontal, 10.0, 0.0), 255);
+        let vertical = black_to_white(90.0);
+        assert_eq!(
+            (gray(&vertical, 3.0, 0.0), gray(&vertical, 3.0, 10.0)),
+            (0, 255)
+        );
+        // the corners lie on the first and last stop
+        let diagonal = black_to_white(45.0);
+        assert_eq!(
+            (gray(&diagonal, 0.0, 0.0), gray(&diagonal, 10.0, 10.0)),
+            (0, 255)
+        );
+        assert_eq!(gray(&diagonal, 10.0, 0.0), gray(&diagonal, 0.0, 10.0));
+        // positions beyond the ends take the color of the nearest stop
+        assert_eq!(
+            (gray(&horizontal, -5.0, 0.0), gray(&horizontal, 15.0, 0.0)),
+            (0, 255)
+        );
+    }
+
+    #[test]
+    fn stops_are_ordered_like_in_css() {
+        let red = rgba(255, 0, 0, 255);
+        let blue = rgba(0, 0, 255, 255);
+        let sampler = linear(
+            0.0,
+            vec![
+                GradientStop::new(0.5, red.clone()),
+                GradientStop::new(0.2, blue.clone()),
+            ],
+        );
+        // the second stop moves to the first one, which gives a hard edge
+        assert_eq!(channels(sampler.at(4.9, 0.0)), channels(red));
+        assert_eq!(channels(sampler.at(5.1, 0.0)), channels(blue));
+    }
+
+    #[test]
+    fn fading_out_does_not_darken() {
+        let sampler = linear(
+            0.0,
+            vec![
+                GradientStop::new(0.0, rgba(255, 0, 0, 255)),
+                GradientStop::new(1.0, rgba(0, 0, 0, 0)),
+            ],
+        );
+        assert_eq!(channels(sampler.at(5.0, 0.0)), (255, 0, 0, 128));
+        assert_eq!(sampler.at(10.0, 0.0).a, 0);
+    }
+
+    #[test]
+    fn radial_gradients_reach_the_farthest_corner() {
+        let stops = vec![
+            GradientStop::new(0.0, rgba(0, 0, 0, 255)),
+            GradientStop::new(1.0, rgba(255, 255, 255, 255)),
+        ];
+        let paint = Paint::Radial(RadialGradient::centered(stops.clone()));
+        let sampler = Sampler::new(&paint, (0.0, 0.0, 10.0, 20.0));
+        let gray = |x, y| sampler.at(x, y).r;
+        assert_eq!(
+            (gray(5.0, 10.0), gray(0.0, 0.0), gray(10.0, 20.0)),
+            (0, 255, 255)
+        );
+        assert_eq!(gray(0.0, 10.0), gray(10.0, 10.0));
+        assert!(gray(0.0, 10.0) < gray(5.0, 0.0));
+
+        // a gradient around the top left corner
+        let paint = Paint::Radial(RadialGradient::new(0.0, 0.0, 0.5, stops.clone()));
+        let sampler = Sampler::new(&paint, (0.0, 0.0, 6.0, 8.0));
+        assert_eq!((sampler.at(0.0, 0.0).r, sampler.at(3.0, 4.0).r), (0, 255));
+        // without a radius everything shows the last stop
+        let paint = Paint::Radial(RadialGradient::new(0.5, 0.5, 0.0, stops));
+        assert_eq!(
+            Sampler::new(&paint, (0.0, 0.0, 6.0, 8.0)).at(3.0, 4.0).r,
+            255
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Flattening of paths into polygons and widening of their outlines into strokes
+
+use super::super::draw::{Path, PathSegment};
+use std::f64::consts::PI;
+
+/// The largest distance of a flattened curve to the real one in pixels
+const TOLERANCE: f64 = 0.1;
+/// The most lines a single curve is split into
+const MAX_STEPS: f64 = 256.0;
+
+type Point = (f64, f64);
+
+fn point((x, y): (f32, f32)) -> Point {
+    (f64::from(x), f64::from(y))
+}
+
+/// The length of the vector `a - 2b + c`, which bounds how far a curve bends
+fn bend(a: Point, b: Point, c: Point) -> f64 {
+    (a.0 - 2.0 * b.0 + c.0).hypot(a.1 - 2.0 * b.1 + c.1)
+}
+
+/// How many lines approximate a curve bending by `deviation` within the tolerance
+fn steps(deviation: f64) -> usize {
+    (deviation / TOLERANCE).sqrt().ceil().clamp(1.0, MAX_STEPS) as usize
+}
+
+/// Appends the points of a curve after its start, `at` evaluates the curve from `0.0` to `1.0`
+fn push_curve<F: Fn(f64) -> Point>(points: &mut Vec<Point>, steps: usize, at: F) {
+    points.extend((1..=steps).map(|i| at(i as f64 / steps as f64)));
+}
+
+/// A subpath flattened to straight lines
+struct Subpath {
+    points: Vec<Point>,
+    closed: bool,
+}
+
+/// Splits a path into subpaths of straight lines
+fn subpaths(path: &Path) -> Vec<Subpath> {
+    let mut subpaths = Vec::new();
+    let mut current = Subpath {
+        points: vec![(0.0, 0.0)],
+        closed: false,
+    };
+    for segment in &path.segments {
+        let last = *current.points.last().unwrap_or(&(0.0, 0.0));
+        match *segment {
+            PathSegment::MoveTo(x, y) => {
+                let start = point((x, y));
+                let next = Subpath {
+                    points: vec![start],
+                    closed: false,
+                };
+                subpaths.push(std::mem::replace(&mut current, next));
+            }
+            PathSegment::LineTo(x, y) => current.points.push(point((x, y))),
+            PathSegment::QuadTo(c, p) => {
+                let (c, p) = (point(c), point(p));
+                let n = steps(bend(last, c, p) / 4.0);
+                push_curve(&mut current.points, n, |t| {
+                    let u = 1.0 - t;
+                    let (a, b, d) = (u * u, 2.0 * u * t, t * t);
+                    (
+                        a * last.0 + b * c.0 + d * p.0,
+                        a * last.1 + b * c.1 + d * p.1,
+                    )
+                });
+            }
+            PathSegment::CubicTo(c1, c2, p) => {
+                let (c1, c2, p) = (point(c1), point(c2), point(p));
+                let n = steps(bend(last, c1, c2).max(bend(c1, c2, p)) * 3.0 / 4.0);
+                push_curve(&mut current.points, n, |t| {
+                    let u = 1.0 - t;
+                    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                    (
+                        a * last.0 + b * c1.0 + c * c2.0 + d * p.0,
+                        a * last.1 + b * c1.1 + c * c2.1 + d * p.1,
+                    )
+                });
+            }
+            PathSegment::Close => {
+                // the next subpath starts where this one started
+                let start = current.points[0];
+                current.closed = true;
+                let next = Subpath {
+                    points: vec![start],
+                    closed: false,
+                };
+                subpaths.push(std::mem::replace(&mut current, next));
+            }
+        }
+    }
+    subpaths.push(current);
+    // subpaths without a segment draw nothing
+    subpaths.retain(|subpath| subpath.points.len() > 1);
+    subpaths
+}
+
+/// Flattens a path into contours for filling
+pub fn flatten(path: &Path) -> Vec<Vec<Point>> {
+    subpaths(path)
+        .into_iter()
+        .map(|subpath| subpath.points)
+        .collect()
+}
+
+/// A circle as a polygon, in the same direction as the widened segments of [`stroke`]
+fn disc((cx, cy): Point, r: f64) -> Vec<Point> {
+    let n = (r * 4.0).ceil().clamp(8.0, 128.0) as usize;
+    (0..n)
+        .map(|i| {
+            let angle = -2.0 * PI * i as f64 / n as f64;
+            (cx + r * angle.cos(), cy + r * angle.sin())
+        })
+        .collect()
+}
+
+/// Widens the outlines of a path to contours filling its stroke by the non-zero rule.
+/// All contours run in the same direction, so that overlapping parts are covered only once.
+pub fn stroke(path: &Path, width: f64) -> Vec<Vec<Point>> {
+    let r = width / 2.0;
+    let mut contours = Vec::new();
+    for mut subpath in subpaths(path) {
+        subpath.points.dedup();
+        if subpath.closed && subpath.points.len() > 1 {
+            let start = subpath.points[0];
+            subpath.points.push(start);
+        }
+        for pair in subpath.points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            let len = (x1 - x0).hypot(y1 - y0);
+            let (nx, ny) = (-(y1 - y0) / len * r, (x1 - x0) / len * r);
+            contours.push(vec![
+                (x0 + nx, y0 + ny),
+                (x1 + nx, y1 + ny),
+                (x1 - nx, y1 - ny),
+                (x0 - nx, y0 - ny),
+            ]);
+        }
+        // round joins and ends, a segment of length zero becomes a dot
+        contours.extend(subpath.points.iter().map(|&p| disc(p, r)));
+    }
+    contours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::color::{Color, ColorRgba32};
+    use super::super::super::draw::{DrawCommand, FillRule, LineInfo};
+    use super::super::super::Image;
+    use super::super::draw;
+    use super::*;
+
+    /// The distance of a point to the line segment from `a` to `b`
+    fn distance(p: Point, a: Point, b: Point) -> f64 {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len = dx * dx + dy * dy;
+        let t = if len > 0.0 {
+            (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+    }
+
+    #[test]
+    fn curves_are_flattened_within_the_tolerance() {
+        let path = Path::new()
+            .move_to(0.0, 0.0)
+            .quad_to(10.0, 20.0, 20.0, 0.0)
+            .cubic_to(30.0, -20.0, 40.0, 20.0, 50.0, 0.0);
+        let contours = flatten(&path);
+        assert_eq!(contours.len(), 1);
+        let points = &contours[0];
+        assert_eq!(points[0], (0.0, 0.0));
+        assert_eq!(points[points.len() - 1], (50.0, 0.0));
+        assert!(points.contains(&(20.0, 0.0)));
+
+        let quad = |t: f64| (20.0 * t, 40.0 * t * (1.0 - t));
+        let cubic = |t: f64| {
+            let u = 1.0 - t;
+            (20.0 + 30.0 * t, -60.0 * u * u * t + 60.0 * u * t * t)
+        };
+        for i in 0..=1000 {
+            let t = f64::from(i) / 1000.0;
+            for p in [quad(t), cubic(t)].iter() {
+                let nearest = points
+                    .windows(2)
+                    .map(|pair| distance(*p, pair[0], pair[1]))
+                    .fold(f64::INFINITY, f64::min);
+                assert!(nearest <= TOLERANCE, "{:?} is {} away", p, nearest);
+            }
+        }
+        // straight curves stay a single line, huge ones are limited
+        let straight = flatten(&Path::new().quad_to(1.0, 1.0, 2.0, 2.0));
+        assert_eq!(straight, [vec![(0.0, 0.0), (2.0, 2.0)]]);
+        let huge = flatten(&Path::new().quad_to(1e7, 1e7, 0.0, 1.0));
+        assert_eq!(huge[0].len(), MAX_STEPS as usize + 1);
+    }
+
+    #[test]
+    fn subpaths_start_at_moves_and_closes() {
+        let path = Path::new()
+            .line_to(1.0, 0.0)
+            .move_to(5.0, 5.0)
+            .line_to(6.0, 5.0)
+            .line_to(6.0, 6.0)
+            .close()
+            .line_to(5.0, 7.0)
+            .move_to(9.0, 9.0);
+        assert_eq!(
+            flatten(&path),
+            [
+                vec![(0.0, 0.0), (1.0, 0.0)],
+                vec![(5.0, 5.0), (6.0, 5.0), (6.0, 6.0)],
+                vec![(5.0, 5.0), (5.0, 7.0)],
+            ]
+        );
+        assert!(flatten(&Path::new()).is_empty());
+        assert!(flatten(&Path::new().move_to(1.0, 1.0).close()).is_empty());
+        assert!(stroke(&Path::new().move_to(1.0, 1.0), 2.0).is_empty());
+    }
+
+    #[test]
+    fn strokes_widen_segments_and_round_their_joints() {
+        let contours = stroke(&Path::polyline(vec![(1.0, 1.0), (5.0, 1.0)]), 2.0);
+        // one rectangle around the segment and a dot at each end
+        assert_eq!(contours.len(), 3);
+        assert_eq!(
+            contours[0],
+            [(1.0, 2.0), (5.0, 2.0), (5.0, 0.0), (1.0, 0.0)]
+        );
+        for (dot, center) in contours[1..].iter().zip([(1.0, 1.0), (5.0, 1.0)].iter()) {
+            assert!(dot
+                .iter()
+                .all(|p| ((p.0 - center.0).hypot(p.1 - center.1) - 1.0).abs() < 1e-9));
+        }
+        // closed subpaths get a segment back to their start
+        let square = Path::polyline(vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)]).close();
+        assert_eq!(stroke(&square, 1.0).len(), 3 + 4);
+        // a segment of length zero is a dot
+        assert_eq!(
+            stroke(&Path::polyline(vec![(2.0, 2.0), (2.0, 2.0)]), 2.0).len(),
+            1
+        );
+    }
+
+    /// The alpha channel of every pixel after drawing a path command
+    fn alphas(w: u64, h: u64, cmd: DrawCommand<ColorRgba32>) -> Vec<Vec<u8>> {
+        let mut img = Image::new(w, h).unwrap();
+        draw(&mut img, &cmd).unwrap();
+        (0..h)
+            .map(|y| (0..w).map(|x| img.get_pixel_at(x, y).unwrap().a).collect())
+            .collect()
+    }
+
+    #[test]
+    fn fill_rules_decide_about_holes() {
+        let outer = Path::polyline(vec![(0.0, 0.0), (6.0, 0.0), (6.0, 6.0), (0.0, 6.0)]).close();
+        let inner = Path::polyline(vec![(2.0, 2.0), (4.0, 2.0), (4.0, 4.0), (2.0, 4.0)]).close();
+        let reversed = Path::polyline(vec![(2.0, 2.0), (2.0, 4.0), (4.0, 4.0), (4.0, 2.0)]).close();
+        let fill = |inner: &Path, rule| {
+            let path = outer.clone().append(inner.clone());
+            let black = ColorRgba32::from_rgba8(0, 0, 0, 255);
+            let a = alphas(6, 6, DrawCommand::FilledPath(path, rule, black.into()));
+            // the hole and the ring
+            (a[2][2], a[3][3], a[0][0], a[5][5])
+        };
+        assert_eq!(fill(&inner, FillRule::NonZero), (255, 255, 255, 255));
+        assert_eq!(fill(&inner, FillRule::EvenOdd), (0, 0, 255, 255));
+        assert_eq!(fill(&reversed, FillRule::NonZero), (0, 0, 255, 255));
+        assert_eq!(fill(&reversed, FillRule::EvenOdd), (0, 0, 255, 255));
+    }
+
+    #[test]
+    fn overlapping_parts_of_strokes_are_covered_once() {
+        let path = Path::polyline(vec![(1.5, 1.5), (6.5, 1.5), (6.5, 6.5), (1.5, 1.5)]);
+        let translucent = ColorRgba32::from_rgba8(0, 0, 0, 128);
+        let a = alphas(
+            8,
+            8,
+            DrawCommand::PathOutline(path, LineInfo::new(1, translucent)),
+        );
+        assert_eq!((a[1][3], a[4][6]), (128, 128));
+        // the joints are partially covered by several contours, but blended only once
+        assert!(a[1][6] > 0 && a[1][1] > 0 && a[4][4] > 0);
+        assert!(a.iter().flatten().all(|&a| a <= 128));
+    }
+}
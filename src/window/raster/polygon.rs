@@ -0,0 +1,216 @@
+//! Scanline filling of shapes bounded by straight edges
+
+use super::super::color::Color;
+use super::super::draw::FillRule;
+use super::paint::Sampler;
+use super::{blend, Canvas};
+
+/// The number of scanlines sampled per pixel row,
+/// each of them contributes the exact horizontal coverage of its spans
+const SCANLINES: usize = 16;
+
+/// An edge from `(x0, y0)` down to `(x1, y1)`
+struct Edge {
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+    /// `1` if the contour goes downwards along the edge, `-1` if it goes upwards
+    winding: i32,
+}
+
+impl Edge {
+    /// Horizontal edges are never crossed by a scanline and result in `None`
+    fn new((xa, ya): (f64, f64), (xb, yb): (f64, f64)) -> Option<Self> {
+        if ya < yb {
+            Some(Self {
+                x0: xa,
+                y0: ya,
+                x1: xb,
+                y1: yb,
+                winding: 1,
+            })
+        } else if ya > yb {
+            Some(Self {
+                x0: xb,
+                y0: yb,
+                x1: xa,
+                y1: ya,
+                winding: -1,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The x-coordinate where a scanline crosses the edge
+    fn x_at(&self, y: f64) -> f64 {
+        self.x0 + (y - self.y0) * (self.x1 - self.x0) / (self.y1 - self.y0)
+    }
+}
+
+/// The bounding box `(x0, y0, x1, y1)` of contours, `None` if they have no points
+pub fn extent(contours: &[Vec<(f64, f64)>]) -> Option<(f64, f64, f64, f64)> {
+    let mut points = contours.iter().flatten();
+    let &(x, y) = points.next()?;
+    Some(points.fold((x, y, x, y), |(x0, y0, x1, y1), &(x, y)| {
+        (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+    }))
+}
+
+/// Adds the coverage of the span `a..b` to a row of pixels starting at `0.0`
+fn add_span(row: &mut [f64], a: f64, b: f64) {
+    let len = row.len() as f64;
+    let (a, b) = (a.clamp(0.0, len), b.clamp(0.0, len));
+    if b <= a {
+        return;
+    }
+    let (first, last) = (a.floor() as usize, b.floor() as usize);
+    if first == last {
+        row[first] += b - a;
+        return;
+    }
+    row[first] += (first + 1) as f64 - a;
+    row[first + 1..last].iter_mut().for_each(|c| *c += 1.0);
+    if last < row.len() {
+        row[last] += b - last as f64;
+    }
+}
+
+/// Fills closed contours, the last point of each connects to its first
+pub fn fill<C: Color>(
+    img: &mut Canvas<C>,
+    contours: &[Vec<(f64, f64)>],
+    rule: FillRule,
+    paint: &Sampler<C>,
+) {
+    let edges: Vec<Edge> = contours
+        .iter()
+        .filter(|contour| !contour.is_empty())
+        .flat_map(|contour| {
+            let next = contour.iter().cycle().skip(1);
+            contour
+                .iter()
+                .zip(next)
+                .filter_map(|(&a, &b)| Edge::new(a, b))
+        })
+        .collect();
+    let (x0, y0, x1, y1) = match extent(contours) {
+        Some(extent) => extent,
+        None => return,
+    };
+    let bounds = (
+        x0.floor() as i64,
+        y0.floor() as i64,
+        x1.ceil() as i64,
+        y1.ceil() as i64,
+    );
+    let (dx0, dy0, dx1, dy1) = img.to_device(bounds);
+    if dx0 >= dx1 || dy0 >= dy1 {
+        return;
+    }
+    let (ox, oy) = img.origin;
+    let mut coverage = vec![0.0; (dx1 - dx0) as usize];
+    let mut crossings = Vec::new();
+    for row in dy0..dy1 {
+        coverage.iter_mut().for_each(|c| *c = 0.0);
+        for i in 0..SCANLINES {
+            let y = (row - oy) as f64 + (i as f64 + 0.5) / SCANLINES as f64;
+            crossings.clear();
+            crossings.extend(
+                edges
+                    .iter()
+                    .filter(|e| e.y0 <= y && y < e.y1)
+                    .map(|e| (e.x_at(y), e.winding)),
+            );
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = match rule {
+                    FillRule::NonZero => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if inside {
+                    // the span in columns relative to the first covered column
+                    let start = (ox - dx0) as f64;
+                    add_span(&mut coverage, start + pair[0].0, start + pair[1].0);
+                }
+            }
+        }
+        for (col, c) in (dx0..dx1).zip(&coverage) {
+            let c = (c / SCANLINES as f64).clamp(0.0, 1.0);
+            let c = (c * 255.0).round() as u8;
+            if c > 0 {
+                let (x, y) = ((col - ox) as f64 + 0.5, (row - oy) as f64 + 0.5);
+                blend(img.pixel(col, row), &paint.at(x, y), c);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::color::ColorRgba32;
+    use super::super::super::draw::{DrawCommand, Polygon};
+    use super::super::super::Image;
+    use super::super::draw;
+    use super::*;
+
+    /// The alpha channel of every pixel after filling a black polygon
+    fn fill_polygon(w: u64, h: u64, points: &[(f32, f32)]) -> Vec<Vec<u8>> {
+        let mut img = Image::new(w, h).unwrap();
+        let black = ColorRgba32::from_rgba8(0, 0, 0, 255);
+        let cmd = DrawCommand::FilledPolygon(Polygon::new(points.to_vec()), black.into());
+        draw(&mut img, &cmd).unwrap();
+        (0..h)
+            .map(|y| (0..w).map(|x| img.get_pixel_at(x, y).unwrap().a).collect())
+            .collect()
+    }
+
+    #[test]
+    fn edges_cover_the_area_they_cut_off() {
+        let a = fill_polygon(4, 2, &[(0.0, 0.0), (2.5, 0.0), (2.5, 2.0), (0.0, 2.0)]);
+        assert_eq!(a, [[255, 255, 128, 0], [255, 255, 128, 0]]);
+        // a diagonal halves the pixels it crosses
+        let a = fill_polygon(3, 3, &[(0.0, 0.0), (3.0, 0.0), (0.0, 3.0)]);
+        assert_eq!(a, [[255, 255, 128], [255, 128, 0], [128, 0, 0]]);
+        // the direction of the corners does not matter
+        let reversed = fill_polygon(3, 3, &[(0.0, 3.0), (3.0, 0.0), (0.0, 0.0)]);
+        assert_eq!(reversed, a);
+    }
+
+    #[test]
+    fn degenerate_and_hidden_polygons_draw_nothing() {
+        let empty = vec![vec![0; 4]; 4];
+        assert_eq!(fill_polygon(4, 4, &[]), empty);
+        assert_eq!(fill_polygon(4, 4, &[(1.0, 1.0)]), empty);
+        assert_eq!(fill_polygon(4, 4, &[(0.0, 0.0), (4.0, 4.0)]), empty);
+        assert_eq!(
+            fill_polygon(4, 4, &[(0.0, 0.0), (2.0, 2.0), (4.0, 4.0)]),
+            empty
+        );
+        assert_eq!(
+            fill_polygon(4, 4, &[(5.0, 0.0), (9.0, 0.0), (9.0, 4.0), (5.0, 4.0)]),
+            empty
+        );
+        assert_eq!(
+            fill_polygon(4, 4, &[(0.0, -5.0), (4.0, -5.0), (4.0, -1.0)]),
+            empty
+        );
+    }
+
+    #[test]
+    fn spans_are_clamped_to_the_row() {
+        let mut row = [0.0; 3];
+        add_span(&mut row, -1.0, 1.5);
+        add_span(&mut row, 2.25, 9.0);
+        add_span(&mut row, 2.0, 1.0);
+        assert_eq!(row, [1.0, 0.5, 0.75]);
+        assert_eq!(
+            extent(&[vec![], vec![(1.0, 2.0), (-1.0, 3.0)]]),
+            Some((-1.0, 2.0, 1.0, 3.0))
+        );
+        assert_eq!(extent(&[vec![]]), None);
+    }
+}
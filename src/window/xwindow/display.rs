@@ -1,8 +1,10 @@
+use super::super::{color::ColorRgba32, Image};
 use super::XError;
 
 pub struct Display {
     main_screen: i32,
     con: xcb::Connection,
+    local: bool,
 }
 
 impl Display {
@@ -25,6 +27,20 @@ impl Display {
         })
     }
 
+    /// Finds a visual by its id and returns it together with its depth
+    pub fn get_visual(&self, screen: usize, id: xcb::Visualid) -> Option<(u8, xcb::Visualtype)> {
+        self.get_screen(screen).and_then(|screen| {
+            screen
+                .allowed_depths()
+                .filter_map(|d| {
+                    d.visuals()
+                        .find(|v| v.visual_id() == id)
+                        .map(|v| (d.depth(), v))
+                })
+                .next()
+        })
+    }
+
     pub fn get_intern_atom(&self, name: &str) -> Result<Option<xcb::Atom>, XError> {
         match xcb::intern_atom(&self.con, true, name).get_reply()?.atom() {
             xcb::ATOM_NONE => Ok(None),
@@ -32,18 +48,56 @@ impl Display {
         }
     }
 
+    /// Fetches the icons a window provides in its `_NET_WM_ICON` property,
+    /// windows without icons result in an empty list
+    pub fn get_net_wm_icons(&self, window: xcb::Window) -> Result<Vec<Image<ColorRgba32>>, XError> {
+        let atom = match self.get_intern_atom("_NET_WM_ICON")? {
+            Some(atom) => atom,
+            None => return Ok(Vec::new()),
+        };
+        // the length is given in 32 bit units
+        let reply = xcb::get_property(
+            &self.con,
+            false,
+            window,
+            atom,
+            xcb::ATOM_CARDINAL,
+            0,
+            u32::MAX / 4,
+        )
+        .get_reply()?;
+        if reply.format() != 32 {
+            return Ok(Vec::new());
+        }
+        Ok(super::parse_net_wm_icon(reply.value::<u32>()))
+    }
+
     pub fn con(&self) -> &xcb::Connection {
         &self.con
     }
+
+    /// Whether the server runs on this machine, which is required to share memory with it
+    pub fn is_local(&self) -> bool {
+        self.local
+    }
+}
+
+/// Whether a display name like `:0` or `host:0` names a server on this machine.
+/// Displays without a host name (`:0`, `unix:0`) are reached through a local socket.
+fn is_local_display(name: &str) -> bool {
+    let host = name.rsplit_once(':').map_or("", |(host, _)| host);
+    host.is_empty() || host == "unix" || host.starts_with('/')
 }
 
 impl super::super::Display for Display {
     type Error = XError;
     fn new() -> Result<Self, Self::Error> {
         let (con, screen_count) = xcb::Connection::connect(None).map_err(XError::ConnError)?;
+        let local = std::env::var("DISPLAY").is_ok_and(|name| is_local_display(&name));
         Ok(Self {
             con,
             main_screen: screen_count,
+            local,
         })
     }
 
@@ -60,3 +114,18 @@ impl super::super::Display for Display {
         self.main_screen as usize
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_names_without_host_are_local() {
+        for name in [":0", ":1.0", "unix:0", "/tmp/.X11-unix/X0:0"].iter() {
+            assert!(is_local_display(name), "{}", name);
+        }
+        for name in ["localhost:0", "127.0.0.1:10.0", "remote.example:0"].iter() {
+            assert!(!is_local_display(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use super::super::color::ColorRgba32;
+use super::super::Image;
+
+/// Converts the value of a `_NET_WM_ICON` property into images.
+/// The property holds any number of icons, each stored as its width, its height
+/// and its pixels as non-premultiplied ARGB values. Truncated icons are dropped.
+pub fn parse_net_wm_icon(data: &[u32]) -> Vec<Image<ColorRgba32>> {
+    let mut icons = Vec::new();
+    let mut rest = data;
+    while let [w, h, pixels @ ..] = rest {
+        let len = match (*w as usize).checked_mul(*h as usize) {
+            Some(len) if len > 0 && len <= pixels.len() => len,
+            _ => break,
+        };
+        let data = pixels[..len]
+            .iter()
+            .map(|&argb| ColorRgba32 {
+                r: (argb >> 16) as u8,
+                g: (argb >> 8) as u8,
+                b: argb as u8,
+                a: (argb >> 24) as u8,
+            })
+            .collect();
+        icons.extend(Image::from_data((*w).into(), (*h).into(), data));
+        rest = &pixels[len..];
+    }
+    icons
+}
+
+/// Picks the icon best suited to be drawn at `size` pixels:
+/// the smallest one at least as large, otherwise the largest one
+pub fn best_icon(icons: &[Image<ColorRgba32>], size: u64) -> Option<&Image<ColorRgba32>> {
+    let extent = |icon: &&Image<ColorRgba32>| icon.res.0.max(icon.res.1);
+    icons
+        .iter()
+        .filter(|icon| extent(icon) >= size)
+        .min_by_key(extent)
+        .or_else(|| icons.iter().max_by_key(extent))
+}
@@ -1,7 +1,12 @@
-mod error;
 mod display;
+mod error;
+mod icon;
+mod shm;
+mod upload;
 mod window;
 
-pub use error::*;
 pub use display::*;
+pub use error::*;
+pub use icon::{best_icon, parse_net_wm_icon};
+pub use shm::UploadMethod;
 pub use window::*;
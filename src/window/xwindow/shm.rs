@@ -0,0 +1,127 @@
+use super::super::{color::Color, Image};
+use super::upload::{self, PixelLayout};
+use super::XError;
+
+/// The way images are transferred to the X server
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UploadMethod {
+    /// Through a shared memory segment of the MIT-SHM extension
+    Shm,
+    /// Through the connection with core `PutImage` requests
+    PutImage,
+}
+
+/// A shared memory segment attached to the X server,
+/// which holds a whole drawable in the server's pixel layout.
+/// The canvas is encoded into the segment, so nothing but the request itself
+/// is sent over the connection.
+pub struct ShmSegment<'a> {
+    con: &'a xcb::Connection,
+    seg: xcb::shm::Seg,
+    addr: *mut u8,
+    len: usize,
+    size: (u16, u16),
+}
+
+impl<'a> ShmSegment<'a> {
+    /// Creates and attaches a segment for a drawable of the given size and pixel layout.
+    /// Fails if the extension is missing or the server can not attach the segment,
+    /// e.g. because it runs on another machine.
+    pub fn new(
+        con: &'a xcb::Connection,
+        w: u16,
+        h: u16,
+        layout: &PixelLayout,
+    ) -> Result<Self, XError> {
+        let present = con
+            .get_extension_data(xcb::shm::id())
+            .is_some_and(|ext| ext.present());
+        if !present {
+            return Err(XError::XcbError(String::from("MIT-SHM extension missing")));
+        }
+        let len = (usize::from(w) * usize::from(h) * layout.bytes_per_pixel()).max(1);
+        let id = unsafe { libc::shmget(libc::IPC_PRIVATE, len, libc::IPC_CREAT | 0o600) };
+        if id < 0 {
+            return Err(XError::XcbError(format!(
+                "shmget failed [{}]",
+                std::io::Error::last_os_error()
+            )));
+        }
+        let addr = unsafe { libc::shmat(id, core::ptr::null(), 0) };
+        if addr as isize == -1 {
+            let err = std::io::Error::last_os_error();
+            unsafe { libc::shmctl(id, libc::IPC_RMID, core::ptr::null_mut()) };
+            return Err(XError::XcbError(format!("shmat failed [{}]", err)));
+        }
+        let seg = con.generate_id();
+        let attached = xcb::shm::attach_checked(con, seg, id as u32, true).request_check();
+        // the segment gets destroyed as soon as both sides detached it
+        unsafe { libc::shmctl(id, libc::IPC_RMID, core::ptr::null_mut()) };
+        if let Err(err) = attached {
+            unsafe { libc::shmdt(addr) };
+            return Err(err.into());
+        }
+        Ok(Self {
+            con,
+            seg,
+            addr: addr as *mut u8,
+            len,
+            size: (w, h),
+        })
+    }
+
+    fn data(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.addr, self.len) }
+    }
+
+    /// Uploads a part of an image to the same position of a drawable.
+    /// The image has to have the size of the segment and `layout` the one it was created for.
+    pub fn put_image<C: Color>(
+        &mut self,
+        drawable: xcb::Drawable,
+        gc: xcb::Gcontext,
+        layout: &PixelLayout,
+        img: &Image<C>,
+        (x, y, w, h): (u16, u16, u16, u16),
+    ) -> Result<(), XError> {
+        let rect = match upload::clip_rect(img, x, y, w, h) {
+            Some(rect) => rect,
+            None => return Ok(()),
+        };
+        let (total_w, total_h) = self.size;
+        let bytes = layout.bytes_per_pixel();
+        let stride = usize::from(total_w) * bytes;
+        let start = rect.1 * stride + rect.0 * bytes;
+        layout.encode_rect(img, rect, &mut self.data()[start..], stride);
+        // the checked request makes sure that the server finished reading
+        // before the segment is written again
+        xcb::shm::put_image_checked(
+            self.con,
+            drawable,
+            gc,
+            total_w,
+            total_h,
+            rect.0 as u16,
+            rect.1 as u16,
+            rect.2 as u16,
+            rect.3 as u16,
+            rect.0 as i16,
+            rect.1 as i16,
+            layout.depth(),
+            xcb::IMAGE_FORMAT_Z_PIXMAP as u8,
+            0, // send no completion event
+            self.seg,
+            0, // offset
+        )
+        .request_check()?;
+        Ok(())
+    }
+}
+
+impl<'a> Drop for ShmSegment<'a> {
+    fn drop(&mut self) {
+        xcb::shm::detach(self.con, self.seg);
+        self.con.flush();
+        unsafe { libc::shmdt(self.addr as *const libc::c_void) };
+    }
+}
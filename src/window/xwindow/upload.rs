@@ -0,0 +1,338 @@
+use super::super::color::{
+    Color, ColorArgb32, ColorBgra32, ColorRgb565, ColorRgba32, PackedColor, PixelFormat,
+};
+use super::super::Image;
+use super::XError;
+
+/// Describes how the server stores the pixels of a drawable
+#[derive(Debug, Clone, Copy)]
+pub struct PixelLayout {
+    depth: u8,
+    /// The size of a pixel in bytes, `2` or `4`
+    bytes: usize,
+    lsb_first: bool,
+    red: Channel,
+    green: Channel,
+    blue: Channel,
+    /// Only drawables of depth 32 carry an alpha channel
+    alpha: Option<Channel>,
+    /// The color type with the same representation in memory, if there is one
+    format: Option<PixelFormat>,
+}
+
+/// The position of a color channel in a pixel value
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    shift: u32,
+    bits: u32,
+}
+
+impl Channel {
+    /// Reads a mask of up to 8 contiguous bits
+    fn from_mask(mask: u32) -> Option<Self> {
+        if mask == 0 {
+            return None;
+        }
+        let shift = mask.trailing_zeros();
+        let bits = (mask >> shift).trailing_ones();
+        if bits <= 8 && mask >> shift >> bits == 0 {
+            Some(Self { shift, bits })
+        } else {
+            None
+        }
+    }
+
+    /// Scales an 8 bit value down to the width of the channel and moves it into place
+    fn place(&self, v: u8) -> u32 {
+        let max = (1u32 << self.bits) - 1;
+        ((u32::from(v) * max + 127) / 255) << self.shift
+    }
+}
+
+impl PixelLayout {
+    pub fn new(setup: &xcb::Setup, depth: u8, visual: &xcb::Visualtype) -> Result<Self, XError> {
+        let bits_per_pixel = setup
+            .pixmap_formats()
+            .find(|f| f.depth() == depth)
+            .map(|f| f.bits_per_pixel());
+        let lsb_first = setup.image_byte_order() == xcb::IMAGE_ORDER_LSB_FIRST as u8;
+        let masks = (visual.red_mask(), visual.green_mask(), visual.blue_mask());
+        Self::from_masks(depth, bits_per_pixel, lsb_first, masks)
+    }
+
+    /// The layout of a drawable of `depth` whose pixels take `bits_per_pixel` bits,
+    /// `masks` are the bits of the red, green and blue channel in a pixel value
+    fn from_masks(
+        depth: u8,
+        bits_per_pixel: Option<u8>,
+        lsb_first: bool,
+        masks: (u32, u32, u32),
+    ) -> Result<Self, XError> {
+        let bytes = match bits_per_pixel {
+            Some(16) => 2,
+            Some(32) => 4,
+            _ => {
+                return Err(XError::FormatError(format!(
+                    "depth {} is not stored with 16 or 32 bits per pixel",
+                    depth
+                )))
+            }
+        };
+        let (red, green, blue) = match (
+            Channel::from_mask(masks.0),
+            Channel::from_mask(masks.1),
+            Channel::from_mask(masks.2),
+        ) {
+            (Some(r), Some(g), Some(b)) => (r, g, b),
+            _ => {
+                return Err(XError::FormatError(format!(
+                    "visual masks {:#x}, {:#x}, {:#x} are not at most 8 bits wide",
+                    masks.0, masks.1, masks.2
+                )))
+            }
+        };
+        let alpha = if depth == 32 {
+            Channel::from_mask(!(masks.0 | masks.1 | masks.2))
+        } else {
+            None
+        };
+        // the usual layout of a 32 bit pixel value is 0xaarrggbb,
+        // where the unused byte of depth 24 can take the alpha channel's place
+        let argb = (0xff_0000, 0xff00, 0xff);
+        let format = match (bytes, masks, alpha.map(|a| a.shift)) {
+            (4, m, Some(24)) | (4, m, None) if m == argb && lsb_first => Some(PixelFormat::Bgra32),
+            (4, m, Some(24)) | (4, m, None) if m == argb => Some(PixelFormat::Argb32),
+            (2, (0xf800, 0x07e0, 0x1f), None) if lsb_first => Some(PixelFormat::Rgb565),
+            _ => None,
+        };
+        Ok(Self {
+            depth,
+            bytes,
+            lsb_first,
+            red,
+            green,
+            blue,
+            alpha,
+            format,
+        })
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// The number of bytes a pixel takes in an image sent to the server
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bytes
+    }
+
+    /// Converts a color to the one the server stores.
+    /// The color channels get premultiplied with alpha as expected by compositors,
+    /// drawables without alpha get opaque colors.
+    fn server_color<C: Color, T: Color>(&self, c: &C) -> T {
+        match self.alpha {
+            Some(_) => {
+                let a = u32::from(c.a8());
+                let premultiply = |v: u8| ((u32::from(v) * a + 127) / 255) as u8;
+                T::from_rgba8(
+                    premultiply(c.r8()),
+                    premultiply(c.g8()),
+                    premultiply(c.b8()),
+                    c.a8(),
+                )
+            }
+            None => T::from_rgba8(c.r8(), c.g8(), c.b8(), 255),
+        }
+    }
+
+    /// Encodes a single pixel into `out` by shifting its channels into place
+    fn encode<C: Color>(&self, c: &C, out: &mut [u8]) {
+        let c: ColorRgba32 = self.server_color(c);
+        let mut value = self.red.place(c.r) | self.green.place(c.g) | self.blue.place(c.b);
+        if let Some(alpha) = self.alpha {
+            value |= alpha.place(c.a);
+        }
+        if self.lsb_first {
+            out.copy_from_slice(&value.to_le_bytes()[..self.bytes]);
+        } else {
+            out.copy_from_slice(&value.to_be_bytes()[4 - self.bytes..]);
+        }
+    }
+
+    /// Encodes the rows `y..y + h` of the columns `x..x + w` of an image.
+    /// The rectangle has to lie inside of the image,
+    /// consecutive rows are placed `stride` bytes apart in the output.
+    pub fn encode_rect<C: Color>(
+        &self,
+        img: &Image<C>,
+        (x, y, w, h): (usize, usize, usize, usize),
+        out: &mut [u8],
+        stride: usize,
+    ) {
+        let width = img.res.0 as usize;
+        for (i, row) in (y..y + h).enumerate() {
+            let start = row * width + x;
+            let src = &img.data[start..start + w];
+            let dst = &mut out[i * stride..i * stride + w * self.bytes];
+            match self.format {
+                Some(PixelFormat::Bgra32) => self.encode_native::<C, ColorBgra32>(src, dst),
+                Some(PixelFormat::Argb32) => self.encode_native::<C, ColorArgb32>(src, dst),
+                Some(PixelFormat::Rgb565) => self.encode_native::<C, ColorRgb565>(src, dst),
+                _ => {
+                    for (c, px) in src.iter().zip(dst.chunks_exact_mut(self.bytes)) {
+                        self.encode(c, px);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Encodes pixels directly into the color type matching the server's memory layout
+    fn encode_native<C: Color, T: PackedColor>(&self, src: &[C], out: &mut [u8]) {
+        for (c, px) in src.iter().zip(out.chunks_exact_mut(T::SIZE)) {
+            self.server_color::<C, T>(c).write_bytes(px);
+        }
+    }
+}
+
+/// Clips a rectangle to the image, returns `None` if nothing is left
+pub fn clip_rect<C: Color>(
+    img: &Image<C>,
+    x: u16,
+    y: u16,
+    w: u16,
+    h: u16,
+) -> Option<(usize, usize, usize, usize)> {
+    let (width, height) = (img.res.0 as usize, img.res.1 as usize);
+    let (x, y) = (usize::from(x), usize::from(y));
+    let w = usize::from(w).min(width.saturating_sub(x));
+    let h = usize::from(h).min(height.saturating_sub(y));
+    if w == 0 || h == 0 {
+        None
+    } else {
+        Some((x, y, w, h))
+    }
+}
+
+/// Uploads a part of an image to the same position of a drawable using core `PutImage` requests.
+/// The rows are split into bands that fit into the maximum request length.
+pub fn put_image<C: Color>(
+    con: &xcb::Connection,
+    drawable: xcb::Drawable,
+    gc: xcb::Gcontext,
+    layout: &PixelLayout,
+    img: &Image<C>,
+    (x, y, w, h): (u16, u16, u16, u16),
+) -> Result<(), XError> {
+    let (x, y, w, h) = match clip_rect(img, x, y, w, h) {
+        Some(rect) => rect,
+        None => return Ok(()),
+    };
+    // the request length is given in units of 4 bytes, the PutImage header takes 24 bytes
+    let max_bytes = (con.get_maximum_request_length() as usize * 4).saturating_sub(24);
+    let row_bytes = w * layout.bytes_per_pixel();
+    let band = (max_bytes / row_bytes).max(1);
+    let mut buf = Vec::new();
+    for band_y in (y..y + h).step_by(band) {
+        let band_h = band.min(y + h - band_y);
+        buf.resize(row_bytes * band_h, 0);
+        layout.encode_rect(img, (x, band_y, w, band_h), &mut buf, row_bytes);
+        xcb::put_image(
+            con,
+            xcb::IMAGE_FORMAT_Z_PIXMAP as u8,
+            drawable,
+            gc,
+            w as u16,
+            band_h as u16,
+            x as i16,
+            band_y as i16,
+            0, // left pad
+            layout.depth(),
+            &buf,
+        )
+        .request_check()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RGB565: (u32, u32, u32) = (0xf800, 0x07e0, 0x1f);
+    const RGB888: (u32, u32, u32) = (0xff_0000, 0xff00, 0xff);
+
+    /// Encodes a red, a green and a half transparent white pixel
+    fn encode(layout: &PixelLayout) -> Vec<u8> {
+        let data = vec![
+            ColorRgba32::from_rgba8(255, 0, 0, 255),
+            ColorRgba32::from_rgba8(0, 255, 0, 255),
+            ColorRgba32::from_rgba8(255, 255, 255, 128),
+        ];
+        let img = Image::from_data(3, 1, data).unwrap();
+        let stride = 3 * layout.bytes_per_pixel();
+        let mut out = vec![0; stride];
+        layout.encode_rect(&img, (0, 0, 3, 1), &mut out, stride);
+        out
+    }
+
+    /// The first three bytes of each 32 bit pixel
+    fn channels(bytes: &[u8]) -> Vec<[u8; 3]> {
+        bytes
+            .chunks_exact(4)
+            .map(|px| [px[0], px[1], px[2]])
+            .collect()
+    }
+
+    #[test]
+    fn pixels_are_encoded_in_the_server_layout() {
+        let layout = PixelLayout::from_masks(24, Some(32), true, RGB888).unwrap();
+        assert_eq!(layout.format, Some(PixelFormat::Bgra32));
+        // the server ignores the fourth byte of depth 24
+        assert_eq!(
+            channels(&encode(&layout)),
+            [[0, 0, 255], [0, 255, 0], [255, 255, 255]]
+        );
+        // drawables of depth 32 get premultiplied alpha
+        let layout = PixelLayout::from_masks(32, Some(32), false, RGB888).unwrap();
+        assert_eq!(layout.format, Some(PixelFormat::Argb32));
+        assert_eq!(
+            encode(&layout),
+            [255, 255, 0, 0, 255, 0, 255, 0, 128, 128, 128, 128]
+        );
+        // layouts without a matching color type are encoded channel by channel
+        let layout =
+            PixelLayout::from_masks(24, Some(32), true, (0xff, 0xff00, 0xff_0000)).unwrap();
+        assert_eq!(layout.format, None);
+        assert_eq!(
+            encode(&layout),
+            [255, 0, 0, 0, 0, 255, 0, 0, 255, 255, 255, 0]
+        );
+    }
+
+    #[test]
+    fn sixteen_bit_visuals_are_supported() {
+        let layout = PixelLayout::from_masks(16, Some(16), true, RGB565).unwrap();
+        assert_eq!(
+            (layout.format, layout.bytes_per_pixel()),
+            (Some(PixelFormat::Rgb565), 2)
+        );
+        assert_eq!(encode(&layout), [0x00, 0xf8, 0xe0, 0x07, 0xff, 0xff]);
+        let layout = PixelLayout::from_masks(16, Some(16), false, RGB565).unwrap();
+        assert_eq!(layout.format, None);
+        assert_eq!(encode(&layout), [0xf8, 0x00, 0x07, 0xe0, 0xff, 0xff]);
+        let layout = PixelLayout::from_masks(15, Some(16), true, (0x7c00, 0x03e0, 0x1f)).unwrap();
+        assert_eq!(encode(&layout), [0x00, 0x7c, 0xe0, 0x03, 0xff, 0x7f]);
+    }
+
+    #[test]
+    fn other_layouts_are_rejected() {
+        assert!(PixelLayout::from_masks(24, Some(24), true, RGB888).is_err());
+        assert!(PixelLayout::from_masks(24, None, true, RGB888).is_err());
+        assert!(
+            PixelLayout::from_masks(30, Some(32), true, (0x3ff0_0000, 0xffc00, 0x3ff)).is_err()
+        );
+        assert!(PixelLayout::from_masks(24, Some(32), true, (0, 0xff00, 0xff)).is_err());
+        assert!(PixelLayout::from_masks(24, Some(32), true, (0xf0f0, 0xff00, 0xff)).is_err());
+    }
+}
@@ -1,24 +1,38 @@
 use super::super::{
-    color::{Color, PixelFormat},
+    color::{Color, ColorRgba32, PixelFormat},
     draw::DrawCommand,
-    event, Display, Surface, WindowBuilder, WindowType,
+    event, Display, Image, Surface, WindowBuilder, WindowType,
 };
+use super::shm::{ShmSegment, UploadMethod};
+use super::upload::{self, PixelLayout};
 use super::Display as XDisplay;
 use super::XError;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
 
 pub struct Window<'a> {
     dis: &'a XDisplay,
     screen: xcb::Screen<'a>,
     win: xcb::Window,
+    gc: xcb::Gcontext,
     transparency: bool,
     size: (u16, u16),
+    layout: PixelLayout,
+    /// The back buffer, which is copied to the window on present and on expose
+    pixmap: xcb::Pixmap,
+    /// The canvas all drawing happens on before it is uploaded to the back buffer
+    image: Image<ColorRgba32>,
+    /// Whether the canvas changed since the last upload
+    dirty: bool,
+    /// The shared memory segment used for uploads, if the server supports it
+    shm: Option<ShmSegment<'a>>,
 }
 
 impl<'a> Window<'a> {
     fn create_pixmap(&self, w: u64, h: u64) -> (xcb::VoidCookie, u32) {
         let con = self.dis.con();
         let pix = con.generate_id();
-        let depth = if self.transparency { 24 } else { 32 };
+        let depth = self.layout.depth();
         (
             xcb::create_pixmap(con, depth, pix, self.win, w as u16, h as u16),
             pix,
@@ -44,8 +58,11 @@ impl<'a> Window<'a> {
             xcb::create_gc(
                 self.dis.con(),
                 gc,
-                self.screen.root(),
-                &[(xcb::GC_FOREGROUND, self.screen.black_pixel())],
+                self.win,
+                &[
+                    (xcb::GC_FOREGROUND, self.screen.black_pixel()),
+                    (xcb::GC_GRAPHICS_EXPOSURES, 0),
+                ],
             ),
             gc,
         )
@@ -60,46 +77,167 @@ impl<'a> Window<'a> {
         }
     }
 
+    /// Repaints a region of the window from the back buffer
     fn redraw(&mut self, x: u16, y: u16, w: u16, h: u16) -> Result<(), XError> {
-        println!("redrawing {},{} - {},{}", x, y, w, h);
+        let (x, y) = (x as i16, y as i16);
+        xcb::copy_area(
+            self.dis.con(),
+            self.pixmap,
+            self.win,
+            self.gc,
+            x,
+            y,
+            x,
+            y,
+            w,
+            h,
+        )
+        .request_check()?;
+        self.dis.con().flush();
         Ok(())
     }
 
+    /// Uploads the canvas to the back buffer if it changed
+    fn upload(&mut self) -> Result<(), XError> {
+        if self.dirty {
+            let rect = (0, 0, self.size.0, self.size.1);
+            match self.shm {
+                Some(ref mut shm) => {
+                    shm.put_image(self.pixmap, self.gc, &self.layout, &self.image, rect)?
+                }
+                None => upload::put_image(
+                    self.dis.con(),
+                    self.pixmap,
+                    self.gc,
+                    &self.layout,
+                    &self.image,
+                    rect,
+                )?,
+            }
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Returns how the canvas is transferred to the X server
+    pub fn upload_method(&self) -> UploadMethod {
+        if self.shm.is_some() {
+            UploadMethod::Shm
+        } else {
+            UploadMethod::PutImage
+        }
+    }
+
+    /// Makes everything drawn so far visible
+    pub fn present(&mut self) -> Result<(), XError> {
+        self.upload()?;
+        let (w, h) = self.size;
+        self.redraw(0, 0, w, h)
+    }
+
+    /// Returns a surface to draw onto the window
+    pub fn surface<'s>(&'s mut self) -> WindowSurface<'s, 'a> {
+        WindowSurface { win: self }
+    }
+
+    /// Translates an X event, events that are handled internally or ignored result in `Err(None)`
+    fn translate_event(
+        &mut self,
+        event: &xcb::GenericEvent,
+    ) -> Result<event::Event, Option<XError>> {
+        match event.response_type() {
+            xcb::EXPOSE => unsafe {
+                let event: &xcb::ExposeEvent = xcb::cast_event(event);
+                Err(self
+                    .redraw(event.x(), event.y(), event.width(), event.height())
+                    .err())
+            },
+            xcb::BUTTON_PRESS => unsafe {
+                let event: &xcb::ButtonPressEvent = xcb::cast_event(event);
+                Self::translate_button(event.detail())
+                    .map(|b| (b, (event.event_x().into(), event.event_y().into())))
+            }
+            .map(|(b, s)| event::Event::ButtonDown(b, s))
+            .ok_or(None),
+            xcb::BUTTON_RELEASE => unsafe {
+                let event: &xcb::ButtonReleaseEvent = xcb::cast_event(event);
+                Self::translate_button(event.detail())
+                    .map(|b| (b, (event.event_x().into(), event.event_y().into())))
+            }
+            .map(|(b, s)| event::Event::ButtonUp(b, s))
+            .ok_or(None),
+            xcb::MOTION_NOTIFY => unsafe {
+                let event: &xcb::MotionNotifyEvent = xcb::cast_event(event);
+                Self::translate_button_mask(event.state())
+                    .map(|b| (b, (event.event_x().into(), event.event_y().into())))
+            }
+            .map(|(b, s)| event::Event::ButtonMove(b, s))
+            .ok_or(None),
+            _ => Err(None),
+        }
+    }
+
     pub fn fetch_event(&mut self) -> Option<Result<event::Event, Option<XError>>> {
         let con = self.dis.con();
-        Some(con.wait_for_event().ok_or(None).and_then(|event| {
-            let r = event.response_type();
-            match r {
-                xcb::EXPOSE => unsafe {
-                    let event: &xcb::ExposeEvent = xcb::cast_event(&event);
-                    Err(self
-                        .redraw(event.x(), event.y(), event.width(), event.height())
-                        .err())
-                },
-                xcb::BUTTON_PRESS => unsafe {
-                    let event: &xcb::ButtonPressEvent = xcb::cast_event(&event);
-                    Self::translate_button(event.detail())
-                        .map(|b| (b, (event.event_x().into(), event.event_y().into())))
-                }
-                .map(|(b, s)| event::Event::ButtonDown(b, s))
-                .ok_or(None),
-                xcb::BUTTON_RELEASE => unsafe {
-                    let event: &xcb::ButtonReleaseEvent = xcb::cast_event(&event);
-                    Self::translate_button(event.detail())
-                        .map(|b| (b, (event.event_x().into(), event.event_y().into())))
+        Some(
+            con.wait_for_event()
+                .ok_or(None)
+                .and_then(|event| self.translate_event(&event)),
+        )
+    }
+
+    /// Blocks until the connection can be read from or the timeout passed,
+    /// returns whether there is something to read
+    fn wait_readable(&self, timeout: Option<Duration>) -> Result<bool, XError> {
+        let mut fd = libc::pollfd {
+            fd: self.dis.con().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // rounded up, so that the timeout has passed when poll returns
+        let millis = timeout.map_or(-1, |t| {
+            t.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32
+        });
+        loop {
+            match unsafe { libc::poll(&mut fd, 1, millis) } {
+                -1 => {
+                    let err = std::io::Error::last_os_error();
+                    if err.kind() != std::io::ErrorKind::Interrupted {
+                        return Err(XError::XcbError(format!("poll failed [{}]", err)));
+                    }
                 }
-                .map(|(b, s)| event::Event::ButtonUp(b, s))
-                .ok_or(None),
-                xcb::MOTION_NOTIFY => unsafe {
-                    let event: &xcb::MotionNotifyEvent = xcb::cast_event(&event);
-                    Self::translate_button_mask(event.state())
-                        .map(|b| (b, (event.event_x().into(), event.event_y().into())))
+                n => return Ok(n > 0),
+            }
+        }
+    }
+
+    /// Waits for the next event, but no longer than `timeout` if one is given.
+    /// Returns `Ok(None)` if the time ran out.
+    pub fn wait_event(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Option<event::Event>, XError> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let con = self.dis.con();
+            con.flush();
+            let event = match con.poll_for_event() {
+                Some(event) => event,
+                None => {
+                    con.has_error().map_err(XError::ConnError)?;
+                    let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+                    if remaining == Some(Duration::ZERO) || !self.wait_readable(remaining)? {
+                        return Ok(None);
+                    }
+                    continue;
                 }
-                .map(|(b, s)| event::Event::ButtonMove(b, s))
-                .ok_or(None),
-                _ => Err(None),
+            };
+            match self.translate_event(&event) {
+                Ok(event) => return Ok(Some(event)),
+                Err(Some(err)) => return Err(err),
+                Err(None) => (),
             }
-        }))
+        }
     }
 }
 
@@ -165,6 +303,7 @@ impl<'a> super::super::Window<'a, XDisplay> for Window<'a> {
                 | xcb::EVENT_MASK_BUTTON_RELEASE
                 | xcb::EVENT_MASK_BUTTON_MOTION,
         ));
+        let visual = visual.unwrap_or_else(|| screen.root_visual());
         xcb::create_window(
             con,
             depth_val,
@@ -176,10 +315,14 @@ impl<'a> super::super::Window<'a, XDisplay> for Window<'a> {
             size.1,
             0, // border width
             xcb::xproto::WINDOW_CLASS_INPUT_OUTPUT as u16,
-            visual.unwrap_or_else(|| screen.root_visual()),
+            visual,
             &cw_values,
         )
         .request_check()?;
+        let layout = dis
+            .get_visual(screen_id, visual)
+            .ok_or_else(|| XError::ScreenError(String::from("could not find window visual")))
+            .and_then(|(depth, vis)| PixelLayout::new(&con.get_setup(), depth, &vis))?;
 
         match window_type {
             WindowType::Normal => (),
@@ -204,16 +347,55 @@ impl<'a> super::super::Window<'a, XDisplay> for Window<'a> {
             }
         }
 
-        xcb::map_window(con, win).request_check()?;
-        con.flush();
-
-        Ok(Self {
+        let mut window = Self {
             dis,
             screen,
             transparency,
             win,
+            gc: xcb::NONE,
             size,
-        })
+            layout,
+            pixmap: xcb::NONE,
+            // the area of an u16 sized window always fits into an usize
+            image: Image::new(size.0.into(), size.1.into()).unwrap(),
+            dirty: true,
+            // falls back to core requests if the segment can not be attached
+            shm: if dis.is_local() {
+                ShmSegment::new(con, size.0, size.1, &layout).ok()
+            } else {
+                None
+            },
+        };
+        let (cookie, gc) = window.create_gc();
+        cookie.request_check()?;
+        window.gc = gc;
+        let (cookie, pixmap) = window.create_pixmap(size.0.into(), size.1.into());
+        cookie.request_check()?;
+        window.pixmap = pixmap;
+        window.upload()?;
+
+        xcb::map_window(con, win).request_check()?;
+        con.flush();
+
+        Ok(window)
+    }
+
+    fn wait_event(&mut self, timeout: Option<Duration>) -> Result<Option<event::Event>, XError> {
+        Window::wait_event(self, timeout)
+    }
+
+    fn draw(&mut self, draw: DrawCommand<ColorRgba32>) -> Result<(), XError> {
+        self.surface().draw(draw)
+    }
+
+    fn canvas(&mut self) -> &mut Image<ColorRgba32> {
+        // whoever borrows the canvas may change it
+        self.dirty = true;
+        &mut self.image
+    }
+
+    fn present(&mut self) -> Result<(), XError> {
+        Window::present(self)
     }
 }
 
@@ -239,8 +421,28 @@ where
             PixelFormat::Rgb24
         }
     }
-    fn draw(&self, draw: DrawCommand<C>) -> Result<(), Self::Error> {
-        todo!();
+    /// Draws onto the canvas, the result becomes visible on `present`
+    fn draw(&mut self, draw: DrawCommand<C>) -> Result<(), Self::Error> {
+        self.win.image.draw(draw.map_color(&Color::as_rgba32))?;
+        self.win.dirty = true;
+        Ok(())
+    }
+    fn present(&mut self) -> Result<(), Self::Error> {
+        self.win.present()
+    }
+}
+
+impl<'a> Drop for Window<'a> {
+    fn drop(&mut self) {
+        let con = self.dis.con();
+        // construction may have failed before the resources were created
+        if self.pixmap != xcb::NONE {
+            xcb::free_pixmap(con, self.pixmap);
+        }
+        if self.gc != xcb::NONE {
+            xcb::free_gc(con, self.gc);
+        }
+        con.flush();
     }
 }
 
@@ -257,3 +459,40 @@ impl<'a> Iterator for Window<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::{Display as _, Surface};
+    use super::*;
+
+    /// Connects to the display of `$DISPLAY` under another name,
+    /// the name decides whether the server is treated as local
+    fn upload_method_as(name: &str) -> UploadMethod {
+        std::env::set_var("DISPLAY", name);
+        let dis = XDisplay::new().expect("can not connect to the X server");
+        let mut win: Window = dis.new_window_builder().size(32, 16).build().unwrap();
+        // the chosen path uploads and presents without protocol errors
+        win.surface()
+            .draw(DrawCommand::Clear(ColorRgba32::from_rgba8(
+                0x20, 0x40, 0x60, 0xff,
+            )))
+            .unwrap();
+        win.present().unwrap();
+        win.upload_method()
+    }
+
+    /// Needs a running X server that also listens on TCP,
+    /// e.g. `xvfb-run -s "-listen tcp" cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn local_servers_get_shared_memory_uploads() {
+        let display = std::env::var("DISPLAY").expect("no X server, run the test under Xvfb");
+        let number = &display[display.rfind(':').expect("malformed $DISPLAY")..];
+        let methods = (
+            upload_method_as(number),
+            upload_method_as(&format!("localhost{}", number)),
+        );
+        std::env::set_var("DISPLAY", &display);
+        assert_eq!(methods, (UploadMethod::Shm, UploadMethod::PutImage));
+    }
+}